@@ -0,0 +1,1001 @@
+//! Color glyphs: layered `COLR` / `CPAL` glyphs (version 0 and 1), embedded PNG
+//! bitmaps (`sbix`, `CBDT` / `CBLC`) and SVG documents (`SVG ` table)
+
+use crate::text_shaping::GlyphOutline;
+use allsorts::tables::FontTableProvider;
+use std::num::NonZeroU16;
+
+/// Palette entry index that stands for the current text color
+const FOREGROUND_PALETTE_INDEX: u16 = 0xFFFF;
+
+/// Maximum nesting of COLRv1 paints, protects against cycles in broken fonts
+const MAX_PAINT_DEPTH: usize = 64;
+
+/// 8-bit RGBA color
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub struct ColorU {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+/// Color of a layer or a gradient stop of a color glyph
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C, u8)]
+pub enum ColorGlyphColor {
+    /// The color of the text (`color` CSS property), multiplied with the alpha
+    CurrentColor { alpha: f32 },
+    /// Color from the palette, the alpha is already applied
+    Palette(ColorU),
+}
+
+/// What kind of data a glyph is drawn from, in the order of preference
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub enum GlyphKind {
+    /// COLRv1 glyph: tree of paints (gradients, transforms, compositing)
+    ColorPaint,
+    /// COLRv0 glyph: outlines of other glyphs, filled with a solid color each
+    ColorLayers,
+    /// SVG document from the `SVG ` table
+    Svg,
+    /// PNG image from the `sbix` or `CBDT` table
+    Bitmap,
+    /// Regular (monochrome) outline
+    Outline,
+}
+
+/// Data to draw a glyph with, see `ParsedFont::get_glyph_data`
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C, u8)]
+pub enum GlyphData {
+    ColorPaint(ColorPaint),
+    ColorLayers(Vec<ColorGlyphLayer>),
+    Svg(GlyphSvg),
+    Bitmap(GlyphBitmap),
+    Outline(GlyphOutline),
+}
+
+/// Single layer of a COLRv0 glyph, layers are drawn bottom to top
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct ColorGlyphLayer {
+    /// Glyph whose outline is filled with the color
+    pub glyph_index: u16,
+    pub color: ColorGlyphColor,
+}
+
+/// Paint of a COLRv1 glyph, all coordinates are in unscaled font units (y pointing up)
+///
+/// `PaintColrGlyph` references are already resolved and all transformation
+/// paints (translate, scale, rotate, skew) are reduced to a `ColorTransform`
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C, u8)]
+pub enum ColorPaint {
+    /// Paints drawn on top of each other (bottom to top)
+    Layers(Vec<ColorPaint>),
+    Solid(ColorGlyphColor),
+    LinearGradient(ColorLinearGradient),
+    RadialGradient(ColorRadialGradient),
+    SweepGradient(ColorSweepGradient),
+    /// Fills the outline of the glyph with the paint
+    Glyph {
+        glyph_index: u16,
+        paint: Box<ColorPaint>,
+    },
+    Transform {
+        transform: ColorTransform,
+        paint: Box<ColorPaint>,
+    },
+    /// Draws the `source` onto the `backdrop` with the compositing / blend mode
+    Composite {
+        source: Box<ColorPaint>,
+        mode: ColorCompositeMode,
+        backdrop: Box<ColorPaint>,
+    },
+}
+
+/// Gradient along the line p0 → p1, rotated so that it is perpendicular to p0 → p2
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct ColorLinearGradient {
+    pub color_line: ColorLine,
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+/// Gradient between the circle (x0, y0, r0) and the circle (x1, y1, r1)
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct ColorRadialGradient {
+    pub color_line: ColorLine,
+    pub x0: f32,
+    pub y0: f32,
+    pub r0: f32,
+    pub x1: f32,
+    pub y1: f32,
+    pub r1: f32,
+}
+
+/// Gradient around the center, angles are in degrees (counter-clockwise)
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct ColorSweepGradient {
+    pub color_line: ColorLine,
+    pub center_x: f32,
+    pub center_y: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct ColorLine {
+    pub extend: ColorExtend,
+    pub stops: Vec<ColorStop>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct ColorStop {
+    /// Position on the color line (0.0 = start, 1.0 = end, can be outside of that range)
+    pub offset: f32,
+    pub color: ColorGlyphColor,
+}
+
+/// How a gradient continues outside of its color line
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum ColorExtend {
+    #[default]
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+/// Affine transform: x' = xx * x + xy * y + dx, y' = yx * x + yy * y + dy
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct ColorTransform {
+    pub xx: f32,
+    pub yx: f32,
+    pub xy: f32,
+    pub yy: f32,
+    pub dx: f32,
+    pub dy: f32,
+}
+
+impl ColorTransform {
+    pub const IDENTITY: Self = Self {
+        xx: 1.0,
+        yx: 0.0,
+        xy: 0.0,
+        yy: 1.0,
+        dx: 0.0,
+        dy: 0.0,
+    };
+
+    pub const fn translate(dx: f32, dy: f32) -> Self {
+        Self {
+            dx,
+            dy,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub const fn scale(sx: f32, sy: f32) -> Self {
+        Self {
+            xx: sx,
+            yy: sy,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Counter-clockwise rotation (in degrees)
+    pub fn rotate(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self {
+            xx: cos,
+            yx: sin,
+            xy: -sin,
+            yy: cos,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Skew along the x and y axis (in degrees, counter-clockwise)
+    pub fn skew(x_degrees: f32, y_degrees: f32) -> Self {
+        Self {
+            yx: y_degrees.to_radians().tan(),
+            xy: -x_degrees.to_radians().tan(),
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Returns the transform that first applies `other`, then `self`
+    pub fn multiply(&self, other: &Self) -> Self {
+        Self {
+            xx: self.xx * other.xx + self.xy * other.yx,
+            yx: self.yx * other.xx + self.yy * other.yx,
+            xy: self.xx * other.xy + self.xy * other.yy,
+            yy: self.yx * other.xy + self.yy * other.yy,
+            dx: self.xx * other.dx + self.xy * other.dy + self.dx,
+            dy: self.yx * other.dx + self.yy * other.dy + self.dy,
+        }
+    }
+
+    /// Applies the transform with (center_x, center_y) as the origin
+    pub fn around_center(&self, center_x: f32, center_y: f32) -> Self {
+        Self::translate(center_x, center_y)
+            .multiply(self)
+            .multiply(&Self::translate(-center_x, -center_y))
+    }
+
+    pub fn transform_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            self.xx * x + self.xy * y + self.dx,
+            self.yx * x + self.yy * y + self.dy,
+        )
+    }
+}
+
+/// Porter-Duff compositing and blend modes of `PaintComposite`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub enum ColorCompositeMode {
+    Clear,
+    Source,
+    Destination,
+    SourceOver,
+    DestinationOver,
+    SourceIn,
+    DestinationIn,
+    SourceOut,
+    DestinationOut,
+    SourceAtop,
+    DestinationAtop,
+    Xor,
+    Plus,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Multiply,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl ColorCompositeMode {
+    fn from_u8(mode: u8) -> Option<Self> {
+        use self::ColorCompositeMode::*;
+        const MODES: [ColorCompositeMode; 28] = [
+            Clear,
+            Source,
+            Destination,
+            SourceOver,
+            DestinationOver,
+            SourceIn,
+            DestinationIn,
+            SourceOut,
+            DestinationOut,
+            SourceAtop,
+            DestinationAtop,
+            Xor,
+            Plus,
+            Screen,
+            Overlay,
+            Darken,
+            Lighten,
+            ColorDodge,
+            ColorBurn,
+            HardLight,
+            SoftLight,
+            Difference,
+            Exclusion,
+            Multiply,
+            Hue,
+            Saturation,
+            Color,
+            Luminosity,
+        ];
+        MODES.get(mode as usize).copied()
+    }
+}
+
+/// PNG image of a glyph from the strike closest to the requested size
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub struct GlyphBitmap {
+    /// Offset of the bottom left corner of the image from the glyph origin
+    /// (in pixels of the strike, y pointing up)
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    /// Size of the strike, the image has to be scaled by `get_scale`
+    pub pixels_per_em: u16,
+    pub png_data: Vec<u8>,
+}
+
+impl GlyphBitmap {
+    /// Returns the factor to scale the image with for the given font size
+    pub fn get_scale(&self, font_size_px: f32) -> f32 {
+        font_size_px / self.pixels_per_em.max(1) as f32
+    }
+}
+
+/// SVG document that contains the glyph (as the element with the id `glyph<index>`),
+/// a single document can contain the drawings of multiple glyphs
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub struct GlyphSvg {
+    pub document: Vec<u8>,
+    /// The document is gzip-compressed (SVGZ) and has to be decompressed first
+    pub is_compressed: bool,
+}
+
+/// Raw data of the color tables of a font, parsed on demand
+#[derive(Debug, Clone, Default)]
+pub struct ColorGlyphTables {
+    pub num_glyphs: u16,
+    pub colr: Option<Box<[u8]>>,
+    pub cpal: Option<Box<[u8]>>,
+    pub sbix: Option<Box<[u8]>>,
+    pub cblc: Option<Box<[u8]>>,
+    pub cbdt: Option<Box<[u8]>>,
+    pub svg: Option<Box<[u8]>>,
+}
+
+impl ColorGlyphTables {
+    pub(crate) fn new<P: FontTableProvider>(provider: &P, num_glyphs: u16) -> Self {
+        use allsorts::tag;
+
+        let table = |tag: u32| {
+            provider
+                .table_data(tag)
+                .ok()
+                .flatten()
+                .map(|data| data.into_owned().into_boxed_slice())
+        };
+
+        Self {
+            num_glyphs,
+            colr: table(tag::COLR),
+            cpal: table(tag::CPAL),
+            sbix: table(tag::SBIX),
+            cblc: table(tag::CBLC),
+            cbdt: table(tag::CBDT),
+            svg: table(tag::SVG),
+        }
+    }
+
+    /// Returns the kind of color data of the glyph (`None` if the glyph is not a color glyph)
+    pub fn get_glyph_kind(&self, glyph_index: u16) -> Option<GlyphKind> {
+        if self.get_base_paint_offset(glyph_index).is_some() {
+            Some(GlyphKind::ColorPaint)
+        } else if self.get_base_glyph_layers(glyph_index).is_some() {
+            Some(GlyphKind::ColorLayers)
+        } else if self.get_svg(glyph_index).is_some() {
+            Some(GlyphKind::Svg)
+        } else if self.get_bitmap(glyph_index, u16::MAX).is_some() {
+            Some(GlyphKind::Bitmap)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the layers of a COLRv0 glyph
+    pub fn get_color_layers(
+        &self,
+        glyph_index: u16,
+        palette_index: usize,
+    ) -> Option<Vec<ColorGlyphLayer>> {
+        let colr = self.colr.as_deref()?;
+        let (first_layer, num_layers) = self.get_base_glyph_layers(glyph_index)?;
+        let layer_records_offset = read_u32(colr, 8)? as usize;
+        (first_layer..first_layer + num_layers)
+            .map(|layer| {
+                let offset = layer_records_offset + layer * 4;
+                Some(ColorGlyphLayer {
+                    glyph_index: read_u16(colr, offset)?,
+                    color: self.get_color(palette_index, read_u16(colr, offset + 2)?, 1.0),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the paint tree of a COLRv1 glyph
+    pub fn get_color_paint(&self, glyph_index: u16, palette_index: usize) -> Option<ColorPaint> {
+        let offset = self.get_base_paint_offset(glyph_index)?;
+        self.parse_paint(offset, palette_index, 0)
+    }
+
+    /// Returns the PNG image of the glyph from the strike that fits `pixels_per_em` best
+    pub fn get_bitmap(&self, glyph_index: u16, pixels_per_em: u16) -> Option<GlyphBitmap> {
+        let glyph_id = ttf_parser::GlyphId(glyph_index);
+        let image = match (
+            self.sbix.as_deref(),
+            self.cblc.as_deref(),
+            self.cbdt.as_deref(),
+        ) {
+            (Some(sbix), _, _) => {
+                ttf_parser::sbix::Table::parse(NonZeroU16::new(self.num_glyphs)?, sbix)?
+                    .best_strike(pixels_per_em)?
+                    .get(glyph_id)?
+            }
+            (None, Some(cblc), Some(cbdt)) => {
+                ttf_parser::cbdt::Table::parse(ttf_parser::cblc::Table::parse(cblc)?, cbdt)?
+                    .get(glyph_id, pixels_per_em)?
+            }
+            _ => return None,
+        };
+
+        Some(GlyphBitmap {
+            x: image.x,
+            y: image.y,
+            width: image.width,
+            height: image.height,
+            pixels_per_em: image.pixels_per_em,
+            png_data: image.data.to_vec(),
+        })
+    }
+
+    /// Returns the SVG document that contains the glyph
+    pub fn get_svg(&self, glyph_index: u16) -> Option<GlyphSvg> {
+        let svg = ttf_parser::svg::Table::parse(self.svg.as_deref()?)?;
+        let document = svg.documents.find(ttf_parser::GlyphId(glyph_index))?;
+        Some(GlyphSvg {
+            document: document.to_vec(),
+            is_compressed: document.starts_with(&[0x1F, 0x8B]),
+        })
+    }
+
+    /// Returns (first layer index, number of layers) of a COLRv0 base glyph record
+    fn get_base_glyph_layers(&self, glyph_index: u16) -> Option<(usize, usize)> {
+        let colr = self.colr.as_deref()?;
+        let num_records = read_u16(colr, 2)? as usize;
+        let records_offset = read_u32(colr, 4)? as usize;
+        let record = find_glyph_record(colr, records_offset, num_records, 6, glyph_index)?;
+        let num_layers = read_u16(colr, record + 4)? as usize;
+        (num_layers > 0).then_some((read_u16(colr, record + 2)? as usize, num_layers))
+    }
+
+    /// Returns the offset (from the start of the `COLR` table) of the root paint of a COLRv1 glyph
+    fn get_base_paint_offset(&self, glyph_index: u16) -> Option<usize> {
+        let colr = self.colr.as_deref()?;
+        if read_u16(colr, 0)? < 1 {
+            return None;
+        }
+        let list_offset = read_u32(colr, 14)? as usize;
+        if list_offset == 0 {
+            return None;
+        }
+        let num_records = read_u32(colr, list_offset)? as usize;
+        let record = find_glyph_record(colr, list_offset + 4, num_records, 6, glyph_index)?;
+        Some(list_offset + read_u32(colr, record + 2)? as usize)
+    }
+
+    fn get_color(&self, palette_index: usize, entry_index: u16, alpha: f32) -> ColorGlyphColor {
+        if entry_index == FOREGROUND_PALETTE_INDEX {
+            return ColorGlyphColor::CurrentColor { alpha };
+        }
+
+        let color = self.cpal.as_deref().and_then(|cpal| {
+            let num_palettes = read_u16(cpal, 4)? as usize;
+            let palette_index = if palette_index < num_palettes {
+                palette_index
+            } else {
+                0
+            };
+            let color_records_offset = read_u32(cpal, 8)? as usize;
+            let first_record = read_u16(cpal, 12 + palette_index * 2)? as usize;
+            let record = color_records_offset + (first_record + entry_index as usize) * 4;
+            // color records are stored as BGRA
+            let bgra = cpal.get(record..record + 4)?;
+            Some(ColorU {
+                r: bgra[2],
+                g: bgra[1],
+                b: bgra[0],
+                a: (bgra[3] as f32 * alpha.clamp(0.0, 1.0)).round() as u8,
+            })
+        });
+
+        match color {
+            Some(color) => ColorGlyphColor::Palette(color),
+            None => ColorGlyphColor::CurrentColor { alpha },
+        }
+    }
+
+    fn parse_paint(&self, offset: usize, palette_index: usize, depth: usize) -> Option<ColorPaint> {
+        if depth > MAX_PAINT_DEPTH {
+            return None;
+        }
+
+        let colr = self.colr.as_deref()?;
+        // variable paints (`PaintVar*`) have the same layout as the static paints
+        // (+ variation indices), font variations are not supported, so only the
+        // default values are used
+        let format = read_u8(colr, offset)?;
+
+        let child = |offset_field: usize| {
+            let child_offset = offset + read_u24(colr, offset + offset_field)? as usize;
+            self.parse_paint(child_offset, palette_index, depth + 1)
+                .map(Box::new)
+        };
+        let fword = |field: usize| read_i16(colr, offset + field).map(f32::from);
+        let f2dot14 = |field: usize| read_f2dot14(colr, offset + field);
+        let transformed = |transform: ColorTransform| {
+            Some(ColorPaint::Transform {
+                transform,
+                paint: child(1)?,
+            })
+        };
+
+        match format {
+            1 => {
+                let num_layers = read_u8(colr, offset + 1)? as usize;
+                let first_layer = read_u32(colr, offset + 2)? as usize;
+                let layer_list = read_u32(colr, 18)? as usize;
+                let layers = (first_layer..first_layer + num_layers)
+                    .map(|layer| {
+                        let paint_offset = read_u32(colr, layer_list + 4 + layer * 4)? as usize;
+                        self.parse_paint(layer_list + paint_offset, palette_index, depth + 1)
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(ColorPaint::Layers(layers))
+            }
+            2 | 3 => Some(ColorPaint::Solid(self.get_color(
+                palette_index,
+                read_u16(colr, offset + 1)?,
+                f2dot14(3)?,
+            ))),
+            4 | 5 => Some(ColorPaint::LinearGradient(ColorLinearGradient {
+                color_line: self.parse_color_line(offset, format == 5, palette_index)?,
+                x0: fword(4)?,
+                y0: fword(6)?,
+                x1: fword(8)?,
+                y1: fword(10)?,
+                x2: fword(12)?,
+                y2: fword(14)?,
+            })),
+            6 | 7 => Some(ColorPaint::RadialGradient(ColorRadialGradient {
+                color_line: self.parse_color_line(offset, format == 7, palette_index)?,
+                x0: fword(4)?,
+                y0: fword(6)?,
+                r0: read_u16(colr, offset + 8)? as f32,
+                x1: fword(10)?,
+                y1: fword(12)?,
+                r1: read_u16(colr, offset + 14)? as f32,
+            })),
+            8 | 9 => Some(ColorPaint::SweepGradient(ColorSweepGradient {
+                color_line: self.parse_color_line(offset, format == 9, palette_index)?,
+                center_x: fword(4)?,
+                center_y: fword(6)?,
+                start_angle: f2dot14(8)? * 180.0,
+                end_angle: f2dot14(10)? * 180.0,
+            })),
+            10 => Some(ColorPaint::Glyph {
+                glyph_index: read_u16(colr, offset + 4)?,
+                paint: child(1)?,
+            }),
+            11 => {
+                let base_paint = self.get_base_paint_offset(read_u16(colr, offset + 1)?)?;
+                self.parse_paint(base_paint, palette_index, depth + 1)
+            }
+            12 | 13 => {
+                let matrix = offset + read_u24(colr, offset + 4)? as usize;
+                let fixed = |index: usize| read_fixed(colr, matrix + index * 4);
+                transformed(ColorTransform {
+                    xx: fixed(0)?,
+                    yx: fixed(1)?,
+                    xy: fixed(2)?,
+                    yy: fixed(3)?,
+                    dx: fixed(4)?,
+                    dy: fixed(5)?,
+                })
+            }
+            14 | 15 => transformed(ColorTransform::translate(fword(4)?, fword(6)?)),
+            16 | 17 => transformed(ColorTransform::scale(f2dot14(4)?, f2dot14(6)?)),
+            18 | 19 => transformed(
+                ColorTransform::scale(f2dot14(4)?, f2dot14(6)?)
+                    .around_center(fword(8)?, fword(10)?),
+            ),
+            20 | 21 => transformed(ColorTransform::scale(f2dot14(4)?, f2dot14(4)?)),
+            22 | 23 => transformed(
+                ColorTransform::scale(f2dot14(4)?, f2dot14(4)?).around_center(fword(6)?, fword(8)?),
+            ),
+            24 | 25 => transformed(ColorTransform::rotate(f2dot14(4)? * 180.0)),
+            26 | 27 => transformed(
+                ColorTransform::rotate(f2dot14(4)? * 180.0).around_center(fword(6)?, fword(8)?),
+            ),
+            28 | 29 => transformed(ColorTransform::skew(
+                f2dot14(4)? * 180.0,
+                f2dot14(6)? * 180.0,
+            )),
+            30 | 31 => transformed(
+                ColorTransform::skew(f2dot14(4)? * 180.0, f2dot14(6)? * 180.0)
+                    .around_center(fword(8)?, fword(10)?),
+            ),
+            32 => Some(ColorPaint::Composite {
+                source: child(1)?,
+                mode: ColorCompositeMode::from_u8(read_u8(colr, offset + 4)?)?,
+                backdrop: child(5)?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Parses the color line of the gradient paint at `paint_offset`
+    fn parse_color_line(
+        &self,
+        paint_offset: usize,
+        is_variable: bool,
+        palette_index: usize,
+    ) -> Option<ColorLine> {
+        let colr = self.colr.as_deref()?;
+        let offset = paint_offset + read_u24(colr, paint_offset + 1)? as usize;
+        let extend = match read_u8(colr, offset)? {
+            1 => ColorExtend::Repeat,
+            2 => ColorExtend::Reflect,
+            _ => ColorExtend::Pad,
+        };
+        let num_stops = read_u16(colr, offset + 1)? as usize;
+        // variable color stops have an additional u32 variation index
+        let stop_size = if is_variable { 10 } else { 6 };
+        let stops = (0..num_stops)
+            .map(|i| {
+                let stop = offset + 3 + i * stop_size;
+                Some(ColorStop {
+                    offset: read_f2dot14(colr, stop)?,
+                    color: self.get_color(
+                        palette_index,
+                        read_u16(colr, stop + 2)?,
+                        read_f2dot14(colr, stop + 4)?,
+                    ),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(ColorLine { extend, stops })
+    }
+}
+
+/// Binary search for the record of a glyph in an array of records sorted by glyph index
+/// (the glyph index is the first field of each record), returns the offset of the record
+fn find_glyph_record(
+    data: &[u8],
+    records_offset: usize,
+    num_records: usize,
+    record_size: usize,
+    glyph_index: u16,
+) -> Option<usize> {
+    let (mut low, mut high) = (0, num_records);
+    while low < high {
+        let mid = (low + high) / 2;
+        let record = records_offset + mid * record_size;
+        match read_u16(data, record)?.cmp(&glyph_index) {
+            std::cmp::Ordering::Less => low = mid + 1,
+            std::cmp::Ordering::Greater => high = mid,
+            std::cmp::Ordering::Equal => return Some(record),
+        }
+    }
+    None
+}
+
+fn read_u8(data: &[u8], offset: usize) -> Option<u8> {
+    data.get(offset).copied()
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let b = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|v| v as i16)
+}
+
+fn read_u24(data: &[u8], offset: usize) -> Option<u32> {
+    let b = data.get(offset..offset + 3)?;
+    Some(u32::from_be_bytes([0, b[0], b[1], b[2]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let b = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_f2dot14(data: &[u8], offset: usize) -> Option<f32> {
+    read_i16(data, offset).map(|v| v as f32 / 16384.0)
+}
+
+fn read_fixed(data: &[u8], offset: usize) -> Option<f32> {
+    read_u32(data, offset).map(|v| v as i32 as f32 / 65536.0)
+}
+
+#[test]
+fn test_color_glyphs() {
+    use crate::test_font::{push_i16, push_u16, push_u32};
+
+    fn push_u24(bytes: &mut Vec<u8>, value: usize) {
+        bytes.extend_from_slice(&(value as u32).to_be_bytes()[1..]);
+    }
+    fn set_u32(bytes: &mut [u8], at: usize, value: usize) {
+        bytes[at..at + 4].copy_from_slice(&(value as u32).to_be_bytes());
+    }
+    fn push_color_stop(bytes: &mut Vec<u8>, offset: u16, entry: u16, is_variable: bool) {
+        push_u16(bytes, offset);
+        push_u16(bytes, entry);
+        push_u16(bytes, 0x4000); // alpha 1.0
+        if is_variable {
+            push_u32(bytes, 0xFFFF_FFFF); // variation index
+        }
+    }
+
+    // two palettes with two colors each (stored as BGRA)
+    let mut cpal = Vec::new();
+    for value in [0, 2, 2, 4] {
+        push_u16(&mut cpal, value); // version, entries, palettes, color records
+    }
+    push_u32(&mut cpal, 16);
+    push_u16(&mut cpal, 0);
+    push_u16(&mut cpal, 2);
+    for bgra in [
+        [0, 0, 255, 255],
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [255; 4],
+    ] {
+        cpal.extend_from_slice(&bgra);
+    }
+    let red = ColorU {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+    let blue = ColorU {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+    let green = ColorU {
+        r: 0,
+        g: 255,
+        b: 0,
+        a: 255,
+    };
+    let white = ColorU {
+        r: 255,
+        g: 255,
+        b: 255,
+        a: 255,
+    };
+
+    let mut colr = Vec::new();
+    push_u16(&mut colr, 1); // version
+    push_u16(&mut colr, 1); // v0 base glyph records
+    push_u32(&mut colr, 34);
+    push_u32(&mut colr, 40); // v0 layer records
+    push_u16(&mut colr, 2);
+    colr.extend_from_slice(&[0; 20]); // base glyph list, layer list, clip list, variations
+
+    // COLRv0: glyph 1 = glyph 2 in the first color + glyph 3 in the text color
+    for value in [1, 0, 2, 2, 0, 3, FOREGROUND_PALETTE_INDEX] {
+        push_u16(&mut colr, value);
+    }
+
+    let base_glyph_list = colr.len();
+    set_u32(&mut colr, 14, base_glyph_list);
+    push_u32(&mut colr, 4);
+    colr.extend_from_slice(&[0; 4 * 6]);
+    let layer_list = colr.len();
+    set_u32(&mut colr, 18, layer_list);
+    push_u32(&mut colr, 2);
+    colr.extend_from_slice(&[0; 2 * 4]);
+
+    // glyph 5: PaintColrLayers (layer 0 and 1)
+    let colr_layers = colr.len();
+    colr.extend_from_slice(&[1, 2]);
+    push_u32(&mut colr, 0);
+    // layer 0: PaintGlyph (glyph 2) -> PaintSolid (second color, alpha 0.5)
+    let layer_0 = colr.len();
+    colr.push(10);
+    push_u24(&mut colr, 6);
+    push_u16(&mut colr, 2);
+    colr.push(2);
+    push_u16(&mut colr, 1);
+    push_u16(&mut colr, 0x2000);
+    // layer 1: PaintTranslate (10, 20) -> PaintGlyph (glyph 3) -> PaintLinearGradient
+    let layer_1 = colr.len();
+    colr.push(14);
+    push_u24(&mut colr, 8);
+    push_i16(&mut colr, 10);
+    push_i16(&mut colr, 20);
+    colr.push(10);
+    push_u24(&mut colr, 6);
+    push_u16(&mut colr, 3);
+    colr.push(4);
+    push_u24(&mut colr, 16);
+    for value in [0, 0, 100, 0, 0, 100] {
+        push_i16(&mut colr, value);
+    }
+    colr.push(1); // repeat
+    push_u16(&mut colr, 2);
+    push_color_stop(&mut colr, 0, 0, false);
+    push_color_stop(&mut colr, 0x4000, 1, false);
+
+    // glyph 6: PaintColrGlyph (glyph 5)
+    let colr_glyph = colr.len();
+    colr.push(11);
+    push_u16(&mut colr, 5);
+
+    // glyph 7: PaintTransform -> PaintVarSolid (second color, alpha 0.25)
+    let transform = colr.len();
+    colr.push(12);
+    push_u24(&mut colr, 7);
+    push_u24(&mut colr, 16);
+    colr.push(3);
+    push_u16(&mut colr, 1);
+    push_u16(&mut colr, 0x1000);
+    push_u32(&mut colr, 0xFFFF_FFFF);
+    for value in [2.0, 0.0, 0.0, 2.0, 5.0, -5.0] {
+        push_u32(&mut colr, (value * 65536.0) as i32 as u32);
+    }
+
+    // glyph 8: PaintVarLinearGradient (first color -> text color)
+    let var_gradient = colr.len();
+    colr.push(5);
+    push_u24(&mut colr, 20);
+    for value in [0, 0, 0, 100, 100, 0] {
+        push_i16(&mut colr, value);
+    }
+    push_u32(&mut colr, 0xFFFF_FFFF);
+    colr.push(0); // pad
+    push_u16(&mut colr, 2);
+    push_color_stop(&mut colr, 0, 0, true);
+    push_color_stop(&mut colr, 0x2000, FOREGROUND_PALETTE_INDEX, true);
+
+    let paints = [
+        (5, colr_layers),
+        (6, colr_glyph),
+        (7, transform),
+        (8, var_gradient),
+    ];
+    for (i, (glyph_index, paint)) in paints.into_iter().enumerate() {
+        let record = base_glyph_list + 4 + i * 6;
+        colr[record..record + 2].copy_from_slice(&(glyph_index as u16).to_be_bytes());
+        set_u32(&mut colr, record + 2, paint - base_glyph_list);
+    }
+    set_u32(&mut colr, layer_list + 4, layer_0 - layer_list);
+    set_u32(&mut colr, layer_list + 8, layer_1 - layer_list);
+
+    let tables = ColorGlyphTables {
+        num_glyphs: 9,
+        colr: Some(colr.into_boxed_slice()),
+        cpal: Some(cpal.into_boxed_slice()),
+        ..Default::default()
+    };
+
+    assert_eq!(tables.get_glyph_kind(1), Some(GlyphKind::ColorLayers));
+    assert_eq!(tables.get_glyph_kind(5), Some(GlyphKind::ColorPaint));
+    assert_eq!(tables.get_glyph_kind(6), Some(GlyphKind::ColorPaint));
+    assert_eq!(tables.get_glyph_kind(2), None);
+
+    // COLRv0 layers, the palette index selects the colors
+    let layers = |color: ColorU| {
+        Some(vec![
+            ColorGlyphLayer {
+                glyph_index: 2,
+                color: ColorGlyphColor::Palette(color),
+            },
+            ColorGlyphLayer {
+                glyph_index: 3,
+                color: ColorGlyphColor::CurrentColor { alpha: 1.0 },
+            },
+        ])
+    };
+    assert_eq!(tables.get_color_layers(1, 0), layers(red));
+    assert_eq!(tables.get_color_layers(1, 1), layers(green));
+    // invalid palettes fall back to the first palette
+    assert_eq!(tables.get_color_layers(1, 7), layers(red));
+
+    // COLRv1 layers, solid fill, transform and gradient
+    let expected = ColorPaint::Layers(vec![
+        ColorPaint::Glyph {
+            glyph_index: 2,
+            paint: Box::new(ColorPaint::Solid(ColorGlyphColor::Palette(ColorU {
+                a: 128,
+                ..white
+            }))),
+        },
+        ColorPaint::Transform {
+            transform: ColorTransform::translate(10.0, 20.0),
+            paint: Box::new(ColorPaint::Glyph {
+                glyph_index: 3,
+                paint: Box::new(ColorPaint::LinearGradient(ColorLinearGradient {
+                    color_line: ColorLine {
+                        extend: ColorExtend::Repeat,
+                        stops: vec![
+                            ColorStop {
+                                offset: 0.0,
+                                color: ColorGlyphColor::Palette(green),
+                            },
+                            ColorStop {
+                                offset: 1.0,
+                                color: ColorGlyphColor::Palette(white),
+                            },
+                        ],
+                    },
+                    x0: 0.0,
+                    y0: 0.0,
+                    x1: 100.0,
+                    y1: 0.0,
+                    x2: 0.0,
+                    y2: 100.0,
+                })),
+            }),
+        },
+    ]);
+    assert_eq!(tables.get_color_paint(5, 1), Some(expected.clone()));
+    // PaintColrGlyph is resolved to the paint of the referenced glyph
+    assert_eq!(tables.get_color_paint(6, 1), Some(expected));
+
+    // affine transform, variable paints are read with their default values
+    assert_eq!(
+        tables.get_color_paint(7, 0),
+        Some(ColorPaint::Transform {
+            transform: ColorTransform {
+                xx: 2.0,
+                yx: 0.0,
+                xy: 0.0,
+                yy: 2.0,
+                dx: 5.0,
+                dy: -5.0,
+            },
+            paint: Box::new(ColorPaint::Solid(ColorGlyphColor::Palette(ColorU {
+                a: 64,
+                ..blue
+            }))),
+        })
+    );
+    assert_eq!(
+        tables.get_color_paint(8, 0),
+        Some(ColorPaint::LinearGradient(ColorLinearGradient {
+            color_line: ColorLine {
+                extend: ColorExtend::Pad,
+                stops: vec![
+                    ColorStop {
+                        offset: 0.0,
+                        color: ColorGlyphColor::Palette(red),
+                    },
+                    ColorStop {
+                        offset: 0.5,
+                        color: ColorGlyphColor::CurrentColor { alpha: 1.0 },
+                    },
+                ],
+            },
+            x0: 0.0,
+            y0: 0.0,
+            x1: 0.0,
+            y1: 100.0,
+            x2: 100.0,
+            y2: 0.0,
+        }))
+    );
+}
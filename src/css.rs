@@ -1,8 +1,10 @@
 //! Provides a public API with datatypes used to describe style properties of DOM nodes.
 
+use allsorts::post::Header as PostHeader;
 use allsorts::tables::os2::Os2;
 use allsorts::tables::{HeadTable, HheaTable};
 
+use crate::logical::{LogicalPosition, LogicalRect, LogicalSize};
 use crate::text_shaping::ParsedFont;
 use core::fmt;
 use core::hash::Hash;
@@ -25,10 +27,379 @@ pub enum StyleVerticalAlign {
     Bottom,
 }
 
+/// Vertical alignment of an inline span relative to the line (`vertical-align` CSS property)
+/// - default: `Baseline`
+///
+/// See [CSS 2.1](https://www.w3.org/TR/CSS21/visudet.html#propdef-vertical-align)
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+#[repr(C, u8)]
+pub enum StyleInlineVerticalAlign {
+    /// Align the baseline of the span with the baseline of the line
+    #[default]
+    Baseline,
+    /// Lower the baseline to the subscript position of the font
+    Sub,
+    /// Raise the baseline to the superscript position of the font
+    Super,
+    /// Align the top of the span with the top of the line box
+    Top,
+    /// Align the middle of the span with the baseline plus half the x-height of the font
+    Middle,
+    /// Align the bottom of the span with the bottom of the line box
+    Bottom,
+    /// Align the top of the span with the ascender of the font
+    TextTop,
+    /// Align the bottom of the span with the descender of the font
+    TextBottom,
+    /// Raise the baseline by the given amount (in pixels, negative values lower it)
+    Length(f32),
+}
+
+/// How the text is distributed across columns (`column-fill` CSS property) - default: `Balance`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleColumnFill {
+    /// All columns are (about) equally high
+    #[default]
+    Balance,
+    /// Each column is filled up to the `max_vertical_height` before the next column is started
+    Auto,
+}
+
+/// Direction of the lines (`writing-mode` CSS property) - default: `HorizontalTb`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleWritingMode {
+    /// Horizontal lines, stacked from top to bottom
+    #[default]
+    HorizontalTb,
+    /// Vertical lines (top to bottom), stacked from right to left
+    VerticalRl,
+    /// Vertical lines (top to bottom), stacked from left to right
+    VerticalLr,
+}
+
+impl StyleWritingMode {
+    pub const fn is_vertical(&self) -> bool {
+        !matches!(self, Self::HorizontalTb)
+    }
+}
+
+/// How white space inside the text is handled (`white-space` CSS property) - default: `Normal`
+///
+/// See [CSS Text Level 3](https://www.w3.org/TR/css-text-3/#white-space-property)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleWhiteSpace {
+    /// Collapse white space and newlines, wrap lines
+    #[default]
+    Normal,
+    /// Collapse white space and newlines, never wrap lines
+    Nowrap,
+    /// Preserve white space and newlines, never wrap lines
+    Pre,
+    /// Preserve white space and newlines, wrap lines, trailing spaces hang
+    PreWrap,
+    /// Collapse white space, preserve newlines, wrap lines
+    PreLine,
+    /// Like `PreWrap`, but trailing spaces take up space and can wrap onto the next line
+    BreakSpaces,
+}
+
+impl StyleWhiteSpace {
+    /// Whether sequences of spaces and tabs collapse into a single space
+    pub const fn collapses_spaces(&self) -> bool {
+        matches!(self, Self::Normal | Self::Nowrap | Self::PreLine)
+    }
+
+    /// Whether newlines are kept as forced line breaks (instead of being turned into spaces)
+    pub const fn preserves_newlines(&self) -> bool {
+        !matches!(self, Self::Normal | Self::Nowrap)
+    }
+
+    /// Whether lines may be broken at soft wrap opportunities
+    pub const fn wraps(&self) -> bool {
+        !matches!(self, Self::Nowrap | Self::Pre)
+    }
+
+    /// Whether spaces at the end of a line hang (i.e. are ignored when
+    /// measuring the line and never cause a line break)
+    pub const fn hangs_trailing_spaces(&self) -> bool {
+        !matches!(self, Self::Pre | Self::BreakSpaces)
+    }
+}
+
+/// Height of a line box (`line-height` CSS property) - default: `Normal`
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+#[repr(C, u8)]
+pub enum StyleLineHeight {
+    /// Use the ascender, descender and line gap of the font
+    #[default]
+    Normal,
+    /// Multiple of the font size
+    Multiplier(f32),
+    /// Absolute line height (in pixels)
+    Px(f32),
+}
+
+impl StyleLineHeight {
+    /// Returns the height of the line box in pixels, `normal_line_height_px`
+    /// is the (scaled) `ascender - descender + line gap` of the font
+    pub fn resolve(&self, font_size_px: f32, normal_line_height_px: f32) -> f32 {
+        match self {
+            StyleLineHeight::Normal => normal_line_height_px,
+            StyleLineHeight::Multiplier(m) => m * font_size_px,
+            StyleLineHeight::Px(px) => *px,
+        }
+    }
+}
+
+/// Additional spacing between letters or words (`letter-spacing` / `word-spacing` CSS properties)
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C, u8)]
+pub enum StyleTextSpacing {
+    /// Absolute spacing (in pixels)
+    Px(f32),
+    /// Multiple of the font size
+    Em(f32),
+    /// Percentage of the font size (`letter-spacing`) or of the advance of the space (`word-spacing`)
+    Percent(f32),
+}
+
+impl StyleTextSpacing {
+    /// Returns the spacing in pixels, `percent_base_px` is the length that 100% refers to
+    pub fn resolve(&self, font_size_px: f32, percent_base_px: f32) -> f32 {
+        match self {
+            StyleTextSpacing::Px(px) => *px,
+            StyleTextSpacing::Em(em) => em * font_size_px,
+            StyleTextSpacing::Percent(percent) => percent / 100.0 * percent_base_px,
+        }
+    }
+}
+
+/// Weight of a font face (`font-weight` CSS property, 1 - 1000) - default: `NORMAL`
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct StyleFontWeight(pub f32);
+
+impl StyleFontWeight {
+    pub const THIN: Self = Self(100.0);
+    pub const LIGHT: Self = Self(300.0);
+    pub const NORMAL: Self = Self(400.0);
+    pub const MEDIUM: Self = Self(500.0);
+    pub const SEMI_BOLD: Self = Self(600.0);
+    pub const BOLD: Self = Self(700.0);
+    pub const BLACK: Self = Self(900.0);
+}
+
+impl Default for StyleFontWeight {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+/// Slant of a font face (`font-style` CSS property) - default: `Normal`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleFontStyle {
+    #[default]
+    Normal,
+    /// Cursive forms designed for italics
+    Italic,
+    /// Slanted version of the normal forms
+    Oblique,
+}
+
+/// Width of a font face (`font-stretch` CSS property) - default: `Normal`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleFontStretch {
+    UltraCondensed,
+    ExtraCondensed,
+    Condensed,
+    SemiCondensed,
+    #[default]
+    Normal,
+    SemiExpanded,
+    Expanded,
+    ExtraExpanded,
+    UltraExpanded,
+}
+
+impl StyleFontStretch {
+    /// Returns the width as a percentage of the normal width
+    pub const fn get_percentage(&self) -> f32 {
+        use self::StyleFontStretch::*;
+        match self {
+            UltraCondensed => 50.0,
+            ExtraCondensed => 62.5,
+            Condensed => 75.0,
+            SemiCondensed => 87.5,
+            Normal => 100.0,
+            SemiExpanded => 112.5,
+            Expanded => 125.0,
+            ExtraExpanded => 150.0,
+            UltraExpanded => 200.0,
+        }
+    }
+
+    /// Converts the `usWidthClass` of the `OS/2` table (1 - 9)
+    pub const fn from_width_class(width_class: u16) -> Self {
+        use self::StyleFontStretch::*;
+        match width_class {
+            0 | 1 => UltraCondensed,
+            2 => ExtraCondensed,
+            3 => Condensed,
+            4 => SemiCondensed,
+            5 => Normal,
+            6 => SemiExpanded,
+            7 => Expanded,
+            8 => ExtraExpanded,
+            _ => UltraExpanded,
+        }
+    }
+}
+
+/// Whether an otherwise unbreakable word may be broken if it is
+/// longer than the line (`overflow-wrap` CSS property) - default: `Normal`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleOverflowWrap {
+    /// Words are never broken, long words overflow the line
+    #[default]
+    Normal,
+    /// Long words are broken at any grapheme boundary, which
+    /// is also taken into account for the min-content width
+    Anywhere,
+    /// Same as `Anywhere`, but the min-content width is not affected
+    BreakWord,
+}
+
+/// Where lines may be broken inside of words (`word-break` CSS property) - default: `Normal`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleWordBreak {
+    /// Only break between CJK characters
+    #[default]
+    Normal,
+    /// Break between any two grapheme clusters
+    BreakAll,
+    /// Never break inside of a word, not even between CJK characters
+    KeepAll,
+}
+
+/// Punctuation marks that are placed outside of the line box (`hanging-punctuation`
+/// CSS property) - default: none
+///
+/// Hanging marks don't count towards the width of the line when it is broken and aligned.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub struct StyleHangingPunctuation {
+    /// Opening brackets and quotes at the start of the first line of a paragraph (`first`)
+    pub first: bool,
+    /// Closing brackets and quotes at the end of the last line of a paragraph (`last`)
+    pub last: bool,
+    /// Stops and commas at the end of a line (`allow-end` / `force-end`)
+    pub end: StyleHangingPunctuationEnd,
+}
+
+/// Whether stops and commas at the end of a line hang (see `StyleHangingPunctuation`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleHangingPunctuationEnd {
+    #[default]
+    None,
+    /// Only if the mark wouldn't fit onto the line otherwise (`allow-end`)
+    AllowEnd,
+    /// Always (`force-end`)
+    ForceEnd,
+}
+
+/// Capital letter glyphs that the text is displayed with (`font-variant-caps` CSS property) - default: `Normal`
+///
+/// Small caps (and petite caps, which fall back to small caps) are synthesized from
+/// scaled down capital letters if the font doesn't have the OpenType feature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleFontVariantCaps {
+    #[default]
+    Normal,
+    /// Lower case letters are displayed as small caps (`smcp`)
+    SmallCaps,
+    /// Lower and upper case letters are displayed as small caps (`smcp` + `c2sc`)
+    AllSmallCaps,
+    /// Lower case letters are displayed as petite caps (`pcap`)
+    PetiteCaps,
+    /// Lower and upper case letters are displayed as petite caps (`pcap` + `c2pc`)
+    AllPetiteCaps,
+    /// Upper case letters are displayed as small caps, lower case letters stay lower case (`unic`)
+    Unicase,
+    /// Capital letters designed for all-caps titles (`titl`), not synthesized
+    TitlingCaps,
+}
+
+/// Case and width conversion of the text (`text-transform` CSS property) - default: `None`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleTextTransform {
+    /// The text is displayed as-is
+    #[default]
+    None,
+    /// All letters are converted to upper case ("ß" becomes "SS")
+    Uppercase,
+    /// All letters are converted to lower case
+    Lowercase,
+    /// The first letter of every word is converted to title case
+    Capitalize,
+    /// ASCII and half-width katakana characters are converted to their full-width forms
+    FullWidth,
+}
+
+/// What is displayed at the end of a cut off line (`text-overflow` CSS property) - default: `Clip`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C, u8)]
+pub enum StyleTextOverflow {
+    /// Cut off the text without any marker
+    #[default]
+    Clip,
+    /// Insert a "…" (U+2026) at the end of the line
+    Ellipsis,
+    /// Insert a custom string at the end of the line
+    String(String),
+}
+
+/// Which line is drawn (`text-decoration-line` CSS property)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub enum StyleTextDecorationLine {
+    /// Below the baseline, at the underline position of the font
+    Underline,
+    /// Above the text, at the ascender of the font
+    Overline,
+    /// Through the text, at the strikeout position of the font
+    LineThrough,
+}
+
+/// How the decoration line is drawn (`text-decoration-style` CSS property) - default: `Solid`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[repr(C)]
+pub enum StyleTextDecorationStyle {
+    #[default]
+    Solid,
+    Double,
+    Dotted,
+    Dashed,
+    Wavy,
+}
+
+/// Tables of the font that contain the global metrics (`head`, `hhea`, `OS/2`, `post`),
+/// the `get_*_unscaled` getters return font units, the other getters pixels
 pub struct FontMetrics {
     pub head: HeadTable,
     pub hhea: HheaTable,
     pub os2: Os2,
+    /// `None` if the font doesn't have a `post` table
+    pub post: Option<PostHeader>,
 }
 
 impl FontMetrics {
@@ -75,6 +446,235 @@ impl FontMetrics {
             None => self.hhea.line_gap,
         }
     }
+
+    /// Height of the lowercase letters, falls back to half an em if the font doesn't specify it
+    pub fn get_x_height_unscaled(&self) -> i16 {
+        match self.os2.version2to4.as_ref().map(|x| x.sx_height) {
+            Some(s) if s > 0 => s,
+            _ => (self.head.units_per_em / 2) as i16,
+        }
+    }
+
+    /// Distance that subscripts are lowered below the baseline (POSITIVE = down),
+    /// falls back to a fifth of an em if the font doesn't specify it
+    pub fn get_subscript_y_offset_unscaled(&self) -> i16 {
+        match self.os2.y_subscript_y_offset {
+            0 => (self.head.units_per_em / 5) as i16,
+            s => s,
+        }
+    }
+
+    /// Position of the TOP of the underline relative to the baseline (NEGATIVE = below),
+    /// falls back to a tenth of an em below the baseline if the font doesn't specify it
+    pub fn get_underline_position_unscaled(&self) -> i16 {
+        match self.post.as_ref().map(|p| p.underline_position) {
+            Some(s) if s != 0 => s,
+            _ => -((self.head.units_per_em / 10) as i16),
+        }
+    }
+
+    /// Thickness of the underline, falls back to the strikeout size
+    /// (or a fourteenth of an em) if the font doesn't specify it
+    pub fn get_underline_thickness_unscaled(&self) -> i16 {
+        match self.post.as_ref().map(|p| p.underline_thickness) {
+            Some(s) if s > 0 => s,
+            _ => self.get_strikeout_size_unscaled(),
+        }
+    }
+
+    /// Position of the TOP of the strikeout stroke above the baseline,
+    /// falls back to half the x-height if the font doesn't specify it
+    pub fn get_strikeout_position_unscaled(&self) -> i16 {
+        match self.os2.y_strikeout_position {
+            0 => (self.get_x_height_unscaled() + self.get_strikeout_size_unscaled()) / 2,
+            s => s,
+        }
+    }
+
+    /// Thickness of the strikeout stroke, falls back to a
+    /// fourteenth of an em if the font doesn't specify it
+    pub fn get_strikeout_size_unscaled(&self) -> i16 {
+        match self.os2.y_strikeout_size {
+            s if s > 0 => s,
+            _ => (self.head.units_per_em / 14).max(1) as i16,
+        }
+    }
+
+    /// Distance that superscripts are raised above the baseline,
+    /// falls back to a third of an em if the font doesn't specify it
+    pub fn get_superscript_y_offset_unscaled(&self) -> i16 {
+        match self.os2.y_superscript_y_offset {
+            0 => (self.head.units_per_em / 3) as i16,
+            s => s,
+        }
+    }
+
+    /// Height of the uppercase letters, falls back to the ascender if the font doesn't specify it
+    pub fn get_cap_height_unscaled(&self) -> i16 {
+        match self.os2.version2to4.as_ref().map(|x| x.s_cap_height) {
+            Some(s) if s > 0 => s,
+            _ => self.get_ascender_unscaled(),
+        }
+    }
+
+    /// Average advance of the glyphs, falls back to half an em if the font doesn't specify it
+    pub fn get_average_char_width_unscaled(&self) -> i16 {
+        match self.os2.x_avg_char_width {
+            s if s > 0 => s,
+            _ => (self.head.units_per_em / 2) as i16,
+        }
+    }
+
+    /// Horizontal and vertical font size of subscripts,
+    /// falls back to two thirds of an em if the font doesn't specify it
+    pub fn get_subscript_size_unscaled(&self) -> (i16, i16) {
+        (
+            self.get_script_size_or_default(self.os2.y_subscript_x_size),
+            self.get_script_size_or_default(self.os2.y_subscript_y_size),
+        )
+    }
+
+    /// Horizontal offset of subscripts (e.g. for italic fonts)
+    pub fn get_subscript_x_offset_unscaled(&self) -> i16 {
+        self.os2.y_subscript_x_offset
+    }
+
+    /// Horizontal and vertical font size of superscripts,
+    /// falls back to two thirds of an em if the font doesn't specify it
+    pub fn get_superscript_size_unscaled(&self) -> (i16, i16) {
+        (
+            self.get_script_size_or_default(self.os2.y_superscript_x_size),
+            self.get_script_size_or_default(self.os2.y_superscript_y_size),
+        )
+    }
+
+    /// Horizontal offset of superscripts (e.g. for italic fonts)
+    pub fn get_superscript_x_offset_unscaled(&self) -> i16 {
+        self.os2.y_superscript_x_offset
+    }
+
+    /// Bounding box of all glyphs as `(x_min, y_min, x_max, y_max)` (y pointing up)
+    pub fn get_bounding_box_unscaled(&self) -> (i16, i16, i16, i16) {
+        (
+            self.head.x_min,
+            self.head.y_min,
+            self.head.x_max,
+            self.head.y_max,
+        )
+    }
+
+    /// Italic angle in degrees counter-clockwise from the vertical
+    /// (NEGATIVE for fonts that lean to the right), zero if the font has no `post` table
+    pub fn get_italic_angle(&self) -> f32 {
+        self.post
+            .as_ref()
+            .map_or(0.0, |p| p.italic_angle as f32 / 65536.0)
+    }
+
+    fn get_script_size_or_default(&self, size: i16) -> i16 {
+        match size {
+            s if s > 0 => s,
+            _ => (self.head.units_per_em as u32 * 2 / 3) as i16,
+        }
+    }
+
+    fn scale(&self, value: i16, target_font_size: f32) -> f32 {
+        value as f32 / self.head.units_per_em as f32 * target_font_size
+    }
+
+    pub fn get_ascender(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_ascender_unscaled(), target_font_size)
+    }
+
+    /// NOTE: descender is NEGATIVE
+    pub fn get_descender(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_descender_unscaled(), target_font_size)
+    }
+
+    pub fn get_line_gap(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_line_gap_unscaled(), target_font_size)
+    }
+
+    /// Size of the CSS `ex` unit
+    pub fn get_x_height(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_x_height_unscaled(), target_font_size)
+    }
+
+    /// Size of the CSS `cap` unit
+    pub fn get_cap_height(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_cap_height_unscaled(), target_font_size)
+    }
+
+    pub fn get_average_char_width(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_average_char_width_unscaled(), target_font_size)
+    }
+
+    /// Returns the `(width, height)` that subscripts should be rendered with
+    pub fn get_subscript_size(&self, target_font_size: f32) -> (f32, f32) {
+        let (x, y) = self.get_subscript_size_unscaled();
+        (
+            self.scale(x, target_font_size),
+            self.scale(y, target_font_size),
+        )
+    }
+
+    /// NOTE: the y offset is POSITIVE (distance below the baseline)
+    pub fn get_subscript_offset(&self, target_font_size: f32) -> LogicalPosition {
+        LogicalPosition::new(
+            self.scale(self.get_subscript_x_offset_unscaled(), target_font_size),
+            self.scale(self.get_subscript_y_offset_unscaled(), target_font_size),
+        )
+    }
+
+    /// Returns the `(width, height)` that superscripts should be rendered with
+    pub fn get_superscript_size(&self, target_font_size: f32) -> (f32, f32) {
+        let (x, y) = self.get_superscript_size_unscaled();
+        (
+            self.scale(x, target_font_size),
+            self.scale(y, target_font_size),
+        )
+    }
+
+    /// NOTE: the y offset is POSITIVE (distance above the baseline)
+    pub fn get_superscript_offset(&self, target_font_size: f32) -> LogicalPosition {
+        LogicalPosition::new(
+            self.scale(self.get_superscript_x_offset_unscaled(), target_font_size),
+            self.scale(self.get_superscript_y_offset_unscaled(), target_font_size),
+        )
+    }
+
+    /// Position of the top of the strikeout stroke above the baseline
+    pub fn get_strikeout_position(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_strikeout_position_unscaled(), target_font_size)
+    }
+
+    pub fn get_strikeout_size(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_strikeout_size_unscaled(), target_font_size)
+    }
+
+    /// Position of the top of the underline relative to the baseline (NEGATIVE = below)
+    pub fn get_underline_position(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_underline_position_unscaled(), target_font_size)
+    }
+
+    pub fn get_underline_thickness(&self, target_font_size: f32) -> f32 {
+        self.scale(self.get_underline_thickness_unscaled(), target_font_size)
+    }
+
+    /// Bounding box of all glyphs relative to the baseline origin (y pointing down)
+    pub fn get_bounding_box(&self, target_font_size: f32) -> LogicalRect {
+        let (x_min, y_min, x_max, y_max) = self.get_bounding_box_unscaled();
+        LogicalRect::new(
+            LogicalPosition::new(
+                self.scale(x_min, target_font_size),
+                -self.scale(y_max, target_font_size),
+            ),
+            LogicalSize::new(
+                (x_max as f32 - x_min as f32) / self.head.units_per_em as f32 * target_font_size,
+                (y_max as f32 - y_min as f32) / self.head.units_per_em as f32 * target_font_size,
+            ),
+        )
+    }
 }
 
 pub struct FontData {
@@ -95,3 +695,98 @@ impl fmt::Debug for FontData {
             .finish()
     }
 }
+
+#[test]
+fn test_font_metrics() {
+    use crate::test_font::TestFont;
+
+    let metrics = |test_font: TestFont| {
+        ParsedFont::from_bytes(&test_font.to_bytes(), 0)
+            .unwrap()
+            .font_metrics
+    };
+    let round = |f: f32| (f * 1000.0).round() / 1000.0;
+
+    // 1000 units per em at 20px: 1 unit = 0.02px
+    let font_metrics = metrics(TestFont {
+        italic_angle: -12.5,
+        ..TestFont::default()
+    });
+    let size = 20.0;
+    assert_eq!(round(font_metrics.get_ascender(size)), 16.0);
+    assert_eq!(round(font_metrics.get_descender(size)), -4.0);
+    assert_eq!(round(font_metrics.get_line_gap(size)), 0.0);
+    assert_eq!(round(font_metrics.get_x_height(size)), 10.0);
+    assert_eq!(round(font_metrics.get_cap_height(size)), 14.0);
+    assert_eq!(round(font_metrics.get_average_char_width(size)), 10.0);
+    assert_eq!(round(font_metrics.get_strikeout_position(size)), 6.0);
+    assert_eq!(round(font_metrics.get_strikeout_size(size)), 1.0);
+    assert_eq!(round(font_metrics.get_underline_position(size)), -2.0);
+    assert_eq!(round(font_metrics.get_underline_thickness(size)), 1.0);
+    assert_eq!(
+        font_metrics.get_subscript_offset(size),
+        LogicalPosition::new(0.0, 4.0)
+    );
+    assert_eq!(
+        font_metrics.get_superscript_offset(size),
+        LogicalPosition::new(0.0, 6.0)
+    );
+    let (width, height) = font_metrics.get_subscript_size(size);
+    assert_eq!((round(width), round(height)), (13.32, 13.32));
+    assert_eq!(font_metrics.get_italic_angle(), -12.5);
+    // the glyphs span from (50, -200) to (450, 700), the rectangle is y-down
+    let bounding_box = font_metrics.get_bounding_box(size);
+    assert_eq!(
+        [
+            bounding_box.origin.x,
+            bounding_box.origin.y,
+            bounding_box.size.width,
+            bounding_box.size.height
+        ]
+        .map(round),
+        [1.0, -14.0, 8.0, 18.0]
+    );
+
+    // OS/2 version 0 and 1 tables have no x-height and cap height: half an em
+    // and the ascender are used instead
+    for os2_version in [0, 1] {
+        let font_metrics = metrics(TestFont {
+            os2_version: Some(os2_version),
+            ..TestFont::default()
+        });
+        assert_eq!(font_metrics.get_x_height_unscaled(), 500);
+        assert_eq!(font_metrics.get_cap_height_unscaled(), 800);
+        assert_eq!(round(font_metrics.get_x_height(size)), 10.0);
+        assert_eq!(round(font_metrics.get_cap_height(size)), 16.0);
+    }
+
+    // without a `post` table, the underline is a tenth of an em below the baseline
+    // and as thick as the strikeout, the italic angle is zero
+    let font_metrics = metrics(TestFont {
+        post: None,
+        ..TestFont::default()
+    });
+    assert!(font_metrics.post.is_none());
+    assert_eq!(font_metrics.get_underline_position_unscaled(), -100);
+    assert_eq!(font_metrics.get_underline_thickness_unscaled(), 50);
+    assert_eq!(round(font_metrics.get_underline_position(size)), -2.0);
+    assert_eq!(font_metrics.get_italic_angle(), 0.0);
+
+    // zero values in the OS/2 table fall back to values derived from the em
+    let font_metrics = metrics(TestFont {
+        post: None,
+        strikeout_position: 0,
+        strikeout_size: 0,
+        subscript_y_offset: 0,
+        superscript_y_offset: 0,
+        ..TestFont::default()
+    });
+    assert_eq!(font_metrics.get_strikeout_size_unscaled(), 71);
+    assert_eq!(font_metrics.get_underline_thickness_unscaled(), 71);
+    assert_eq!(
+        font_metrics.get_strikeout_position_unscaled(),
+        (500 + 71) / 2
+    );
+    assert_eq!(font_metrics.get_subscript_y_offset_unscaled(), 200);
+    assert_eq!(font_metrics.get_superscript_y_offset_unscaled(), 333);
+}
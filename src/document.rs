@@ -0,0 +1,426 @@
+//! Multi-paragraph documents: every paragraph is laid out on its own (with its own
+//! alignment and indentation) and the paragraphs are stacked from top to bottom
+
+use crate::{
+    css::StyleTextAlign,
+    logical::{LogicalPosition, LogicalSize},
+    text_layout::{
+        position_words, shape_words_with_options, split_text_into_paragraphs,
+        split_text_into_words, word_positions_to_inline_text_layout, OBJECT_REPLACEMENT_CHARACTER,
+    },
+    text_shaping::ParsedFont,
+    ui_solver::{InlineObject, InlineSpan, ResolvedTextLayoutOptions},
+    words::{
+        get_inline_text, InlineText, LayoutedGlyphs, ShapedWords, TextNormalization, WordPositions,
+        Words,
+    },
+};
+use core::ops::Range;
+
+/// Formatting of a single paragraph
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct ParagraphStyle {
+    pub text_align: StyleTextAlign,
+    /// Indentation of the first line (`text-indent`, in pixels)
+    pub text_indent: f32,
+    /// Indent all lines except the first one instead (`text-indent: hanging`)
+    pub hanging_indent: bool,
+    /// Space above the paragraph (in pixels)
+    pub spacing_before: f32,
+    /// Space below the paragraph (in pixels), collapses with the
+    /// `spacing_before` of the next paragraph (like CSS margins)
+    pub spacing_after: f32,
+}
+
+impl Default for ParagraphStyle {
+    fn default() -> Self {
+        Self {
+            text_align: StyleTextAlign::Left,
+            text_indent: 0.0,
+            hanging_indent: false,
+            spacing_before: 0.0,
+            spacing_after: 0.0,
+        }
+    }
+}
+
+/// Paragraph of a `Document`
+#[derive(Debug, Clone, Default)]
+pub struct Paragraph {
+    /// Text of the paragraph (without the newline / paragraph separator)
+    pub text: String,
+    pub style: ParagraphStyle,
+    /// Layout of the paragraph from the last `Document::layout`, only
+    /// recalculated if the text, style or layout options change
+    layout: Option<ParagraphLayout>,
+}
+
+/// Result of laying out a single paragraph
+#[derive(Debug, Clone)]
+pub struct ParagraphLayout {
+    pub words: Words,
+    pub shaped_words: ShapedWords,
+    pub word_positions: WordPositions,
+    /// Lines of the paragraph, relative to the `origin`
+    pub inline_text: InlineText,
+    /// Position of the top left corner of the paragraph (relative to the document)
+    pub origin: LogicalPosition,
+    /// Text that the paragraph was laid out with
+    text: String,
+    /// Style and paragraph-specific options that the paragraph was laid out with
+    cache_key: ParagraphCacheKey,
+}
+
+/// Everything besides the text and the options of the document that the
+/// layout of a paragraph depends on
+#[derive(Debug, Clone, PartialEq)]
+struct ParagraphCacheKey {
+    style: ParagraphStyle,
+    /// `leading` of the document, only applies to the first paragraph
+    leading: Option<f32>,
+    /// Spans of the document that overlap the paragraph, relative to the paragraph
+    spans: Vec<InlineSpan>,
+    /// Inline objects of the document that are in the paragraph
+    inline_objects: Vec<InlineObject>,
+}
+
+/// Font and options that the paragraphs of a `Document` were laid out with
+#[derive(Debug, Clone, PartialEq)]
+struct DocumentCacheKey {
+    /// Identifies the font, see `Document::layout`
+    font_id: u64,
+    text_layout_options: ResolvedTextLayoutOptions,
+}
+
+impl Paragraph {
+    pub fn new(text: String, style: ParagraphStyle) -> Self {
+        Self {
+            text,
+            style,
+            layout: None,
+        }
+    }
+
+    /// Returns the layout of the paragraph, `None` if the document hasn't been laid out yet
+    pub fn get_layout(&self) -> Option<&ParagraphLayout> {
+        self.layout.as_ref()
+    }
+}
+
+/// Sequence of paragraphs that are laid out one below the other
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    pub paragraphs: Vec<Paragraph>,
+    /// Size of the document from the last `layout`
+    pub content_size: LogicalSize,
+    /// Font and options of the last `layout`, the cached layouts of the
+    /// paragraphs are discarded if they change
+    cache_key: Option<DocumentCacheKey>,
+}
+
+impl Document {
+    /// Splits the text into paragraphs (see `split_text_into_paragraphs`), which all have the same style
+    pub fn new(text: &str, style: ParagraphStyle) -> Self {
+        Self {
+            paragraphs: split_text_into_paragraphs(text)
+                .into_iter()
+                .map(|range| Paragraph::new(text[range].to_string(), style))
+                .collect(),
+            content_size: LogicalSize::zero(),
+            cache_key: None,
+        }
+    }
+
+    /// Lays out all paragraphs with the given font and options (`max_horizontal_width`
+    /// is the width of the document), returns the size of the document.
+    ///
+    /// The byte ranges of the `spans` refer to the text of the whole document, i.e.
+    /// the paragraphs joined with `\n`, the `inline_objects` are distributed to the
+    /// paragraphs in the order of their `OBJECT_REPLACEMENT_CHARACTER`s. The `leading`
+    /// only applies to the first line of the document.
+    ///
+    /// Paragraphs whose text, style, spans and inline objects haven't changed since
+    /// the last layout are not laid out again, unless the font or the options changed.
+    /// The `font_id` identifies the font for this: it has to be different for every
+    /// font (i.e. the face id of the `FontDatabase` combined with the synthesis) and
+    /// has to change if the font is modified in place.
+    pub fn layout(
+        &mut self,
+        font: &ParsedFont,
+        font_id: u64,
+        text_layout_options: &ResolvedTextLayoutOptions,
+    ) -> LogicalSize {
+        let cache_is_valid = self.cache_key.as_ref().is_some_and(|key| {
+            key.font_id == font_id && key.text_layout_options == *text_layout_options
+        });
+        if !cache_is_valid {
+            self.clear_cache();
+            self.cache_key = Some(DocumentCacheKey {
+                font_id,
+                text_layout_options: text_layout_options.clone(),
+            });
+        }
+
+        let mut y = 0.0_f32;
+        let mut previous_spacing_after = 0.0_f32;
+        let mut width = 0.0_f32;
+        let mut paragraph_start = 0;
+        let mut first_inline_object = 0;
+
+        for (paragraph_index, paragraph) in self.paragraphs.iter_mut().enumerate() {
+            let paragraph_end = paragraph_start + paragraph.text.len();
+            let object_count = paragraph.text.matches(OBJECT_REPLACEMENT_CHARACTER).count();
+            let cache_key = ParagraphCacheKey {
+                style: paragraph.style,
+                leading: if paragraph_index == 0 {
+                    text_layout_options.leading
+                } else {
+                    None
+                },
+                spans: get_paragraph_spans(
+                    &text_layout_options.spans,
+                    paragraph_start..paragraph_end,
+                ),
+                inline_objects: text_layout_options
+                    .inline_objects
+                    .iter()
+                    .skip(first_inline_object)
+                    .take(object_count)
+                    .copied()
+                    .collect(),
+            };
+            // the separator between the paragraphs counts as one byte
+            paragraph_start = paragraph_end + 1;
+            first_inline_object += object_count;
+
+            let mut layout = match paragraph.layout.take() {
+                Some(layout) if layout.text == paragraph.text && layout.cache_key == cache_key => {
+                    layout
+                }
+                _ => layout_paragraph(font, &paragraph.text, cache_key, text_layout_options),
+            };
+
+            // the spacing between two paragraphs is the larger of the two spacings
+            let spacing = if paragraph_index == 0 {
+                paragraph.style.spacing_before
+            } else {
+                paragraph.style.spacing_before.max(previous_spacing_after)
+            };
+            y += spacing;
+            layout.origin = LogicalPosition::new(0.0, y);
+            y += layout.inline_text.content_size.height;
+            width = width.max(layout.inline_text.content_size.width);
+            previous_spacing_after = paragraph.style.spacing_after;
+
+            paragraph.layout = Some(layout);
+        }
+
+        self.content_size = LogicalSize::new(width, y + previous_spacing_after);
+        self.content_size
+    }
+
+    /// Removes the cached layouts of all paragraphs
+    pub fn clear_cache(&mut self) {
+        for paragraph in self.paragraphs.iter_mut() {
+            paragraph.layout = None;
+        }
+    }
+
+    /// Returns the glyphs of all paragraphs (relative to the top left corner of the document)
+    pub fn get_layouted_glyphs(&self) -> LayoutedGlyphs {
+        LayoutedGlyphs {
+            glyphs: self
+                .paragraphs
+                .iter()
+                .filter_map(|p| p.layout.as_ref())
+                .flat_map(|layout| {
+                    let origin = layout.origin;
+                    layout
+                        .inline_text
+                        .get_layouted_glyphs()
+                        .glyphs
+                        .into_iter()
+                        .map(move |mut glyph| {
+                            glyph.point += origin;
+                            glyph
+                        })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the parts of the `spans` that overlap the byte `range` of a
+/// paragraph, relative to the start of the paragraph
+fn get_paragraph_spans(spans: &[InlineSpan], range: Range<usize>) -> Vec<InlineSpan> {
+    spans
+        .iter()
+        .filter_map(|span| {
+            let start = span.range.start.max(range.start);
+            let end = span.range.end.min(range.end);
+            if start >= end {
+                return None;
+            }
+            Some(InlineSpan {
+                range: (start - range.start)..(end - range.start),
+                ..span.clone()
+            })
+        })
+        .collect()
+}
+
+fn layout_paragraph(
+    font: &ParsedFont,
+    text: &str,
+    cache_key: ParagraphCacheKey,
+    text_layout_options: &ResolvedTextLayoutOptions,
+) -> ParagraphLayout {
+    let style = &cache_key.style;
+
+    // the first line starts at the indentation, a hanging indent is implemented
+    // by indenting all lines and starting the first line before the indentation
+    let (line_indent, first_line_leading) = if style.hanging_indent {
+        (style.text_indent, -style.text_indent)
+    } else {
+        (0.0, style.text_indent)
+    };
+    let options = ResolvedTextLayoutOptions {
+        leading: Some(cache_key.leading.unwrap_or(0.0) + first_line_leading),
+        max_horizontal_width: text_layout_options
+            .max_horizontal_width
+            .map(|width| (width - line_indent).max(0.0)),
+        spans: cache_key.spans.clone(),
+        inline_objects: cache_key.inline_objects.clone(),
+        ..text_layout_options.clone()
+    };
+
+    let words = split_text_into_words(text, options.white_space, TextNormalization::Nfc);
+    let shaped_words = shape_words_with_options(&words, font, &options);
+    let word_positions = position_words(&words, &shaped_words, &options);
+
+    let mut inline_text_layout = word_positions_to_inline_text_layout(&word_positions);
+    let available_size = LogicalSize::new(
+        options
+            .max_horizontal_width
+            .unwrap_or(word_positions.content_size.width),
+        word_positions.content_size.height,
+    );
+    inline_text_layout.align_children_horizontal(&available_size, style.text_align);
+    for line in inline_text_layout.lines.iter_mut() {
+        line.bounds.origin.x += line_indent;
+    }
+
+    let mut inline_text =
+        get_inline_text(&words, &shaped_words, &word_positions, &inline_text_layout);
+    inline_text.content_size.width += line_indent;
+
+    ParagraphLayout {
+        words,
+        shaped_words,
+        word_positions,
+        inline_text,
+        origin: LogicalPosition::zero(),
+        text: text.to_string(),
+        cache_key,
+    }
+}
+
+#[test]
+fn test_document_layout() {
+    use crate::{css::StyleInlineVerticalAlign, test_font::TestFont};
+
+    // 5px per character and space, 10px per line
+    let font = ParsedFont::from_bytes(&TestFont::default().to_bytes(), 0).unwrap();
+    let options = ResolvedTextLayoutOptions {
+        font_size_px: 10.0,
+        max_horizontal_width: Some(40.0),
+        ..Default::default()
+    };
+    let line_starts = |document: &Document| {
+        let mut starts = Vec::new();
+        for glyph in document.get_layouted_glyphs().glyphs {
+            let (x, y) = (glyph.point.x, glyph.point.y);
+            if starts.last().map(|&(_, last_y)| last_y) != Some(y) {
+                starts.push((x, y));
+            }
+        }
+        starts
+    };
+
+    // the spacing between two paragraphs collapses to the larger one
+    let style = ParagraphStyle {
+        spacing_before: 4.0,
+        spacing_after: 6.0,
+        ..Default::default()
+    };
+    let mut document = Document::new("aa\nbb\ncc", style);
+    document.paragraphs[2].style.spacing_before = 8.0;
+    assert_eq!(
+        document.layout(&font, 0, &options),
+        LogicalSize::new(40.0, 54.0)
+    );
+    let origins = document
+        .paragraphs
+        .iter()
+        .map(|p| p.get_layout().unwrap().origin.y)
+        .collect::<Vec<_>>();
+    assert_eq!(origins, vec![4.0, 20.0, 38.0]);
+
+    // the first line is indented, or all other lines with a hanging indent
+    let mut style = ParagraphStyle {
+        text_indent: 10.0,
+        ..Default::default()
+    };
+    let mut document = Document::new("aa bb cc dd", style);
+    document.layout(&font, 0, &options);
+    assert_eq!(line_starts(&document), vec![(10.0, 8.0), (0.0, 18.0)]);
+    style.hanging_indent = true;
+    let mut document = Document::new("aa bb cc dd", style);
+    document.layout(&font, 0, &options);
+    assert_eq!(line_starts(&document), vec![(0.0, 8.0), (10.0, 18.0)]);
+
+    // the leading only indents the first line of the document, the spans are
+    // relative to the whole text ("dd" is in the second paragraph)
+    let mut document = Document::new("aa bb\ncc dd", ParagraphStyle::default());
+    let span_options = ResolvedTextLayoutOptions {
+        leading: Some(5.0),
+        spans: vec![InlineSpan {
+            range: 9..11,
+            font_size_px: Some(20.0),
+            vertical_align: StyleInlineVerticalAlign::Baseline,
+            font_variant_caps: None,
+        }],
+        ..options.clone()
+    };
+    assert_eq!(document.layout(&font, 0, &span_options).height, 30.0);
+    assert_eq!(line_starts(&document), vec![(5.0, 8.0), (0.0, 26.0)]);
+
+    // unchanged paragraphs keep their layout until the options or the font id change
+    // (the font itself isn't compared), cached layouts are marked to tell them apart
+    let mark_layouts = |document: &mut Document| {
+        for paragraph in document.paragraphs.iter_mut() {
+            paragraph.layout.as_mut().unwrap().inline_text.font_size_px = -1.0;
+        }
+    };
+    let marked = |document: &Document| {
+        document
+            .paragraphs
+            .iter()
+            .map(|p| p.get_layout().unwrap().inline_text.font_size_px == -1.0)
+            .collect::<Vec<_>>()
+    };
+    mark_layouts(&mut document);
+    document.layout(&font, 0, &span_options);
+    assert_eq!(marked(&document), vec![true, true]);
+    document.paragraphs[1].text = "cc ee".to_string();
+    document.layout(&font, 0, &span_options);
+    assert_eq!(marked(&document), vec![true, false]);
+    document.layout(&font, 0, &options);
+    assert_eq!(marked(&document), vec![false, false]);
+    mark_layouts(&mut document);
+    document.layout(&font, 0, &options);
+    assert_eq!(marked(&document), vec![true, true]);
+    document.layout(&font, 1, &options);
+    assert_eq!(marked(&document), vec![false, false]);
+}
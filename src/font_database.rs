@@ -0,0 +1,770 @@
+//! Database of font faces (loaded from files or memory) that resolves
+//! CSS-style font queries with the font matching algorithm of CSS Fonts 4
+
+use crate::css::{FontData, StyleFontStretch, StyleFontStyle, StyleFontWeight};
+use crate::text_shaping::ParsedFont;
+use allsorts::{
+    binary::read::ReadScope,
+    font_data::FontData as FontFile,
+    tables::{os2::Os2, FontTableProvider, OpenTypeData},
+    tag,
+};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// File extensions that `FontDatabase::load_fonts_dir` loads
+const FONT_FILE_EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc", "woff", "woff2"];
+
+/// Tags of the registered variation axes
+const WGHT: u32 = u32::from_be_bytes(*b"wght");
+const WDTH: u32 = u32::from_be_bytes(*b"wdth");
+const ITAL: u32 = u32::from_be_bytes(*b"ital");
+const SLNT: u32 = u32::from_be_bytes(*b"slnt");
+
+/// `fsSelection` flags of the `OS/2` table
+const FS_SELECTION_ITALIC: u16 = 1 << 0;
+const FS_SELECTION_OBLIQUE: u16 = 1 << 9;
+
+/// Where the bytes of a font file come from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FontSource {
+    /// Font file on disk, only read again when a face of it is loaded
+    File(PathBuf),
+    Memory(Rc<[u8]>),
+}
+
+impl FontSource {
+    fn read(&self) -> Option<Vec<u8>> {
+        match self {
+            FontSource::File(path) => fs::read(path).ok(),
+            FontSource::Memory(bytes) => Some(bytes.to_vec()),
+        }
+    }
+}
+
+/// Range of values that a face supports (a single value for static fonts,
+/// the range of the variation axis for variable fonts)
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct FontValueRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl FontValueRange {
+    pub const fn single(value: f32) -> Self {
+        Self {
+            min: value,
+            max: value,
+        }
+    }
+
+    /// Returns the value in the range that is closest to `value`
+    pub fn clamp(&self, value: f32) -> f32 {
+        value.max(self.min).min(self.max)
+    }
+}
+
+/// Axis of a variable font (`fvar` table)
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct FontVariationAxis {
+    /// Tag of the axis (e.g. `wght`, `wdth`, `ital`, `slnt`)
+    pub tag: u32,
+    pub min_value: f32,
+    pub default_value: f32,
+    pub max_value: f32,
+}
+
+/// Metadata of a single face of a font file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFaceInfo {
+    /// Index into `FontDatabase::sources`
+    pub source: usize,
+    /// Index of the face in the font file (only non-zero for font collections)
+    pub font_index: usize,
+    /// Typographic family name (name ID 16) or family name (name ID 1)
+    pub family_name: String,
+    /// Typographic subfamily name (name ID 17) or subfamily name (name ID 2), e.g. "Bold Italic"
+    pub subfamily_name: String,
+    /// PostScript name (name ID 6), e.g. "DejaVuSans-BoldOblique", empty if the font has none
+    pub postscript_name: String,
+    /// `usWeightClass` of the `OS/2` table or the range of the `wght` axis
+    pub weight: FontValueRange,
+    /// `usWidthClass` of the `OS/2` table or the range of the `wdth` axis (in percent)
+    pub stretch: FontValueRange,
+    pub style: StyleFontStyle,
+    pub variation_axes: Vec<FontVariationAxis>,
+}
+
+impl FontFaceInfo {
+    fn get_axis(&self, axis_tag: u32) -> Option<&FontVariationAxis> {
+        self.variation_axes.iter().find(|a| a.tag == axis_tag)
+    }
+}
+
+/// CSS-style font query (`font-family`, `font-weight`, `font-stretch`, `font-style`)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FontQuery {
+    /// Family names in the order of preference, can contain generic families (`serif`, ...)
+    pub families: Vec<String>,
+    pub weight: StyleFontWeight,
+    pub stretch: StyleFontStretch,
+    pub style: StyleFontStyle,
+}
+
+/// Styles that the matched face doesn't have and that have to be synthesized
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub struct FontSynthesis {
+    /// A bold weight was requested, but the family has no bold face
+    pub bold: bool,
+    /// An italic / oblique style was requested, but the family has no slanted face
+    pub oblique: bool,
+}
+
+/// Value of a variation axis that has to be set to get the requested style
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct FontVariation {
+    pub tag: u32,
+    pub value: f32,
+}
+
+/// Result of `FontDatabase::query`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontMatch {
+    /// Index into `FontDatabase::faces`
+    pub face_id: usize,
+    pub synthesis: FontSynthesis,
+    /// Axis values for variable fonts (`wght`, `wdth`, `ital`)
+    pub variations: Vec<FontVariation>,
+}
+
+/// Collection of font faces that can be queried by family and style
+#[derive(Debug, Default, Clone)]
+pub struct FontDatabase {
+    pub sources: Vec<FontSource>,
+    pub faces: Vec<FontFaceInfo>,
+    /// Family that each generic family (`serif`, `sans-serif`, `monospace`, ...) resolves to
+    pub generic_families: BTreeMap<String, String>,
+}
+
+impl FontDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds all faces of the font file, returns the number of faces added
+    pub fn load_font_data(&mut self, bytes: Vec<u8>) -> usize {
+        let bytes: Rc<[u8]> = bytes.into();
+        let faces = parse_font_faces(&bytes, self.sources.len());
+        self.add_source(FontSource::Memory(bytes), faces)
+    }
+
+    /// Adds all faces of the font file at `path`, returns the number of faces added
+    pub fn load_font_file(&mut self, path: &Path) -> io::Result<usize> {
+        let bytes = fs::read(path)?;
+        let faces = parse_font_faces(&bytes, self.sources.len());
+        Ok(self.add_source(FontSource::File(path.to_path_buf()), faces))
+    }
+
+    /// Recursively adds all font files (TTF, OTF, TTC, WOFF, WOFF2) in the
+    /// directory, returns the number of faces added
+    ///
+    /// Files that can't be read or parsed are skipped
+    pub fn load_fonts_dir(&mut self, dir: &Path) -> usize {
+        let entries = match fs::read_dir(dir) {
+            Ok(o) => o,
+            Err(_) => return 0,
+        };
+
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .collect::<Vec<_>>();
+        // load the files in a deterministic order
+        paths.sort();
+
+        paths
+            .iter()
+            .map(|path| {
+                if path.is_dir() {
+                    self.load_fonts_dir(path)
+                } else if is_font_file(path) {
+                    self.load_font_file(path).unwrap_or(0)
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+
+    /// Sets the family that a generic family (`serif`, `sans-serif`, `monospace`, ...) resolves to
+    pub fn set_generic_family(&mut self, generic_family: &str, family: &str) {
+        self.generic_families
+            .insert(generic_family.to_lowercase(), family.to_string());
+    }
+
+    /// Returns the face that matches the query best (CSS Fonts 4, "font matching algorithm"):
+    /// the first family in the list that has any faces is selected, then the faces
+    /// are narrowed down by stretch, style and finally weight
+    pub fn query(&self, query: &FontQuery) -> Option<FontMatch> {
+        let candidates = query.families.iter().find_map(|family| {
+            let family = self
+                .generic_families
+                .get(&family.to_lowercase())
+                .unwrap_or(family);
+            let faces = self
+                .faces
+                .iter()
+                .enumerate()
+                .filter(|(_, face)| face.family_name.eq_ignore_ascii_case(family))
+                .collect::<Vec<_>>();
+            (!faces.is_empty()).then_some(faces)
+        })?;
+
+        let desired_stretch = query.stretch.get_percentage();
+        let candidates = retain_closest(candidates, |face| {
+            let stretch = face.stretch.clamp(desired_stretch);
+            match (desired_stretch <= 100.0, stretch <= desired_stretch) {
+                // narrower widths are preferred for condensed queries
+                (true, true) => (0, desired_stretch - stretch),
+                (true, false) => (1, stretch - desired_stretch),
+                (false, false) => (0, stretch - desired_stretch),
+                (false, true) => (1, desired_stretch - stretch),
+            }
+        });
+
+        let style_order = match query.style {
+            StyleFontStyle::Normal => [
+                StyleFontStyle::Normal,
+                StyleFontStyle::Oblique,
+                StyleFontStyle::Italic,
+            ],
+            StyleFontStyle::Italic => [
+                StyleFontStyle::Italic,
+                StyleFontStyle::Oblique,
+                StyleFontStyle::Normal,
+            ],
+            StyleFontStyle::Oblique => [
+                StyleFontStyle::Oblique,
+                StyleFontStyle::Italic,
+                StyleFontStyle::Normal,
+            ],
+        };
+        let candidates = retain_closest(candidates, |face| {
+            let order = style_order.iter().position(|s| *s == face.style);
+            (order.unwrap_or(style_order.len()) as u8, 0.0)
+        });
+
+        let desired_weight = query.weight.0;
+        let candidates = retain_closest(candidates, |face| {
+            let weight = face.weight.clamp(desired_weight);
+            if (400.0..=500.0).contains(&desired_weight) {
+                // weights up to 500 first, then lighter weights, then heavier weights
+                if weight >= desired_weight && weight <= 500.0 {
+                    (0, weight - desired_weight)
+                } else if weight < desired_weight {
+                    (1, desired_weight - weight)
+                } else {
+                    (2, weight - desired_weight)
+                }
+            } else if desired_weight < 400.0 {
+                if weight <= desired_weight {
+                    (0, desired_weight - weight)
+                } else {
+                    (1, weight - desired_weight)
+                }
+            } else if weight >= desired_weight {
+                (0, weight - desired_weight)
+            } else {
+                (1, desired_weight - weight)
+            }
+        });
+
+        let (face_id, face) = candidates.into_iter().next()?;
+
+        let has_slant_axis = face.get_axis(ITAL).is_some() || face.get_axis(SLNT).is_some();
+        let synthesis = FontSynthesis {
+            bold: desired_weight >= 600.0 && face.weight.max < 600.0,
+            oblique: query.style != StyleFontStyle::Normal
+                && face.style == StyleFontStyle::Normal
+                && !has_slant_axis,
+        };
+
+        let mut variations = Vec::new();
+        if let Some(axis) = face.get_axis(WGHT) {
+            variations.push(FontVariation {
+                tag: axis.tag,
+                value: face.weight.clamp(desired_weight),
+            });
+        }
+        if let Some(axis) = face.get_axis(WDTH) {
+            variations.push(FontVariation {
+                tag: axis.tag,
+                value: face.stretch.clamp(desired_stretch),
+            });
+        }
+        if let Some(axis) = face.get_axis(ITAL) {
+            let italic: f32 = if query.style == StyleFontStyle::Normal {
+                0.0
+            } else {
+                1.0
+            };
+            variations.push(FontVariation {
+                tag: axis.tag,
+                value: italic.max(axis.min_value).min(axis.max_value),
+            });
+        }
+
+        Some(FontMatch {
+            face_id,
+            synthesis,
+            variations,
+        })
+    }
+
+    /// Reads and parses the face with the index `face_id` (index into `faces`)
+    pub fn load_face(&self, face_id: usize) -> Option<FontData> {
+        let face = self.faces.get(face_id)?;
+        let bytes = self.sources.get(face.source)?.read()?;
+        let parsed = ParsedFont::from_bytes(&bytes, face.font_index)?;
+        Some(FontData {
+            bytes,
+            font_index: face.font_index as u32,
+            parsed,
+        })
+    }
+
+    /// Queries the best face and loads it, the synthesis hints of the match
+    /// are applied to the font (see `ParsedFont::synthesis`)
+    pub fn load_font(&self, query: &FontQuery) -> Option<(FontData, FontMatch)> {
+        let font_match = self.query(query)?;
+        let mut font = self.load_face(font_match.face_id)?;
+        font.parsed.synthesis = font_match.synthesis;
+        Some((font, font_match))
+    }
+
+    fn add_source(&mut self, source: FontSource, faces: Vec<FontFaceInfo>) -> usize {
+        let face_count = faces.len();
+        if face_count > 0 {
+            self.sources.push(source);
+            self.faces.extend(faces);
+        }
+        face_count
+    }
+}
+
+fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| FONT_FILE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Keeps only the candidates with the lowest `(priority, distance)`
+fn retain_closest(
+    candidates: Vec<(usize, &FontFaceInfo)>,
+    key: impl Fn(&FontFaceInfo) -> (u8, f32),
+) -> Vec<(usize, &FontFaceInfo)> {
+    let best = candidates
+        .iter()
+        .map(|(_, face)| key(face))
+        .min_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+
+    match best {
+        Some(best) => candidates
+            .into_iter()
+            .filter(|(_, face)| key(face) == best)
+            .collect(),
+        None => candidates,
+    }
+}
+
+/// Returns the number of faces in the font file (> 1 for TTC / OTC collections),
+/// zero if the file can't be parsed
+pub fn get_number_of_font_faces(font_bytes: &[u8]) -> usize {
+    ReadScope::new(font_bytes)
+        .read::<FontFile<'_>>()
+        .map_or(0, |font_file| get_number_of_faces(&font_file))
+}
+
+/// Lists the faces of a font file (all faces of TTC / OTC collections).
+///
+/// `FontFaceInfo::source` is always zero, a face can be loaded with
+/// `parse_font(font_bytes, face.font_index)`.
+pub fn get_font_faces(font_bytes: &[u8]) -> Vec<FontFaceInfo> {
+    parse_font_faces(font_bytes, 0)
+}
+
+/// Returns the number of faces in the font file (> 1 for TTC / OTC collections)
+fn get_number_of_faces(font_file: &FontFile<'_>) -> usize {
+    match font_file {
+        FontFile::OpenType(font) => match &font.data {
+            OpenTypeData::Single(_) => 1,
+            OpenTypeData::Collection(ttc) => ttc.offset_tables.len(),
+        },
+        FontFile::Woff(_) => 1,
+        FontFile::Woff2(font) => font
+            .collection_directory
+            .as_ref()
+            .map_or(1, |directory| directory.fonts().count()),
+    }
+}
+
+/// Reads the `name`, `OS/2` and `fvar` tables of all faces in the font file
+fn parse_font_faces(bytes: &[u8], source: usize) -> Vec<FontFaceInfo> {
+    let font_file = match ReadScope::new(bytes).read::<FontFile<'_>>() {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+
+    (0..get_number_of_faces(&font_file))
+        .filter_map(|font_index| {
+            let provider = font_file.table_provider(font_index).ok()?;
+            parse_font_face(&provider, source, font_index)
+        })
+        .collect()
+}
+
+fn parse_font_face<P: FontTableProvider>(
+    provider: &P,
+    source: usize,
+    font_index: usize,
+) -> Option<FontFaceInfo> {
+    use allsorts::get_name::fontcode_get_name;
+
+    let name_data = provider.table_data(tag::NAME).ok()??;
+    let get_name = |name_ids: &[u16]| {
+        name_ids.iter().find_map(|name_id| {
+            let name = fontcode_get_name(&name_data, *name_id).ok()??;
+            name.into_string().ok().filter(|n| !n.is_empty())
+        })
+    };
+    // typographic family / subfamily names group more than four styles into one family
+    let family_name = get_name(&[16, 1])?;
+    let subfamily_name = get_name(&[17, 2]).unwrap_or_default();
+    let postscript_name = get_name(&[6]).unwrap_or_default();
+
+    let os2 = provider
+        .table_data(tag::OS_2)
+        .ok()
+        .flatten()
+        .and_then(|data| ReadScope::new(&data).read_dep::<Os2>(data.len()).ok());
+
+    let variation_axes = provider
+        .table_data(tag::FVAR)
+        .ok()
+        .flatten()
+        .and_then(|data| {
+            let fvar = ttf_parser::fvar::Table::parse(&data)?;
+            Some(
+                fvar.axes
+                    .into_iter()
+                    .map(|axis| FontVariationAxis {
+                        tag: axis.tag.0,
+                        min_value: axis.min_value,
+                        default_value: axis.def_value,
+                        max_value: axis.max_value,
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .unwrap_or_default();
+
+    let axis_range = |axis_tag: u32| {
+        variation_axes
+            .iter()
+            .find(|a| a.tag == axis_tag)
+            .map(|a| FontValueRange {
+                min: a.min_value,
+                max: a.max_value,
+            })
+    };
+
+    let weight = axis_range(WGHT).unwrap_or_else(|| {
+        let weight_class = os2.as_ref().map_or(400, |os2| os2.us_weight_class);
+        FontValueRange::single(weight_class.clamp(1, 1000) as f32)
+    });
+    let stretch = axis_range(WDTH).unwrap_or_else(|| {
+        let width_class = os2.as_ref().map_or(5, |os2| os2.us_width_class);
+        FontValueRange::single(StyleFontStretch::from_width_class(width_class).get_percentage())
+    });
+
+    let fs_selection = os2.as_ref().map_or(0, |os2| os2.fs_selection);
+    let style = if fs_selection & FS_SELECTION_OBLIQUE != 0 {
+        StyleFontStyle::Oblique
+    } else if fs_selection & FS_SELECTION_ITALIC != 0 {
+        StyleFontStyle::Italic
+    } else {
+        StyleFontStyle::Normal
+    };
+
+    Some(FontFaceInfo {
+        source,
+        font_index,
+        family_name,
+        subfamily_name,
+        postscript_name,
+        weight,
+        stretch,
+        style,
+        variation_axes,
+    })
+}
+
+#[test]
+fn test_font_matching() {
+    use StyleFontStyle::*;
+
+    let face = |family: &str, weight: f32, stretch: f32, style: StyleFontStyle| FontFaceInfo {
+        source: 0,
+        font_index: 0,
+        family_name: family.to_string(),
+        subfamily_name: String::new(),
+        postscript_name: String::new(),
+        weight: FontValueRange::single(weight),
+        stretch: FontValueRange::single(stretch),
+        style,
+        variation_axes: Vec::new(),
+    };
+    let axis = |tag: u32, min_value: f32, default_value: f32, max_value: f32| FontVariationAxis {
+        tag,
+        min_value,
+        default_value,
+        max_value,
+    };
+
+    let mut database = FontDatabase::new();
+    database.faces = vec![
+        face("Sans", 300.0, 100.0, Normal),     // 0
+        face("Sans", 400.0, 100.0, Normal),     // 1
+        face("Sans", 600.0, 100.0, Normal),     // 2
+        face("Sans", 900.0, 100.0, Normal),     // 3
+        face("Sans", 400.0, 100.0, Italic),     // 4
+        face("Sans", 400.0, 75.0, Normal),      // 5
+        face("Sans", 400.0, 125.0, Normal),     // 6
+        face("Slanted", 400.0, 100.0, Oblique), // 7
+        face("Slanted", 400.0, 100.0, Italic),  // 8
+        face("Weights", 300.0, 100.0, Normal),  // 9
+        face("Weights", 550.0, 100.0, Normal),  // 10
+        face("Serif", 400.0, 100.0, Normal),    // 11
+        FontFaceInfo {
+            weight: FontValueRange {
+                min: 100.0,
+                max: 900.0,
+            },
+            variation_axes: vec![axis(WGHT, 100.0, 400.0, 900.0), axis(ITAL, 0.0, 0.0, 1.0)],
+            ..face("Variable", 400.0, 100.0, Normal)
+        }, // 12
+    ];
+    database.set_generic_family("sans-serif", "Sans");
+
+    let query =
+        |families: &[&str], weight: f32, stretch: StyleFontStretch, style: StyleFontStyle| {
+            database.query(&FontQuery {
+                families: families.iter().map(|f| f.to_string()).collect(),
+                weight: StyleFontWeight(weight),
+                stretch,
+                style,
+            })
+        };
+    let face_id = |family: &str, weight: f32, stretch: StyleFontStretch, style: StyleFontStyle| {
+        query(&[family], weight, stretch, style).map(|m| m.face_id)
+    };
+    use StyleFontStretch::{SemiCondensed, SemiExpanded, UltraCondensed, UltraExpanded};
+    let normal = StyleFontStretch::Normal;
+
+    // stretch is matched first: narrower faces for condensed queries, wider
+    // faces for expanded queries, then the closest face in the other direction
+    assert_eq!(face_id("Sans", 400.0, normal, Normal), Some(1));
+    assert_eq!(face_id("Sans", 400.0, SemiCondensed, Normal), Some(5));
+    assert_eq!(face_id("Sans", 400.0, SemiExpanded, Normal), Some(6));
+    assert_eq!(face_id("Sans", 400.0, UltraCondensed, Normal), Some(5));
+    assert_eq!(face_id("Sans", 400.0, UltraExpanded, Normal), Some(6));
+    // ... even if the weight and style are worse
+    assert_eq!(face_id("Sans", 700.0, SemiCondensed, Italic), Some(5));
+
+    // then the style: italic falls back to oblique and vice versa, normal prefers oblique
+    assert_eq!(face_id("Sans", 400.0, normal, Italic), Some(4));
+    assert_eq!(face_id("Sans", 400.0, normal, Oblique), Some(4));
+    assert_eq!(face_id("Slanted", 400.0, normal, Normal), Some(7));
+    assert_eq!(face_id("Slanted", 400.0, normal, Italic), Some(8));
+    assert_eq!(face_id("Slanted", 400.0, normal, Oblique), Some(7));
+
+    // then the weight: below 400, lighter weights are preferred, above 500 heavier weights
+    assert_eq!(face_id("Sans", 350.0, normal, Normal), Some(0));
+    assert_eq!(face_id("Sans", 200.0, normal, Normal), Some(0));
+    assert_eq!(face_id("Sans", 600.0, normal, Normal), Some(2));
+    assert_eq!(face_id("Sans", 700.0, normal, Normal), Some(3));
+    assert_eq!(face_id("Sans", 950.0, normal, Normal), Some(3));
+    // between 400 and 500, weights up to 500 are tried first, then lighter weights,
+    // then heavier weights: 300 is chosen over the closer 550
+    assert_eq!(face_id("Weights", 450.0, normal, Normal), Some(9));
+    assert_eq!(face_id("Weights", 520.0, normal, Normal), Some(10));
+
+    // the first family with any faces is used, generic families are resolved
+    assert_eq!(
+        query(&["Missing", "Serif", "Sans"], 400.0, normal, Normal).map(|m| m.face_id),
+        Some(11)
+    );
+    assert_eq!(face_id("Missing", 400.0, normal, Normal), None);
+    // generic families are only resolved if they were set
+    assert_eq!(face_id("monospace", 400.0, normal, Normal), None);
+    assert_eq!(
+        query(&["SANS-SERIF"], 400.0, normal, Normal).map(|m| m.face_id),
+        Some(1)
+    );
+
+    // bold and oblique are synthesized if the matched face doesn't have them
+    let synthesis = |family: &str, weight: f32, style: StyleFontStyle| {
+        query(&[family], weight, normal, style).map(|m| (m.synthesis.bold, m.synthesis.oblique))
+    };
+    assert_eq!(synthesis("Serif", 400.0, Normal), Some((false, false)));
+    assert_eq!(synthesis("Serif", 700.0, Normal), Some((true, false)));
+    assert_eq!(synthesis("Serif", 400.0, Italic), Some((false, true)));
+    assert_eq!(synthesis("Serif", 600.0, Oblique), Some((true, true)));
+    assert_eq!(synthesis("Sans", 700.0, Normal), Some((false, false)));
+    // the style is matched before the weight: the only italic face is regular
+    assert_eq!(synthesis("Sans", 700.0, Italic), Some((true, false)));
+    assert_eq!(synthesis("Weights", 700.0, Normal), Some((true, false)));
+
+    // variable fonts are set to the requested weight and slant instead
+    let font_match = query(&["Variable"], 700.0, normal, Italic).unwrap();
+    assert_eq!(font_match.face_id, 12);
+    assert_eq!(font_match.synthesis, FontSynthesis::default());
+    assert_eq!(
+        font_match.variations,
+        vec![
+            FontVariation {
+                tag: WGHT,
+                value: 700.0
+            },
+            FontVariation {
+                tag: ITAL,
+                value: 1.0
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_font_collection_faces() {
+    use crate::test_font::{build_collection, TestFont};
+
+    let regular = TestFont::default();
+    let bold = TestFont {
+        subfamily_name: "Bold".to_string(),
+        postscript_name: "Test-Bold".to_string(),
+        weight_class: 700,
+        fs_selection: 0x20,
+        ..TestFont::default()
+    };
+    let condensed_italic = TestFont {
+        family_name: "Test Condensed".to_string(),
+        subfamily_name: "Italic".to_string(),
+        postscript_name: "TestCondensed-Italic".to_string(),
+        width_class: 3,
+        fs_selection: FS_SELECTION_ITALIC,
+        ..TestFont::default()
+    };
+    let collection = build_collection(&[
+        regular.to_bytes(),
+        bold.to_bytes(),
+        condensed_italic.to_bytes(),
+    ]);
+
+    assert_eq!(get_number_of_font_faces(&regular.to_bytes()), 1);
+    assert_eq!(get_number_of_font_faces(&collection), 3);
+    assert_eq!(get_number_of_font_faces(b"not a font"), 0);
+
+    let faces = get_font_faces(&collection);
+    assert_eq!(
+        faces
+            .iter()
+            .map(|f| (
+                f.font_index,
+                f.family_name.as_str(),
+                f.subfamily_name.as_str(),
+                f.postscript_name.as_str(),
+                f.weight,
+                f.stretch,
+                f.style,
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                0,
+                "Test",
+                "Regular",
+                "Test-Regular",
+                FontValueRange::single(400.0),
+                FontValueRange::single(100.0),
+                StyleFontStyle::Normal,
+            ),
+            (
+                1,
+                "Test",
+                "Bold",
+                "Test-Bold",
+                FontValueRange::single(700.0),
+                FontValueRange::single(100.0),
+                StyleFontStyle::Normal,
+            ),
+            (
+                2,
+                "Test Condensed",
+                "Italic",
+                "TestCondensed-Italic",
+                FontValueRange::single(400.0),
+                FontValueRange::single(75.0),
+                StyleFontStyle::Italic,
+            ),
+        ]
+    );
+
+    // the faces of the collection are loaded by their index
+    let mut database = FontDatabase::new();
+    assert_eq!(database.load_font_data(collection), 3);
+    let (font, font_match) = database
+        .load_font(&FontQuery {
+            families: vec!["test".to_string()],
+            weight: StyleFontWeight::BOLD,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(font_match.face_id, 1);
+    assert_eq!(font.font_index, 1);
+    assert_eq!(font_match.synthesis, FontSynthesis::default());
+    assert_eq!(font.parsed.font_metrics.os2.us_weight_class, 700);
+}
+
+#[test]
+fn test_font_without_os2_table() {
+    use crate::test_font::TestFont;
+
+    // the face is indexed and loaded as a regular face, the metrics fall back
+    // to the `hhea` table and the em size
+    let font = TestFont {
+        os2_version: None,
+        ..TestFont::default()
+    };
+    let mut database = FontDatabase::new();
+    assert_eq!(database.load_font_data(font.to_bytes()), 1);
+    assert_eq!(database.faces[0].weight, FontValueRange::single(400.0));
+    assert_eq!(database.faces[0].stretch, FontValueRange::single(100.0));
+
+    let font = database.load_face(0).unwrap();
+    let font_metrics = &font.parsed.font_metrics;
+    assert_eq!(font_metrics.get_ascender_unscaled(), 800);
+    assert_eq!(font_metrics.get_descender_unscaled(), -200);
+    assert_eq!(font_metrics.get_x_height_unscaled(), 500);
+    assert_eq!(font_metrics.get_superscript_y_offset_unscaled(), 333);
+
+    // bytes that aren't a font (i.e. the file changed since it was indexed) aren't loaded
+    assert!(ParsedFont::from_bytes(b"not a font", 0).is_none());
+}
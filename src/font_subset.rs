@@ -0,0 +1,153 @@
+//! Subsetting of fonts to the glyphs that are actually used (for embedding fonts in PDFs)
+
+use crate::words::LayoutedGlyphs;
+use allsorts::{binary::read::ReadScope, font_data::FontData as FontFile};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+
+/// Font that only contains a subset of the glyphs of the original font
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontSubset {
+    /// TrueType (`glyf`) or CFF font, without layout tables (`GSUB`, `GPOS`)
+    pub bytes: Vec<u8>,
+    /// Maps the glyph IDs of the original font to the glyph IDs of the subset
+    /// (`.notdef` and the requested glyphs, the components that were added for
+    /// composite glyphs are not included)
+    pub glyph_mapping: BTreeMap<u16, u16>,
+}
+
+impl FontSubset {
+    pub fn get_new_glyph_id(&self, old_glyph_id: u16) -> Option<u16> {
+        self.glyph_mapping.get(&old_glyph_id).copied()
+    }
+
+    /// Replaces the glyph IDs of the original font with the IDs of the subset.
+    ///
+    /// Glyphs that are not in the `glyph_mapping` (i.e. were not passed to
+    /// [`subset`]) are turned into `.notdef` (glyph 0), so the subset has to
+    /// be created from all glyphs of the text, e.g. with
+    /// [`LayoutedGlyphs::get_glyph_ids`].
+    pub fn remap_glyphs(&self, glyphs: &mut LayoutedGlyphs) {
+        for glyph in glyphs.glyphs.iter_mut() {
+            glyph.index = u16::try_from(glyph.index)
+                .ok()
+                .and_then(|gid| self.get_new_glyph_id(gid))
+                .unwrap_or(0) as u32;
+        }
+    }
+}
+
+impl LayoutedGlyphs {
+    /// Returns the (deduplicated, sorted) IDs of all glyphs, e.g. to subset the font
+    pub fn get_glyph_ids(&self) -> Vec<u16> {
+        self.glyphs
+            .iter()
+            .filter_map(|g| u16::try_from(g.index).ok())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Creates a font that only contains the given glyphs (and the `.notdef` glyph).
+///
+/// The glyphs keep their order: `.notdef` stays glyph 0, the other glyphs are
+/// numbered in the order of `glyph_ids` (duplicates are ignored). Glyphs that
+/// are referenced by composite glyphs are added at the end of the font.
+///
+/// Returns `None` if the font can't be parsed or the glyph IDs are out of range.
+pub fn subset(font_bytes: &[u8], font_index: usize, glyph_ids: &[u16]) -> Option<FontSubset> {
+    let font_file = ReadScope::new(font_bytes).read::<FontFile<'_>>().ok()?;
+    let provider = font_file.table_provider(font_index).ok()?;
+
+    let mut glyph_mapping = BTreeMap::new();
+    let mut subset_glyph_ids = Vec::with_capacity(glyph_ids.len() + 1);
+    for glyph_id in core::iter::once(0).chain(glyph_ids.iter().copied()) {
+        if let Entry::Vacant(entry) = glyph_mapping.entry(glyph_id) {
+            entry.insert(subset_glyph_ids.len() as u16);
+            subset_glyph_ids.push(glyph_id);
+        }
+    }
+
+    let bytes = allsorts::subset::subset(&provider, &subset_glyph_ids).ok()?;
+
+    Some(FontSubset {
+        bytes,
+        glyph_mapping,
+    })
+}
+
+#[test]
+fn test_subset() {
+    use crate::test_font::{TestFont, TestGlyph};
+    use crate::words::GlyphInstance;
+
+    // glyph 3 = 'B', glyph 4 = 'C': a composite of the glyphs 1 and 3
+    let mut font = TestFont::default();
+    font.glyphs.push((500, TestGlyph::rect(0, 0, 300, 300)));
+    font.glyphs
+        .push((1000, TestGlyph::Composite(vec![(1, 0, 0), (3, 500, 0)])));
+    font.cmap.retain(|(c, _)| !matches!(c, 'B' | 'C'));
+    font.cmap.extend([('B', 3), ('C', 4)]);
+
+    // TrueType: the components of the composite glyph are appended to the subset,
+    // but they aren't part of the mapping (they weren't requested)
+    let ttf = font.to_bytes();
+    let ttf_subset = subset(&ttf, 0, &[4, 2, 4]).unwrap();
+    assert_eq!(
+        ttf_subset.glyph_mapping,
+        [(0, 0), (4, 1), (2, 2)].into_iter().collect()
+    );
+    let face = ttf_parser::Face::parse(&ttf_subset.bytes, 0).unwrap();
+    assert_eq!(face.number_of_glyphs(), 5);
+    // allsorts writes a Mac Roman `cmap` subtable, which `Face::glyph_index` ignores
+    let cmap = |face: &ttf_parser::Face<'_>, c: char| {
+        let subtable = face.tables().cmap?.subtables.get(0)?;
+        subtable.glyph_index(c as u32).map(|g| g.0)
+    };
+    assert_eq!(cmap(&face, 'C'), Some(1));
+    assert_eq!(cmap(&face, ' '), Some(2));
+    let bbox = |face: &ttf_parser::Face<'_>, glyph_index: u16| {
+        let b = face
+            .glyph_bounding_box(ttf_parser::GlyphId(glyph_index))
+            .unwrap();
+        (b.x_min, b.y_min, b.x_max, b.y_max)
+    };
+    assert_eq!(bbox(&face, 0), (50, 0, 450, 700));
+    assert_eq!(bbox(&face, 1), (100, -200, 800, 700));
+    assert_eq!(bbox(&face, 3), (100, -200, 400, 700));
+    assert_eq!(bbox(&face, 4), (0, 0, 300, 300));
+    assert_eq!(face.glyph_hor_advance(ttf_parser::GlyphId(1)), Some(1000));
+
+    // CFF: composite glyphs are flattened by the test font
+    let otf = font.to_cff_bytes();
+    let cff_subset = subset(&otf, 0, &[3, 1, 4]).unwrap();
+    assert_eq!(
+        cff_subset.glyph_mapping,
+        [(0, 0), (3, 1), (1, 2), (4, 3)].into_iter().collect()
+    );
+    let face = ttf_parser::Face::parse(&cff_subset.bytes, 0).unwrap();
+    assert!(face.tables().cff.is_some());
+    assert_eq!(face.number_of_glyphs(), 4);
+    assert_eq!(cmap(&face, 'B'), Some(1));
+    assert_eq!(bbox(&face, 0), (50, 0, 450, 700));
+    assert_eq!(bbox(&face, 1), (0, 0, 300, 300));
+    assert_eq!(bbox(&face, 2), (100, -200, 400, 700));
+    assert_eq!(bbox(&face, 3), (100, -200, 800, 700));
+
+    // glyphs outside of the subset (or out of the u16 range) become `.notdef`
+    let mut glyphs = LayoutedGlyphs {
+        glyphs: [4, 2, 1, 3, 70_000]
+            .into_iter()
+            .map(|index| GlyphInstance {
+                index,
+                ..Default::default()
+            })
+            .collect(),
+    };
+    assert_eq!(glyphs.get_glyph_ids(), vec![1, 2, 3, 4]);
+    ttf_subset.remap_glyphs(&mut glyphs);
+    let indices = glyphs.glyphs.iter().map(|g| g.index).collect::<Vec<_>>();
+    assert_eq!(indices, vec![1, 2, 0, 0, 0]);
+
+    assert!(subset(&ttf, 0, &[5]).is_none());
+}
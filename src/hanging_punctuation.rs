@@ -0,0 +1,94 @@
+//! Punctuation at the edges of a line: `hanging-punctuation` places whole punctuation
+//! marks outside of the line box, optical margin alignment lets the glyphs of
+//! punctuation marks protrude partially, so that the margins look straight
+//!
+//! See [CSS Text Level 3](https://www.w3.org/TR/css-text-3/#hanging-punctuation-property)
+
+/// Quotation marks (Unicode categories Pi and Pf plus the ASCII quotes), which
+/// are opening or closing marks depending on the language
+const QUOTES: &[char] = &[
+    '\'', '"', '\u{AB}', '\u{BB}', '\u{2018}', '\u{2019}', '\u{201B}', '\u{201C}', '\u{201D}',
+    '\u{201F}', '\u{2039}', '\u{203A}', '\u{2E02}', '\u{2E03}', '\u{2E04}', '\u{2E05}', '\u{2E09}',
+    '\u{2E0A}', '\u{2E0C}', '\u{2E0D}', '\u{2E1C}', '\u{2E1D}', '\u{2E20}', '\u{2E21}',
+];
+
+/// Opening brackets (Unicode category Ps)
+const OPENING_BRACKETS: &[char] = &[
+    '(', '[', '{', '\u{201A}', '\u{201E}', '\u{2045}', '\u{207D}', '\u{208D}', '\u{2329}',
+    '\u{27E6}', '\u{27E8}', '\u{27EA}', '\u{3008}', '\u{300A}', '\u{300C}', '\u{300E}', '\u{3010}',
+    '\u{3014}', '\u{3016}', '\u{3018}', '\u{301A}', '\u{301D}', '\u{FE59}', '\u{FE5B}', '\u{FE5D}',
+    '\u{FF08}', '\u{FF3B}', '\u{FF5B}', '\u{FF5F}', '\u{FF62}',
+];
+
+/// Closing brackets (Unicode category Pe)
+const CLOSING_BRACKETS: &[char] = &[
+    ')', ']', '}', '\u{2046}', '\u{207E}', '\u{208E}', '\u{232A}', '\u{27E7}', '\u{27E9}',
+    '\u{27EB}', '\u{3009}', '\u{300B}', '\u{300D}', '\u{300F}', '\u{3011}', '\u{3015}', '\u{3017}',
+    '\u{3019}', '\u{301B}', '\u{301E}', '\u{301F}', '\u{FE5A}', '\u{FE5C}', '\u{FE5E}', '\u{FF09}',
+    '\u{FF3D}', '\u{FF5D}', '\u{FF60}', '\u{FF63}',
+];
+
+/// Full stops and commas that hang with `allow-end` / `force-end`
+const STOPS_AND_COMMAS: &[char] = &[
+    ',', '.', '\u{60C}', '\u{6D4}', '\u{3001}', '\u{3002}', '\u{FE50}', '\u{FE51}', '\u{FE52}',
+    '\u{FF0C}', '\u{FF0E}', '\u{FF61}', '\u{FF64}',
+];
+
+/// How far the glyphs protrude past the start and end of the line with optical margin
+/// alignment (as a fraction of their advance), similar to the defaults of `microtype`
+const PROTRUSION: &[(char, f32, f32)] = &[
+    ('.', 0.0, 0.7),
+    (',', 0.0, 0.7),
+    (':', 0.0, 0.5),
+    (';', 0.0, 0.5),
+    ('!', 0.0, 0.2),
+    ('?', 0.0, 0.2),
+    ('\u{2026}', 0.0, 0.3), // …
+    ('-', 0.7, 0.7),
+    ('\u{AD}', 0.7, 0.7),   // soft hyphen
+    ('\u{2010}', 0.7, 0.7), // hyphen
+    ('\u{2011}', 0.7, 0.7), // non-breaking hyphen
+    ('\u{2013}', 0.4, 0.3), // –
+    ('\u{2014}', 0.3, 0.2), // —
+    ('\'', 0.7, 0.7),
+    ('\u{2018}', 0.7, 0.7), // ‘
+    ('\u{2019}', 0.7, 0.7), // ’
+    ('\u{201A}', 0.7, 0.7), // ‚
+    ('"', 0.5, 0.5),
+    ('\u{201C}', 0.5, 0.5), // “
+    ('\u{201D}', 0.5, 0.5), // ”
+    ('\u{201E}', 0.5, 0.5), // „
+    ('\u{2039}', 0.4, 0.4), // ‹
+    ('\u{203A}', 0.4, 0.4), // ›
+    ('\u{AB}', 0.2, 0.2),   // «
+    ('\u{BB}', 0.2, 0.2),   // »
+    ('(', 0.1, 0.0),
+    (')', 0.0, 0.1),
+    ('[', 0.1, 0.0),
+    (']', 0.0, 0.1),
+];
+
+/// Whether the character hangs at the start of the first line with `hanging-punctuation: first`
+pub(crate) fn is_opening_punctuation(c: char) -> bool {
+    OPENING_BRACKETS.contains(&c) || QUOTES.contains(&c)
+}
+
+/// Whether the character hangs at the end of the last line with `hanging-punctuation: last`
+pub(crate) fn is_closing_punctuation(c: char) -> bool {
+    CLOSING_BRACKETS.contains(&c) || QUOTES.contains(&c)
+}
+
+/// Whether the character hangs at the end of a line with `hanging-punctuation: allow-end / force-end`
+pub(crate) fn is_stop_or_comma(c: char) -> bool {
+    STOPS_AND_COMMAS.contains(&c)
+}
+
+/// Returns how far the glyph of the character protrudes past the start and
+/// end of a line with optical margin alignment (as a fraction of its advance)
+pub(crate) fn get_protrusion(c: char) -> (f32, f32) {
+    PROTRUSION
+        .iter()
+        .find(|(p, _, _)| *p == c)
+        .map(|(_, start, end)| (*start, *end))
+        .unwrap_or((0.0, 0.0))
+}
@@ -70,23 +70,59 @@
 )]
 #![deny(dead_code)]
 
+mod color_font;
 mod css;
+mod document;
+mod font_database;
+mod font_subset;
+mod hanging_punctuation;
 mod logical;
+mod pdf;
 mod ui_solver;
 mod words;
 
+#[cfg(test)]
+mod test_font;
+
+mod text_decoration;
 mod text_layout;
 mod text_shaping;
+mod text_transform;
 
-pub use css::StyleTextAlign;
+pub use color_font::{
+    ColorCompositeMode, ColorExtend, ColorGlyphColor, ColorGlyphLayer, ColorGlyphTables, ColorLine,
+    ColorLinearGradient, ColorPaint, ColorRadialGradient, ColorStop, ColorSweepGradient,
+    ColorTransform, ColorU, GlyphBitmap, GlyphData, GlyphKind, GlyphSvg,
+};
+pub use css::{
+    FontData, FontMetrics, StyleColumnFill, StyleFontStretch, StyleFontStyle, StyleFontVariantCaps,
+    StyleFontWeight, StyleHangingPunctuation, StyleHangingPunctuationEnd, StyleInlineVerticalAlign,
+    StyleLineHeight, StyleOverflowWrap, StyleTextAlign, StyleTextDecorationLine,
+    StyleTextDecorationStyle, StyleTextOverflow, StyleTextSpacing, StyleTextTransform,
+    StyleWhiteSpace, StyleWordBreak, StyleWritingMode,
+};
+pub use document::{Document, Paragraph, ParagraphLayout, ParagraphStyle};
+pub use font_database::{
+    get_font_faces, get_number_of_font_faces, FontDatabase, FontFaceInfo, FontMatch, FontQuery,
+    FontSource, FontSynthesis, FontValueRange, FontVariation, FontVariationAxis,
+};
+pub use font_subset::{subset, FontSubset};
 pub use logical::{LogicalPosition, LogicalRect, LogicalSize};
+pub use pdf::{PdfDocument, PdfPage, PDF_PAGE_SIZE_A4, PDF_PAGE_SIZE_LETTER};
+pub use text_decoration::{get_text_decorations, TextDecorationSegment};
 pub use text_layout::{
-    parse_font, position_words, shape_words, split_text_into_words,
-    word_positions_to_inline_text_layout,
+    get_height_for_width, get_intrinsic_sizes, parse_font, position_words, shape_text_overflow,
+    shape_words, shape_words_vertical, shape_words_with_options, split_text_into_paragraphs,
+    split_text_into_words, word_positions_to_inline_text_layout, OBJECT_REPLACEMENT_CHARACTER,
 };
 pub use text_shaping::ParsedFont;
-pub use ui_solver::{InlineTextLayout, ResolvedTextLayoutOptions};
+pub use text_transform::apply_text_transform;
+pub use ui_solver::{
+    InlineObject, InlineSpan, InlineTextLayout, IntrinsicSizes, LineTruncation,
+    ResolvedTextLayoutOptions, TextContainerFragment, WordFragment,
+};
 pub use words::{
-    get_inline_text, InlineLine, InlineText, InlineWord, ShapedWord, ShapedWords, Word, Token,
-    Words,
+    get_inline_text, GlyphCluster, GlyphInfo, GlyphInstance, GlyphOrientation, InlineLine,
+    InlineObjectContents, InlineText, InlineWord, LayoutedGlyphs, ShapedWord, ShapedWords,
+    TextNormalization, TextOffsetChange, TextOffsetMap, Token, Word, Words,
 };
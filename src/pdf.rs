@@ -0,0 +1,737 @@
+//! Minimal PDF writer that draws laid out text with embedded, subsetted fonts
+//!
+//! The coordinates of the layout are used as PDF points (1/72 inch), the origin
+//! of a page is its top left corner (like in the layout, y pointing down).
+
+use crate::{
+    css::FontData,
+    font_database::get_font_faces,
+    font_subset::{subset, FontSubset},
+    logical::{LogicalPosition, LogicalRect, LogicalSize},
+    words::{InlineLine, InlineText, InlineWord, ShapedWords, Words},
+};
+use core::{fmt::Write, ops::Range};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Size of an A4 page in points
+pub const PDF_PAGE_SIZE_A4: LogicalSize = LogicalSize::new(595.0, 842.0);
+/// Size of a US letter page in points
+pub const PDF_PAGE_SIZE_LETTER: LogicalSize = LogicalSize::new(612.0, 792.0);
+
+/// Maximum number of entries in a `beginbfchar` block of a `ToUnicode` CMap
+const MAX_BFCHAR_ENTRIES: usize = 100;
+
+/// PDF document with text on one or more pages
+#[derive(Debug, Default)]
+pub struct PdfDocument<'a> {
+    pub pages: Vec<PdfPage>,
+    fonts: Vec<PdfFont<'a>>,
+}
+
+/// Page of a `PdfDocument`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfPage {
+    /// Size of the page in points
+    pub size: LogicalSize,
+    text_runs: Vec<PdfTextRun>,
+}
+
+#[derive(Debug)]
+struct PdfFont<'a> {
+    font: &'a FontData,
+    /// Glyphs that are drawn with the font (glyph IDs of the original font)
+    used_glyphs: BTreeSet<u16>,
+    /// Text that each glyph represents (for the `ToUnicode` CMap)
+    glyph_text: BTreeMap<u16, String>,
+}
+
+/// Glyphs that are drawn with a single `TJ` operator: same font, font size and baseline
+#[derive(Debug, Clone, PartialEq)]
+struct PdfTextRun {
+    font_id: usize,
+    font_size: f32,
+    /// Glyph ID (of the original font) and the position of its origin on the page
+    glyphs: Vec<(u16, LogicalPosition)>,
+    /// The glyphs are rotated 90° clockwise (sideways glyphs in vertical text)
+    rotated: bool,
+}
+
+impl<'a> PdfDocument<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a font that text can be drawn with, returns the ID of the font
+    pub fn add_font(&mut self, font: &'a FontData) -> usize {
+        self.fonts.push(PdfFont {
+            font,
+            used_glyphs: BTreeSet::new(),
+            glyph_text: BTreeMap::new(),
+        });
+        self.fonts.len() - 1
+    }
+
+    /// Adds an empty page (size in points), returns the index of the page
+    pub fn add_page(&mut self, size: LogicalSize) -> usize {
+        self.pages.push(PdfPage {
+            size,
+            text_runs: Vec::new(),
+        });
+        self.pages.len() - 1
+    }
+
+    /// Draws the text on the page, `origin` is the position of the top left
+    /// corner of the text on the page.
+    ///
+    /// The `words` and `shaped_words` that the text was laid out from are used to
+    /// map the glyphs back to the text, so that the text in the PDF can be searched and copied.
+    pub fn add_text(
+        &mut self,
+        page_index: usize,
+        font_id: usize,
+        origin: LogicalPosition,
+        text: &InlineText,
+        words: &Words,
+        shaped_words: &ShapedWords,
+    ) {
+        self.add_lines(page_index, font_id, origin, text, 0..text.lines.len());
+        self.add_glyph_text(font_id, words, shaped_words);
+    }
+
+    /// Draws the text on as many new pages as necessary: the lines are placed
+    /// in the `content_rect` of each page (e.g. the page without the margins) and a
+    /// new page is started when the next line doesn't fit anymore.
+    ///
+    /// Returns the indices of the pages that were added.
+    ///
+    /// NOTE: the lines are split by their physical position, so vertical text
+    /// (where every line spans the whole height of the text) is never split.
+    pub fn add_text_flow(
+        &mut self,
+        font_id: usize,
+        page_size: LogicalSize,
+        content_rect: LogicalRect,
+        text: &InlineText,
+        words: &Words,
+        shaped_words: &ShapedWords,
+    ) -> Range<usize> {
+        let first_page = self.pages.len();
+
+        let mut page_lines = 0..0;
+        let mut page_top = 0.0;
+        for (line_index, line) in text.lines.iter().enumerate() {
+            let bounds = text.get_physical_rect(line.bounds);
+            let line_bottom = bounds.origin.y + bounds.size.height;
+            // a line that is higher than a whole page still gets a page of its own
+            if line_bottom - page_top > content_rect.size.height && !page_lines.is_empty() {
+                let page_index = self.add_page(page_size);
+                let origin = content_rect.origin - LogicalPosition::new(0.0, page_top);
+                self.add_lines(page_index, font_id, origin, text, page_lines.clone());
+                page_lines = line_index..line_index;
+                page_top = bounds.origin.y;
+            }
+            page_lines.end = line_index + 1;
+        }
+
+        if !page_lines.is_empty() || self.pages.len() == first_page {
+            let page_index = self.add_page(page_size);
+            let origin = content_rect.origin - LogicalPosition::new(0.0, page_top);
+            self.add_lines(page_index, font_id, origin, text, page_lines);
+        }
+
+        self.add_glyph_text(font_id, words, shaped_words);
+
+        first_page..self.pages.len()
+    }
+
+    /// Serializes the document, returns `None` if one of the fonts can't be subsetted
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        let mut writer = PdfWriter::default();
+        let catalog_id = writer.reserve_object();
+        let pages_id = writer.reserve_object();
+
+        let font_ids = self
+            .fonts
+            .iter()
+            .enumerate()
+            .map(|(font_id, font)| write_font(&mut writer, font_id, font))
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut font_resources = String::new();
+        for (font_id, (object_id, _)) in font_ids.iter().enumerate() {
+            let _ = write!(font_resources, "/F{} {} 0 R ", font_id, object_id);
+        }
+
+        let mut page_ids = Vec::with_capacity(self.pages.len());
+        for page in self.pages.iter() {
+            let contents = get_page_contents(page, &self.fonts, &font_ids);
+            let contents_id = writer.add_stream("", contents.as_bytes());
+            let page_id = writer.add_object(format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << {}>> >> /Contents {} 0 R >>",
+                pages_id,
+                fmt_number(page.size.width),
+                fmt_number(page.size.height),
+                font_resources,
+                contents_id,
+            ));
+            page_ids.push(page_id);
+        }
+
+        let kids = page_ids
+            .iter()
+            .map(|id| format!("{} 0 R", id))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writer.set_object(
+            pages_id,
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids,
+                page_ids.len()
+            ),
+        );
+        writer.set_object(
+            catalog_id,
+            format!("<< /Type /Catalog /Pages {} 0 R >>", pages_id),
+        );
+
+        Some(writer.finish(catalog_id))
+    }
+
+    fn add_lines(
+        &mut self,
+        page_index: usize,
+        font_id: usize,
+        origin: LogicalPosition,
+        text: &InlineText,
+        lines: Range<usize>,
+    ) {
+        let (page, font) = match (self.pages.get_mut(page_index), self.fonts.get_mut(font_id)) {
+            (Some(page), Some(font)) => (page, font),
+            _ => return,
+        };
+
+        for line in &text.lines[lines] {
+            add_line(page, font, font_id, origin, text, line);
+        }
+    }
+
+    fn add_glyph_text(&mut self, font_id: usize, words: &Words, shaped_words: &ShapedWords) {
+        let font = match self.fonts.get_mut(font_id) {
+            Some(font) => font,
+            None => return,
+        };
+
+        for shaped_word in shaped_words.items.iter() {
+            for cluster in shaped_word.clusters.iter() {
+                let cluster_text = match words.internal_str.get(cluster.text_range.clone()) {
+                    Some(s) => s,
+                    None => continue,
+                };
+                // a ligature maps to all of its characters, the other glyphs of a
+                // decomposed character don't represent any text
+                let first_glyph = match shaped_word.glyph_infos.get(cluster.glyph_range.start) {
+                    Some(glyph) => glyph.info.glyph.glyph_index,
+                    None => continue,
+                };
+                font.glyph_text
+                    .entry(first_glyph)
+                    .or_insert_with(|| cluster_text.to_string());
+            }
+        }
+    }
+}
+
+fn add_line(
+    page: &mut PdfPage,
+    font: &mut PdfFont<'_>,
+    font_id: usize,
+    origin: LogicalPosition,
+    text: &InlineText,
+    line: &InlineLine,
+) {
+    for word in line.words.iter().filter_map(InlineWord::get_text_content) {
+        for glyph in word.glyphs.iter() {
+            let instance = text.get_glyph_instance(line, word, glyph);
+            let glyph_id = instance.index as u16;
+            let font_size = word.font_size_px * instance.scale;
+            let point = origin + instance.point;
+            font.used_glyphs.insert(glyph_id);
+
+            // continue the previous run if the glyph is on the same baseline
+            match page.text_runs.last_mut() {
+                Some(run)
+                    if run.font_id == font_id
+                        && run.font_size == font_size
+                        && !run.rotated
+                        && !instance.rotated
+                        && run.glyphs.last().map(|(_, p)| p.y) == Some(point.y) =>
+                {
+                    run.glyphs.push((glyph_id, point));
+                }
+                _ => page.text_runs.push(PdfTextRun {
+                    font_id,
+                    font_size,
+                    glyphs: vec![(glyph_id, point)],
+                    rotated: instance.rotated,
+                }),
+            }
+        }
+    }
+}
+
+/// Writes the `Type0` font with all its dependencies, returns the object ID
+/// of the font and the subset (for the glyph IDs in the content streams)
+fn write_font(
+    writer: &mut PdfWriter,
+    font_id: usize,
+    font: &PdfFont<'_>,
+) -> Option<(usize, FontSubset)> {
+    let data = font.font;
+    let parsed = &data.parsed;
+    let metrics = &parsed.font_metrics;
+    let glyph_ids = font.used_glyphs.iter().copied().collect::<Vec<_>>();
+    let font_subset = subset(&data.bytes, data.font_index as usize, &glyph_ids)?;
+
+    // PDF glyph space: 1000 units per em
+    let units_per_em = metrics.head.units_per_em.max(1) as f32;
+    let scale = |value: f32| fmt_number(value * 1000.0 / units_per_em);
+
+    let postscript_name = get_font_faces(&data.bytes)
+        .into_iter()
+        .find(|face| face.font_index == data.font_index as usize)
+        .map(|face| face.postscript_name)
+        .unwrap_or_default();
+    let postscript_name = postscript_name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect::<String>();
+    let base_font = format!(
+        "{}+{}",
+        get_subset_tag(font_id),
+        if postscript_name.is_empty() {
+            format!("Font{}", font_id)
+        } else {
+            postscript_name
+        }
+    );
+
+    let is_cff = font_subset.bytes.starts_with(b"OTTO");
+    let font_file_id = if is_cff {
+        writer.add_stream("/Subtype /OpenType ", &font_subset.bytes)
+    } else {
+        writer.add_stream(
+            &format!("/Length1 {} ", font_subset.bytes.len()),
+            &font_subset.bytes,
+        )
+    };
+
+    let (x_min, y_min, x_max, y_max) = metrics.get_bounding_box_unscaled();
+    // symbolic (the glyphs are not in the standard Latin character set) + italic
+    let flags = if metrics.get_italic_angle() != 0.0 || parsed.synthesis.oblique {
+        4 | (1 << 6)
+    } else {
+        4
+    };
+    let font_descriptor_id = writer.add_object(format!(
+        "<< /Type /FontDescriptor /FontName /{} /Flags {} /FontBBox [{} {} {} {}] \
+         /ItalicAngle {} /Ascent {} /Descent {} /CapHeight {} /StemV 80 /{} {} 0 R >>",
+        base_font,
+        flags,
+        scale(x_min as f32),
+        scale(y_min as f32),
+        scale(x_max as f32),
+        scale(y_max as f32),
+        fmt_number(metrics.get_italic_angle()),
+        scale(metrics.get_ascender_unscaled() as f32),
+        scale(metrics.get_descender_unscaled() as f32),
+        scale(metrics.get_cap_height_unscaled() as f32),
+        if is_cff { "FontFile3" } else { "FontFile2" },
+        font_file_id,
+    ));
+
+    // widths of the glyphs, indexed by the glyph IDs of the subset
+    let mut widths = String::new();
+    for (old_glyph_id, new_glyph_id) in font_subset.glyph_mapping.iter() {
+        let advance = parsed.get_horizontal_advance(*old_glyph_id);
+        let _ = write!(widths, "{} [{}] ", new_glyph_id, scale(advance as f32));
+    }
+
+    let cid_font_id = writer.add_object(format!(
+        "<< /Type /Font /Subtype /{} /BaseFont /{} \
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+         /FontDescriptor {} 0 R /DW 0 /W [{}]{} >>",
+        if is_cff {
+            "CIDFontType0"
+        } else {
+            "CIDFontType2"
+        },
+        base_font,
+        font_descriptor_id,
+        widths,
+        if is_cff {
+            ""
+        } else {
+            " /CIDToGIDMap /Identity"
+        },
+    ));
+
+    let to_unicode = get_to_unicode_cmap(font, &font_subset);
+    let to_unicode_id = writer.add_stream("", to_unicode.as_bytes());
+
+    let type0_font_id = writer.add_object(format!(
+        "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H \
+         /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+        base_font, cid_font_id, to_unicode_id,
+    ));
+
+    Some((type0_font_id, font_subset))
+}
+
+/// Returns the content stream with the text of the page
+fn get_page_contents(
+    page: &PdfPage,
+    fonts: &[PdfFont<'_>],
+    font_ids: &[(usize, FontSubset)],
+) -> String {
+    let mut contents = String::new();
+    let page_height = page.size.height;
+
+    for run in page.text_runs.iter() {
+        let (font, font_subset) = match (fonts.get(run.font_id), font_ids.get(run.font_id)) {
+            (Some(font), Some((_, font_subset))) => (font, font_subset),
+            _ => continue,
+        };
+        let parsed = &font.font.parsed;
+        let units_per_em = parsed.font_metrics.head.units_per_em.max(1) as f32;
+        let (_, first_point) = match run.glyphs.first() {
+            Some(s) => *s,
+            None => continue,
+        };
+
+        // `ET` doesn't reset the text render mode and line width of synthetic bold,
+        // so every run saves and restores the graphics state
+        let _ = write!(
+            contents,
+            "q\nBT\n/F{} {} Tf\n",
+            run.font_id,
+            fmt_number(run.font_size)
+        );
+
+        // synthetic bold: the outlines are stroked in addition to being filled
+        if parsed.synthesis.bold {
+            let stroke_width = parsed.get_synthetic_bold_strength() / units_per_em * run.font_size;
+            let _ = writeln!(contents, "2 Tr {} w", fmt_number(stroke_width));
+        }
+
+        // text matrix: rotation for sideways glyphs, skew for synthetic oblique
+        let skew = if parsed.synthesis.oblique {
+            parsed.get_synthetic_oblique_skew()
+        } else {
+            0.0
+        };
+        let (a, b, c, d) = if run.rotated {
+            (0.0, -1.0, 1.0, -skew)
+        } else {
+            (1.0, 0.0, skew, 1.0)
+        };
+        let _ = write!(
+            contents,
+            "{} {} {} {} {} {} Tm\n[",
+            fmt_number(a),
+            fmt_number(b),
+            fmt_number(c),
+            fmt_number(d),
+            fmt_number(first_point.x),
+            fmt_number(page_height - first_point.y),
+        );
+
+        // the glyphs are moved by their advance, `TJ` corrects the difference
+        // to the actual position (kerning, justification, letter spacing, ...)
+        let mut expected_position = first_point;
+        for (glyph_id, point) in run.glyphs.iter() {
+            let distance = if run.rotated {
+                point.y - expected_position.y
+            } else {
+                point.x - expected_position.x
+            };
+            let adjustment = -distance / run.font_size * 1000.0;
+            if adjustment.abs() > 0.001 {
+                let _ = write!(contents, "{} ", fmt_number(adjustment));
+            }
+
+            let new_glyph_id = font_subset.get_new_glyph_id(*glyph_id).unwrap_or(0);
+            let _ = write!(contents, "<{:04X}> ", new_glyph_id);
+
+            let advance =
+                parsed.get_horizontal_advance(*glyph_id) as f32 / units_per_em * run.font_size;
+            expected_position = if run.rotated {
+                LogicalPosition::new(point.x, point.y + advance)
+            } else {
+                LogicalPosition::new(point.x + advance, point.y)
+            };
+        }
+        contents.push_str("] TJ\nET\nQ\n");
+    }
+
+    contents
+}
+
+/// Returns the `ToUnicode` CMap that maps the glyph IDs of the subset to the text
+fn get_to_unicode_cmap(font: &PdfFont<'_>, font_subset: &FontSubset) -> String {
+    let mappings = font
+        .glyph_text
+        .iter()
+        .filter_map(|(old_glyph_id, text)| {
+            let new_glyph_id = font_subset.get_new_glyph_id(*old_glyph_id)?;
+            let utf16 = text
+                .encode_utf16()
+                .map(|u| format!("{:04X}", u))
+                .collect::<String>();
+            Some((new_glyph_id, utf16))
+        })
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n\
+         12 dict begin\n\
+         begincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n\
+         /CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    for chunk in mappings.chunks(MAX_BFCHAR_ENTRIES) {
+        let _ = writeln!(cmap, "{} beginbfchar", chunk.len());
+        for (glyph_id, utf16) in chunk {
+            let _ = writeln!(cmap, "<{:04X}> <{}>", glyph_id, utf16);
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str(
+        "endcmap\n\
+         CMapName currentdict /CMap defineresource pop\n\
+         end\n\
+         end\n",
+    );
+    cmap
+}
+
+/// Six uppercase letters that mark the font as a subset (e.g. `AAAAAB+Helvetica`)
+fn get_subset_tag(font_id: usize) -> String {
+    let mut id = font_id;
+    let mut tag = [b'A'; 6];
+    for letter in tag.iter_mut().rev() {
+        *letter = b'A' + (id % 26) as u8;
+        id /= 26;
+    }
+    String::from_utf8_lossy(&tag).into_owned()
+}
+
+/// Formats a number with at most three decimals (PDF doesn't support exponents)
+fn fmt_number(value: f32) -> String {
+    let s = format!("{:.3}", value);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    match s {
+        "" | "-0" => "0".to_string(),
+        s => s.to_string(),
+    }
+}
+
+/// Collects the objects of the document and writes the cross-reference table
+#[derive(Debug, Default)]
+struct PdfWriter {
+    /// Serialized objects, the object ID is the index + 1
+    objects: Vec<Vec<u8>>,
+}
+
+impl PdfWriter {
+    fn reserve_object(&mut self) -> usize {
+        self.objects.push(Vec::new());
+        self.objects.len()
+    }
+
+    fn set_object(&mut self, id: usize, object: String) {
+        self.objects[id - 1] = object.into_bytes();
+    }
+
+    fn add_object(&mut self, object: String) -> usize {
+        let id = self.reserve_object();
+        self.set_object(id, object);
+        id
+    }
+
+    /// Adds a stream, `dictionary` contains the entries besides `/Length`
+    fn add_stream(&mut self, dictionary: &str, data: &[u8]) -> usize {
+        let mut object =
+            format!("<< {}/Length {} >>\nstream\n", dictionary, data.len()).into_bytes();
+        object.extend_from_slice(data);
+        object.extend_from_slice(b"\nendstream");
+        let id = self.reserve_object();
+        self.objects[id - 1] = object;
+        id
+    }
+
+    fn finish(self, catalog_id: usize) -> Vec<u8> {
+        // the binary comment marks the file as binary for transfer programs
+        let mut bytes = b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        for (index, object) in self.objects.iter().enumerate() {
+            offsets.push(bytes.len());
+            bytes.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            bytes.extend_from_slice(object);
+            bytes.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_offset = bytes.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", self.objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(xref, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            xref,
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.objects.len() + 1,
+            catalog_id,
+            xref_offset
+        );
+        bytes.extend_from_slice(xref.as_bytes());
+        bytes
+    }
+}
+
+#[test]
+fn test_pdf_document() {
+    use crate::{
+        css::{StyleTextSpacing, StyleWhiteSpace},
+        test_font::{TestFont, TestGlyph},
+        text_layout::{
+            position_words, shape_words, split_text_into_words,
+            word_positions_to_inline_text_layout,
+        },
+        text_shaping::ParsedFont,
+        ui_solver::ResolvedTextLayoutOptions,
+        words::{get_inline_text, TextNormalization},
+    };
+
+    // 'b' (glyph 3) and 'c' (glyph 4) are wider than the other letters (glyph 1)
+    let mut test_font = TestFont::default();
+    test_font
+        .glyphs
+        .push((600, TestGlyph::rect(0, 0, 600, 700)));
+    test_font
+        .glyphs
+        .push((700, TestGlyph::rect(0, 0, 700, 500)));
+    test_font.cmap.retain(|(c, _)| !matches!(c, 'b' | 'c'));
+    test_font.cmap.extend([('b', 3), ('c', 4)]);
+    let font_data = |bytes: Vec<u8>, bold: bool| {
+        let mut parsed = ParsedFont::from_bytes(&bytes, 0).unwrap();
+        parsed.synthesis.bold = bold;
+        FontData {
+            bytes,
+            font_index: 0,
+            parsed,
+        }
+    };
+    let truetype = font_data(test_font.to_bytes(), false);
+    let cff = font_data(test_font.to_cff_bytes(), true);
+
+    let layout = |text: &str, font: &FontData, letter_spacing: Option<StyleTextSpacing>| {
+        let words = split_text_into_words(text, StyleWhiteSpace::PreLine, TextNormalization::Nfc);
+        let shaped_words = shape_words(&words, &font.parsed);
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            white_space: StyleWhiteSpace::PreLine,
+            letter_spacing,
+            ..Default::default()
+        };
+        let word_positions = position_words(&words, &shaped_words, &options);
+        let layout = word_positions_to_inline_text_layout(&word_positions);
+        let inline_text = get_inline_text(&words, &shaped_words, &word_positions, &layout);
+        (words, shaped_words, inline_text)
+    };
+
+    // the first page has a line of the bold CFF font above a line of the TrueType
+    // font, the two lines of the text flow are split onto two pages (the content
+    // rect fits one line)
+    let mut document = PdfDocument::new();
+    let cff_id = document.add_font(&cff);
+    let truetype_id = document.add_font(&truetype);
+    let page = document.add_page(LogicalSize::new(100.0, 30.0));
+    let (words, shaped_words, text) = layout("cab", &cff, Some(StyleTextSpacing::Px(1.0)));
+    let origin = LogicalPosition::new(10.0, 10.0);
+    document.add_text(page, cff_id, origin, &text, &words, &shaped_words);
+    let (words, shaped_words, text) = layout("ab", &truetype, None);
+    let below = LogicalPosition::new(10.0, 20.0);
+    document.add_text(page, truetype_id, below, &text, &words, &shaped_words);
+    let (words, shaped_words, text) = layout("ab\nca", &truetype, None);
+    let content_rect = LogicalRect::new(origin, LogicalSize::new(80.0, 10.0));
+    let pages = document.add_text_flow(
+        truetype_id,
+        LogicalSize::new(100.0, 30.0),
+        content_rect,
+        &text,
+        &words,
+        &shaped_words,
+    );
+    assert_eq!(pages, 1..3);
+
+    let bytes = document.to_bytes().unwrap();
+    let pdf = String::from_utf8_lossy(&bytes);
+    let find = |haystack: &[u8], needle: &[u8]| {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    };
+
+    // every entry of the cross-reference table points to its object
+    let startxref = find(&bytes, b"startxref\n").unwrap() + "startxref\n".len();
+    let startxref = String::from_utf8_lossy(&bytes[startxref..]);
+    let xref_offset = startxref.lines().next().unwrap().parse::<usize>().unwrap();
+    let xref = String::from_utf8_lossy(&bytes[xref_offset..]);
+    let mut xref_lines = xref.lines();
+    assert_eq!(xref_lines.next(), Some("xref"));
+    assert_eq!(xref_lines.next(), Some("0 19"));
+    assert_eq!(xref_lines.next(), Some("0000000000 65535 f "));
+    for object_id in 1..19 {
+        let entry = xref_lines.next().unwrap();
+        assert!(entry.ends_with(" 00000 n "));
+        let offset = entry[..10].parse::<usize>().unwrap();
+        assert!(bytes[offset..].starts_with(format!("{} 0 obj\n", object_id).as_bytes()));
+    }
+    assert_eq!(xref_lines.next(), Some("trailer"));
+    assert!(pdf.contains("/Type /Pages /Kids [14 0 R 16 0 R 18 0 R] /Count 3"));
+
+    // widths (in 1000 units per em) indexed by the glyph IDs of the subsets:
+    // `.notdef`, then the glyphs in the order of their original IDs (1, 3, 4),
+    // synthetic bold makes the glyphs of the CFF font wider
+    assert!(pdf.contains("/Subtype /CIDFontType0 /BaseFont /AAAAAA+Test-Regular"));
+    assert!(pdf.contains("/DW 0 /W [0 [542] 1 [542] 2 [642] 3 [742] ] >>"));
+    assert!(pdf.contains("/Subtype /CIDFontType2 /BaseFont /AAAAAB+Test-Regular"));
+    assert!(pdf.contains("/DW 0 /W [0 [500] 1 [500] 2 [600] 3 [700] ] /CIDToGIDMap /Identity"));
+
+    // both fonts map their glyphs back to "a", "b" and "c"
+    let bfchar = "3 beginbfchar\n<0001> <0061>\n<0002> <0062>\n<0003> <0063>\nendbfchar\n";
+    assert_eq!(pdf.matches(bfchar).count(), 2);
+
+    // the letter spacing (1px at 10px) is added with `TJ`, the render mode and line
+    // width of synthetic bold are restored before the next run
+    let contents = pdf
+        .split("stream\n")
+        .filter(|s| s.starts_with("q\n"))
+        .map(|s| s.strip_suffix("\nend").unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        contents,
+        vec![
+            "q\nBT\n/F0 10 Tf\n2 Tr 0.417 w\n1 0 0 1 10 12 Tm\n\
+             [<0003> -100 <0001> -100 <0002> ] TJ\nET\nQ\n\
+             q\nBT\n/F1 10 Tf\n1 0 0 1 10 2 Tm\n[<0001> <0002> ] TJ\nET\nQ\n",
+            "q\nBT\n/F1 10 Tf\n1 0 0 1 10 12 Tm\n[<0001> <0002> ] TJ\nET\nQ\n",
+            "q\nBT\n/F1 10 Tf\n1 0 0 1 10 12 Tm\n[<0003> <0001> ] TJ\nET\nQ\n",
+        ]
+    );
+}
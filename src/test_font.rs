@@ -0,0 +1,623 @@
+//! Minimal fonts that are built in memory for the tests (the repository doesn't
+//! contain any font files): a TrueType font with `glyf` outlines or a CFF
+//! font, the tables that `ParsedFont` and `FontDatabase` read and font
+//! collections (TTC)
+
+/// Outline of a glyph (in font units, y pointing up)
+#[derive(Debug, Clone)]
+pub(crate) enum TestGlyph {
+    /// Closed contours of on-curve points (no contours = empty glyph, e.g. a space)
+    Simple(Vec<Vec<(i16, i16)>>),
+    /// References to other glyphs as `(glyph_index, x_offset, y_offset)`
+    Composite(Vec<(u16, i16, i16)>),
+}
+
+impl TestGlyph {
+    /// Rectangle from `(x_min, y_min)` to `(x_max, y_max)`
+    pub(crate) fn rect(x_min: i16, y_min: i16, x_max: i16, y_max: i16) -> Self {
+        TestGlyph::Simple(vec![vec![
+            (x_min, y_min),
+            (x_min, y_max),
+            (x_max, y_max),
+            (x_max, y_min),
+        ]])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TestFont {
+    pub units_per_em: u16,
+    pub ascender: i16,
+    pub descender: i16,
+    /// `(horizontal advance, outline)` of every glyph, glyph 0 is `.notdef`
+    pub glyphs: Vec<(u16, TestGlyph)>,
+    /// Characters of the `cmap` table and their glyph index
+    pub cmap: Vec<(char, u16)>,
+    /// Version of the `OS/2` table: version 0 and 1 don't have an x-height and cap height,
+    /// `None` = no `OS/2` table
+    pub os2_version: Option<u16>,
+    pub x_height: i16,
+    pub cap_height: i16,
+    pub strikeout_position: i16,
+    pub strikeout_size: i16,
+    pub subscript_y_offset: i16,
+    pub superscript_y_offset: i16,
+    pub weight_class: u16,
+    pub width_class: u16,
+    pub fs_selection: u16,
+    /// `(underline position, underline thickness)`, `None` = no `post` table
+    pub post: Option<(i16, i16)>,
+    pub italic_angle: f32,
+    pub family_name: String,
+    pub subfamily_name: String,
+    pub postscript_name: String,
+    /// Additional tables (e.g. `COLR` / `CPAL`)
+    pub tables: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl Default for TestFont {
+    /// Glyph 1 is a 500 units wide box with a descender (all ASCII letters),
+    /// glyph 2 an empty, 500 units wide space: the same metrics as the fake
+    /// font of the text layout tests
+    fn default() -> Self {
+        Self {
+            units_per_em: 1000,
+            ascender: 800,
+            descender: -200,
+            glyphs: vec![
+                (500, TestGlyph::rect(50, 0, 450, 700)),
+                (500, TestGlyph::rect(100, -200, 400, 700)),
+                (500, TestGlyph::Simple(Vec::new())),
+            ],
+            cmap: core::iter::once((' ', 2))
+                .chain(('A'..='Z').chain('a'..='z').map(|c| (c, 1)))
+                .collect(),
+            os2_version: Some(4),
+            x_height: 500,
+            cap_height: 700,
+            strikeout_position: 300,
+            strikeout_size: 50,
+            subscript_y_offset: 200,
+            superscript_y_offset: 300,
+            weight_class: 400,
+            width_class: 5,
+            fs_selection: 0x40,
+            post: Some((-100, 50)),
+            italic_angle: 0.0,
+            family_name: "Test".to_string(),
+            subfamily_name: "Regular".to_string(),
+            postscript_name: "Test-Regular".to_string(),
+            tables: Vec::new(),
+        }
+    }
+}
+
+impl TestFont {
+    /// TrueType font with `glyf` outlines
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let (loca, glyf) = self.get_glyf_and_loca();
+        let mut tables = self.get_common_tables();
+        tables.extend([
+            (*b"glyf", glyf),
+            (*b"loca", loca),
+            (*b"maxp", self.get_maxp()),
+        ]);
+        build_sfnt(0x0001_0000, tables)
+    }
+
+    /// OpenType font with CFF outlines (composite glyphs are flattened)
+    pub(crate) fn to_cff_bytes(&self) -> Vec<u8> {
+        let mut maxp = Vec::new();
+        push_u32(&mut maxp, 0x0000_5000);
+        push_u16(&mut maxp, self.glyphs.len() as u16);
+        let mut tables = self.get_common_tables();
+        tables.extend([(*b"CFF ", self.get_cff()), (*b"maxp", maxp)]);
+        build_sfnt(u32::from_be_bytes(*b"OTTO"), tables)
+    }
+
+    /// Tables that don't depend on the outline format
+    fn get_common_tables(&self) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut tables = vec![
+            (*b"cmap", self.get_cmap()),
+            (*b"head", self.get_head()),
+            (*b"hhea", self.get_hhea()),
+            (*b"hmtx", self.get_hmtx()),
+            (*b"name", self.get_name()),
+        ];
+        if let Some(os2_version) = self.os2_version {
+            tables.push((*b"OS/2", self.get_os2(os2_version)));
+        }
+        if let Some((position, thickness)) = self.post {
+            let mut post = Vec::new();
+            push_u32(&mut post, 0x0003_0000);
+            push_u32(&mut post, (self.italic_angle * 65536.0) as i32 as u32);
+            push_i16(&mut post, position);
+            push_i16(&mut post, thickness);
+            post.extend_from_slice(&[0; 20]);
+            tables.push((*b"post", post));
+        }
+        tables.extend(self.tables.iter().cloned());
+        tables
+    }
+
+    /// Contours of the glyph, with the components of composite glyphs resolved
+    fn get_contours(&self, glyph_index: u16) -> Vec<Vec<(i16, i16)>> {
+        match self.glyphs.get(glyph_index as usize).map(|(_, g)| g) {
+            Some(TestGlyph::Simple(contours)) => contours.clone(),
+            Some(TestGlyph::Composite(components)) => components
+                .iter()
+                .flat_map(|&(glyph, dx, dy)| {
+                    self.get_contours(glyph).into_iter().map(move |contour| {
+                        contour.into_iter().map(|(x, y)| (x + dx, y + dy)).collect()
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// `CFF ` table with a Type 2 charstring per glyph, the predefined ISOAdobe
+    /// charset and the default widths of the private DICT (the advances are in `hmtx`)
+    fn get_cff(&self) -> Vec<u8> {
+        // all numbers are encoded with a fixed size, so the offsets can be computed up front
+        let short_int = |bytes: &mut Vec<u8>, value: i16| {
+            bytes.push(28);
+            push_i16(bytes, value);
+        };
+        let long_int = |bytes: &mut Vec<u8>, value: u32| {
+            bytes.push(29);
+            push_u32(bytes, value);
+        };
+
+        let char_strings = (0..self.glyphs.len() as u16)
+            .map(|glyph_index| {
+                let mut char_string = Vec::new();
+                let mut previous = (0, 0);
+                for contour in self.get_contours(glyph_index) {
+                    for (i, &(x, y)) in contour.iter().enumerate() {
+                        short_int(&mut char_string, x - previous.0);
+                        short_int(&mut char_string, y - previous.1);
+                        // rmoveto starts a contour, rlineto adds a point (contours close implicitly)
+                        char_string.push(if i == 0 { 21 } else { 5 });
+                        previous = (x, y);
+                    }
+                }
+                char_string.push(14); // endchar
+                char_string
+            })
+            .collect::<Vec<_>>();
+
+        let mut private_dict = Vec::new();
+        short_int(&mut private_dict, 0);
+        private_dict.push(21); // nominalWidthX
+
+        let name_index = build_cff_index(&[self.postscript_name.as_bytes().to_vec()]);
+        let top_dict_len = 17;
+        let header_len = 4;
+        let empty_index_len = 2; // string INDEX, global subr INDEX
+        let char_strings_offset =
+            header_len + name_index.len() + (2 + 1 + 2 * 4 + top_dict_len) + 2 * empty_index_len;
+        let char_strings_index = build_cff_index(&char_strings);
+        let private_offset = char_strings_offset + char_strings_index.len();
+
+        let mut top_dict = Vec::new();
+        long_int(&mut top_dict, char_strings_offset as u32);
+        top_dict.push(17); // CharStrings
+        long_int(&mut top_dict, private_dict.len() as u32);
+        long_int(&mut top_dict, private_offset as u32);
+        top_dict.push(18); // Private
+        assert_eq!(top_dict.len(), top_dict_len);
+
+        let mut cff = vec![1, 0, header_len as u8, 4]; // version 1.0, 4 byte offsets
+        cff.extend(name_index);
+        cff.extend(build_cff_index(&[top_dict]));
+        cff.extend(build_cff_index(&[])); // strings
+        cff.extend(build_cff_index(&[])); // global subroutines
+        cff.extend(char_strings_index);
+        cff.extend(private_dict);
+        cff
+    }
+
+    fn get_bounding_box(&self, glyph_index: u16) -> Option<(i16, i16, i16, i16)> {
+        let union = |a: Option<(i16, i16, i16, i16)>, b: (i16, i16, i16, i16)| {
+            Some(match a {
+                Some(a) => (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)),
+                None => b,
+            })
+        };
+        match &self.glyphs.get(glyph_index as usize)?.1 {
+            TestGlyph::Simple(contours) => contours
+                .iter()
+                .flatten()
+                .fold(None, |b, &(x, y)| union(b, (x, y, x, y))),
+            TestGlyph::Composite(components) => {
+                components.iter().fold(None, |b, &(glyph, dx, dy)| {
+                    match self.get_bounding_box(glyph) {
+                        Some(c) => union(b, (c.0 + dx, c.1 + dy, c.2 + dx, c.3 + dy)),
+                        None => b,
+                    }
+                })
+            }
+        }
+    }
+
+    fn get_font_bounding_box(&self) -> (i16, i16, i16, i16) {
+        (0..self.glyphs.len() as u16)
+            .filter_map(|glyph_index| self.get_bounding_box(glyph_index))
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+            .unwrap_or_default()
+    }
+
+    fn get_glyf_and_loca(&self) -> (Vec<u8>, Vec<u8>) {
+        let mut loca = Vec::new();
+        let mut glyf = Vec::new();
+
+        for (glyph_index, (_, glyph)) in self.glyphs.iter().enumerate() {
+            push_u32(&mut loca, glyf.len() as u32);
+            let (x_min, y_min, x_max, y_max) = match self.get_bounding_box(glyph_index as u16) {
+                Some(s) => s,
+                None => continue, // empty glyph
+            };
+            let number_of_contours = match glyph {
+                TestGlyph::Simple(contours) => contours.len() as i16,
+                TestGlyph::Composite(_) => -1,
+            };
+            push_i16(&mut glyf, number_of_contours);
+            for value in [x_min, y_min, x_max, y_max] {
+                push_i16(&mut glyf, value);
+            }
+
+            match glyph {
+                TestGlyph::Simple(contours) => {
+                    let mut end_point = 0;
+                    for contour in contours {
+                        end_point += contour.len() as u16;
+                        push_u16(&mut glyf, end_point - 1);
+                    }
+                    push_u16(&mut glyf, 0); // no instructions
+                    let points = contours.iter().flatten().collect::<Vec<_>>();
+                    // all points on the curve, the coordinates are 16 bit deltas
+                    glyf.extend(points.iter().map(|_| 0x01));
+                    for axis in 0..2 {
+                        let mut previous = 0;
+                        for &&(x, y) in points.iter() {
+                            let value = if axis == 0 { x } else { y };
+                            push_i16(&mut glyf, value - previous);
+                            previous = value;
+                        }
+                    }
+                }
+                TestGlyph::Composite(components) => {
+                    for (i, &(component, dx, dy)) in components.iter().enumerate() {
+                        // ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES (| MORE_COMPONENTS)
+                        let more = if i + 1 < components.len() { 0x20 } else { 0 };
+                        push_u16(&mut glyf, 0x0001 | 0x0002 | more);
+                        push_u16(&mut glyf, component);
+                        push_i16(&mut glyf, dx);
+                        push_i16(&mut glyf, dy);
+                    }
+                }
+            }
+
+            if glyf.len() % 2 != 0 {
+                glyf.push(0);
+            }
+        }
+        push_u32(&mut loca, glyf.len() as u32);
+
+        (loca, glyf)
+    }
+
+    fn get_head(&self) -> Vec<u8> {
+        let (x_min, y_min, x_max, y_max) = self.get_font_bounding_box();
+        let mut head = Vec::new();
+        push_u32(&mut head, 0x0001_0000); // version
+        push_u32(&mut head, 0x0001_0000); // font revision
+        push_u32(&mut head, 0); // checksum adjustment
+        push_u32(&mut head, 0x5F0F_3CF5); // magic number
+        push_u16(&mut head, 0); // flags
+        push_u16(&mut head, self.units_per_em);
+        head.extend_from_slice(&[0; 16]); // created, modified
+        for value in [x_min, y_min, x_max, y_max] {
+            push_i16(&mut head, value);
+        }
+        push_u16(&mut head, 0); // mac style
+        push_u16(&mut head, 8); // lowest recommended PPEM
+        push_i16(&mut head, 2); // font direction hint
+        push_i16(&mut head, 1); // long loca offsets
+        push_i16(&mut head, 0); // glyph data format
+        head
+    }
+
+    fn get_hhea(&self) -> Vec<u8> {
+        let (x_min, _, x_max, _) = self.get_font_bounding_box();
+        let mut hhea = Vec::new();
+        push_u32(&mut hhea, 0x0001_0000);
+        push_i16(&mut hhea, self.ascender);
+        push_i16(&mut hhea, self.descender);
+        push_i16(&mut hhea, 0); // line gap
+        push_u16(
+            &mut hhea,
+            self.glyphs.iter().map(|(a, _)| *a).max().unwrap_or(0),
+        );
+        push_i16(&mut hhea, x_min); // min left side bearing
+        push_i16(&mut hhea, 0); // min right side bearing
+        push_i16(&mut hhea, x_max); // x max extent
+        push_i16(&mut hhea, 1); // caret slope rise
+        push_i16(&mut hhea, 0); // caret slope run
+        hhea.extend_from_slice(&[0; 10]); // caret offset, reserved
+        push_i16(&mut hhea, 0); // metric data format
+        push_u16(&mut hhea, self.glyphs.len() as u16);
+        hhea
+    }
+
+    fn get_hmtx(&self) -> Vec<u8> {
+        let mut hmtx = Vec::new();
+        for (glyph_index, (advance, _)) in self.glyphs.iter().enumerate() {
+            push_u16(&mut hmtx, *advance);
+            let left_side_bearing = self.get_bounding_box(glyph_index as u16).map_or(0, |b| b.0);
+            push_i16(&mut hmtx, left_side_bearing);
+        }
+        hmtx
+    }
+
+    fn get_maxp(&self) -> Vec<u8> {
+        let max_points = self
+            .glyphs
+            .iter()
+            .map(|(_, glyph)| match glyph {
+                TestGlyph::Simple(contours) => contours.iter().map(Vec::len).sum::<usize>(),
+                TestGlyph::Composite(_) => 0,
+            })
+            .max()
+            .unwrap_or(0);
+
+        let mut maxp = Vec::new();
+        push_u32(&mut maxp, 0x0001_0000);
+        push_u16(&mut maxp, self.glyphs.len() as u16);
+        push_u16(&mut maxp, max_points as u16); // max points
+        push_u16(&mut maxp, 8); // max contours
+        push_u16(&mut maxp, max_points as u16 * 2); // max composite points
+        push_u16(&mut maxp, 16); // max composite contours
+        push_u16(&mut maxp, 2); // max zones
+        maxp.extend_from_slice(&[0; 12]); // twilight points ... size of instructions
+        push_u16(&mut maxp, 2); // max component elements
+        push_u16(&mut maxp, 1); // max component depth
+        maxp
+    }
+
+    fn get_os2(&self, os2_version: u16) -> Vec<u8> {
+        let mut os2 = Vec::new();
+        push_u16(&mut os2, os2_version);
+        push_i16(&mut os2, 500); // average char width
+        push_u16(&mut os2, self.weight_class);
+        push_u16(&mut os2, self.width_class);
+        push_u16(&mut os2, 0); // fs type
+        let script_size = (self.units_per_em as i16 / 3) * 2;
+        for value in [
+            script_size,
+            script_size,
+            0,
+            self.subscript_y_offset,
+            script_size,
+            script_size,
+            0,
+            self.superscript_y_offset,
+            self.strikeout_size,
+            self.strikeout_position,
+            0, // family class
+        ] {
+            push_i16(&mut os2, value);
+        }
+        os2.extend_from_slice(&[0; 10]); // panose
+        os2.extend_from_slice(&[0; 16]); // unicode ranges
+        os2.extend_from_slice(b"TEST"); // vendor id
+        push_u16(&mut os2, self.fs_selection);
+        push_u16(&mut os2, 0x20); // first char index
+        push_u16(&mut os2, 0x7A); // last char index
+        push_i16(&mut os2, self.ascender);
+        push_i16(&mut os2, self.descender);
+        push_i16(&mut os2, 0); // typo line gap
+        push_u16(&mut os2, self.ascender as u16); // win ascent
+        push_u16(&mut os2, -self.descender as u16); // win descent
+        if os2_version >= 1 {
+            os2.extend_from_slice(&[0; 8]); // code page ranges
+        }
+        if os2_version >= 2 {
+            push_i16(&mut os2, self.x_height);
+            push_i16(&mut os2, self.cap_height);
+            push_u16(&mut os2, 0); // default char
+            push_u16(&mut os2, 0x20); // break char
+            push_u16(&mut os2, 1); // max context
+        }
+        os2
+    }
+
+    /// `cmap` format 4 subtable (Windows, Unicode BMP) with one segment per character
+    fn get_cmap(&self) -> Vec<u8> {
+        let mut mappings = self
+            .cmap
+            .iter()
+            .map(|&(c, glyph_index)| (c as u32 as u16, glyph_index))
+            .collect::<Vec<_>>();
+        mappings.sort_unstable();
+        // the last segment has to end at 0xFFFF
+        mappings.push((0xFFFF, 0));
+
+        let seg_count = mappings.len() as u16;
+        let entry_selector = 15 - seg_count.leading_zeros() as u16;
+        let search_range = 2 << entry_selector;
+
+        let mut subtable = Vec::new();
+        push_u16(&mut subtable, 4); // format
+        push_u16(&mut subtable, 16 + 8 * seg_count); // length
+        push_u16(&mut subtable, 0); // language
+        push_u16(&mut subtable, seg_count * 2);
+        push_u16(&mut subtable, search_range);
+        push_u16(&mut subtable, entry_selector);
+        push_u16(&mut subtable, seg_count * 2 - search_range);
+        for (c, _) in mappings.iter() {
+            push_u16(&mut subtable, *c); // end code
+        }
+        push_u16(&mut subtable, 0); // reserved
+        for (c, _) in mappings.iter() {
+            push_u16(&mut subtable, *c); // start code
+        }
+        for (c, glyph_index) in mappings.iter() {
+            let delta = if *c == 0xFFFF {
+                1
+            } else {
+                glyph_index.wrapping_sub(*c)
+            };
+            push_u16(&mut subtable, delta);
+        }
+        for _ in mappings.iter() {
+            push_u16(&mut subtable, 0); // id range offset
+        }
+
+        let mut cmap = Vec::new();
+        push_u16(&mut cmap, 0); // version
+        push_u16(&mut cmap, 1); // number of subtables
+        push_u16(&mut cmap, 3); // platform: Windows
+        push_u16(&mut cmap, 1); // encoding: Unicode BMP
+        push_u32(&mut cmap, 12);
+        cmap.extend(subtable);
+        cmap
+    }
+
+    /// `name` table with the family, subfamily, full and PostScript name (Windows, UTF-16)
+    fn get_name(&self) -> Vec<u8> {
+        let full_name = format!("{} {}", self.family_name, self.subfamily_name);
+        let names = [
+            (1, self.family_name.as_str()),
+            (2, self.subfamily_name.as_str()),
+            (4, full_name.as_str()),
+            (6, self.postscript_name.as_str()),
+        ];
+
+        let mut records = Vec::new();
+        let mut strings = Vec::new();
+        for (name_id, name) in names {
+            let encoded = name
+                .encode_utf16()
+                .flat_map(u16::to_be_bytes)
+                .collect::<Vec<_>>();
+            push_u16(&mut records, 3); // platform: Windows
+            push_u16(&mut records, 1); // encoding: Unicode BMP
+            push_u16(&mut records, 0x0409); // language: en-US
+            push_u16(&mut records, name_id);
+            push_u16(&mut records, encoded.len() as u16);
+            push_u16(&mut records, strings.len() as u16);
+            strings.extend(encoded);
+        }
+
+        let mut name = Vec::new();
+        push_u16(&mut name, 0); // format
+        push_u16(&mut name, names.len() as u16);
+        push_u16(&mut name, 6 + records.len() as u16); // offset of the strings
+        name.extend(records);
+        name.extend(strings);
+        name
+    }
+}
+
+/// CFF INDEX with 4 byte offsets
+fn build_cff_index(objects: &[Vec<u8>]) -> Vec<u8> {
+    let mut index = Vec::new();
+    push_u16(&mut index, objects.len() as u16);
+    if objects.is_empty() {
+        return index;
+    }
+    index.push(4);
+    let mut offset = 1;
+    push_u32(&mut index, offset);
+    for object in objects {
+        offset += object.len() as u32;
+        push_u32(&mut index, offset);
+    }
+    for object in objects {
+        index.extend_from_slice(object);
+    }
+    index
+}
+
+/// Builds a font collection (TTC) from the font files
+pub(crate) fn build_collection(fonts: &[Vec<u8>]) -> Vec<u8> {
+    let mut collection = Vec::new();
+    collection.extend_from_slice(b"ttcf");
+    push_u32(&mut collection, 0x0001_0000);
+    push_u32(&mut collection, fonts.len() as u32);
+
+    let mut offset = 12 + 4 * fonts.len();
+    for font in fonts {
+        push_u32(&mut collection, offset as u32);
+        offset += font.len();
+    }
+
+    for font in fonts {
+        let base = collection.len() as u32;
+        let mut font = font.clone();
+        // the table offsets of a collection are relative to the start of the file
+        let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+        for i in 0..num_tables {
+            let record = 12 + 16 * i + 8;
+            let table_offset = u32::from_be_bytes(font[record..record + 4].try_into().unwrap());
+            font[record..record + 4].copy_from_slice(&(table_offset + base).to_be_bytes());
+        }
+        collection.extend(font);
+    }
+
+    collection
+}
+
+/// Builds the table directory and appends the tables (sorted by tag, 4 byte aligned)
+pub(crate) fn build_sfnt(sfnt_version: u32, mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+
+    let num_tables = tables.len() as u16;
+    let entry_selector = 15 - num_tables.leading_zeros() as u16;
+    let search_range = 16 << entry_selector;
+
+    let mut font = Vec::new();
+    push_u32(&mut font, sfnt_version);
+    push_u16(&mut font, num_tables);
+    push_u16(&mut font, search_range);
+    push_u16(&mut font, entry_selector);
+    push_u16(&mut font, num_tables * 16 - search_range);
+
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in tables.iter() {
+        let checksum = data
+            .chunks(4)
+            .map(|chunk| {
+                let mut word = [0; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u32::from_be_bytes(word)
+            })
+            .fold(0u32, u32::wrapping_add);
+        font.extend_from_slice(tag);
+        push_u32(&mut font, checksum);
+        push_u32(&mut font, offset as u32);
+        push_u32(&mut font, data.len() as u32);
+        offset += (data.len() + 3) & !3;
+    }
+
+    for (_, data) in tables.iter() {
+        font.extend_from_slice(data);
+        font.resize((font.len() + 3) & !3, 0);
+    }
+
+    font
+}
+
+pub(crate) fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn push_i16(bytes: &mut Vec<u8>, value: i16) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+pub(crate) fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
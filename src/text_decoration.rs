@@ -0,0 +1,492 @@
+//! Geometry of underlines, overlines and strike-throughs (`text-decoration` CSS property)
+
+use crate::{
+    css::{StyleTextDecorationLine, StyleTextDecorationStyle},
+    logical::{LogicalPosition, LogicalRect, LogicalSize},
+    text_shaping::{GlyphOutline, GlyphOutlineOperation, ParsedFont},
+    words::{InlineLine, InlineText, InlineWord},
+};
+
+/// Number of line segments that a curve of a glyph outline is approximated with
+const CURVE_STEPS: usize = 8;
+
+/// Continuous part of a decoration line on a single text line, the decoration
+/// is interrupted where it would cross the glyphs (`text-decoration-skip-ink`)
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[repr(C)]
+pub struct TextDecorationSegment {
+    /// Index of the line in `InlineText::lines`
+    pub line_index: usize,
+    /// Area of the decoration (relative to the text origin), includes both strokes
+    /// of a `Double` decoration and the area that a `Wavy` decoration oscillates in
+    pub bounds: LogicalRect,
+    /// Thickness of a single stroke (in pixels)
+    pub thickness: f32,
+    pub style: StyleTextDecorationStyle,
+}
+
+impl TextDecorationSegment {
+    /// Returns the rectangles that have to be filled to draw the segment,
+    /// `Wavy` decorations have to be drawn with `get_wave_points` instead
+    pub fn get_strokes(&self) -> Vec<LogicalRect> {
+        use crate::css::StyleTextDecorationStyle::*;
+
+        let t = self.thickness;
+        let stroke = |x: f32, y: f32, width: f32| {
+            LogicalRect::new(LogicalPosition::new(x, y), LogicalSize::new(width, t))
+        };
+        let repeat = |dash: f32, gap: f32| {
+            let end_x = self.bounds.origin.x + self.bounds.size.width;
+            let mut x = self.bounds.origin.x;
+            let mut strokes = Vec::new();
+            while x < end_x {
+                strokes.push(stroke(x, self.bounds.origin.y, dash.min(end_x - x)));
+                x += dash + gap;
+            }
+            strokes
+        };
+
+        match self.style {
+            Solid => vec![stroke(
+                self.bounds.origin.x,
+                self.bounds.origin.y,
+                self.bounds.size.width,
+            )],
+            Double => vec![
+                stroke(
+                    self.bounds.origin.x,
+                    self.bounds.origin.y,
+                    self.bounds.size.width,
+                ),
+                stroke(
+                    self.bounds.origin.x,
+                    self.bounds.origin.y + self.bounds.size.height - t,
+                    self.bounds.size.width,
+                ),
+            ],
+            Dotted => repeat(t, t),
+            Dashed => repeat(3.0 * t, 2.0 * t),
+            Wavy => Vec::new(),
+        }
+    }
+
+    /// Returns the center line of a `Wavy` decoration as a polyline
+    /// (to be stroked with the `thickness`), empty for all other styles
+    pub fn get_wave_points(&self) -> Vec<LogicalPosition> {
+        if self.style != StyleTextDecorationStyle::Wavy || self.thickness <= 0.0 {
+            return Vec::new();
+        }
+
+        let wavelength = 4.0 * self.thickness;
+        let amplitude = (self.bounds.size.height - self.thickness) / 2.0;
+        let center_y = self.bounds.origin.y + self.bounds.size.height / 2.0;
+        let step = wavelength / CURVE_STEPS as f32;
+        let steps = (self.bounds.size.width / step).ceil() as usize;
+
+        (0..=steps)
+            .map(|i| {
+                let dx = (i as f32 * step).min(self.bounds.size.width);
+                let phase = dx / wavelength * core::f32::consts::TAU;
+                LogicalPosition::new(
+                    self.bounds.origin.x + dx,
+                    center_y - amplitude * phase.sin(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Calculates the decoration segments for every line of the text
+///
+/// The position and thickness of the decoration are taken from the `post` (underline)
+/// and `OS/2` (strikeout) tables of the font, scaled to the font size of the words
+/// (words in spans with a different font size or `vertical-align` get their own
+/// segments). If `skip_ink` is set, underlines and overlines are interrupted where
+/// they would cross a glyph (e.g. the descender of a "g"), this requires the glyph
+/// outlines of the font.
+pub fn get_text_decorations(
+    inline_text: &InlineText,
+    font: &ParsedFont,
+    line: StyleTextDecorationLine,
+    style: StyleTextDecorationStyle,
+    skip_ink: bool,
+) -> Vec<TextDecorationSegment> {
+    use crate::css::StyleTextDecorationLine::*;
+    use crate::css::StyleTextDecorationStyle::*;
+
+    let font_metrics = &font.font_metrics;
+    let units_per_em = font_metrics.head.units_per_em as f32;
+
+    let (position, thickness) = match line {
+        Underline => (
+            font_metrics.get_underline_position_unscaled(),
+            font_metrics.get_underline_thickness_unscaled(),
+        ),
+        Overline => (
+            font_metrics.get_ascender_unscaled(),
+            font_metrics.get_underline_thickness_unscaled(),
+        ),
+        LineThrough => (
+            font_metrics.get_strikeout_position_unscaled(),
+            font_metrics.get_strikeout_size_unscaled(),
+        ),
+    };
+    // strike-throughs are never interrupted
+    let skip_ink = skip_ink && line != LineThrough;
+
+    let mut segments = Vec::new();
+
+    for (line_index, inline_line) in inline_text.lines.iter().enumerate() {
+        for run in get_decorated_runs(inline_line) {
+            let scale = run.font_size_px / units_per_em;
+            let thickness = thickness as f32 * scale;
+            let height = match style {
+                Solid | Dotted | Dashed => thickness,
+                Double | Wavy => 3.0 * thickness,
+            };
+            // the wave oscillates around the position of the stroke
+            let offset_y = match style {
+                Wavy => -thickness,
+                _ => 0.0,
+            };
+
+            let top_y =
+                inline_line.bounds.origin.y + run.baseline - position as f32 * scale + offset_y;
+
+            let mut parts = vec![(run.start_x, run.end_x)];
+            if skip_ink {
+                // keep a bit of horizontal distance between the decoration and the glyphs
+                let band = (top_y, top_y + height);
+                for (ink_start, ink_end) in get_ink_extents(inline_line, font, band) {
+                    parts = subtract_range(&parts, (ink_start - thickness, ink_end + thickness));
+                }
+            }
+
+            segments.extend(
+                parts
+                    .into_iter()
+                    .filter(|(start, end)| end - start > 0.0)
+                    .map(|(start, end)| TextDecorationSegment {
+                        line_index,
+                        bounds: LogicalRect::new(
+                            LogicalPosition::new(start, top_y),
+                            LogicalSize::new(end - start, height),
+                        ),
+                        thickness,
+                        style,
+                    }),
+            );
+        }
+    }
+
+    segments
+}
+
+/// Part of a line that is decorated with the same position and thickness
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct DecoratedRun {
+    /// Horizontal range, relative to the text origin
+    start_x: f32,
+    end_x: f32,
+    /// Distance from the top of the line to the baseline of the words
+    baseline: f32,
+    font_size_px: f32,
+}
+
+/// Groups the consecutive words on the line with the same font size and baseline,
+/// the spaces between words are decorated (with the style of the word before
+/// the space), leading and trailing spaces are not
+fn get_decorated_runs(line: &InlineLine) -> Vec<DecoratedRun> {
+    let line_x = line.bounds.origin.x;
+    let mut runs: Vec<DecoratedRun> = Vec::new();
+
+    for word in line.words.iter().filter_map(InlineWord::get_text_content) {
+        let start_x = line_x + word.bounds.origin.x;
+        let end_x = start_x + word.bounds.size.width;
+
+        if let Some(previous) = runs.last_mut() {
+            if previous.font_size_px == word.font_size_px && previous.baseline == word.baseline {
+                previous.end_x = previous.end_x.max(end_x);
+                continue;
+            }
+            previous.end_x = previous.end_x.max(start_x);
+        }
+
+        runs.push(DecoratedRun {
+            start_x,
+            end_x,
+            baseline: word.baseline,
+            font_size_px: word.font_size_px,
+        });
+    }
+
+    runs
+}
+
+/// Returns the horizontal extents (relative to the text origin) of the parts of
+/// the glyphs on the line that are inside the vertical band `(top, bottom)`
+fn get_ink_extents(line: &InlineLine, font: &ParsedFont, band: (f32, f32)) -> Vec<(f32, f32)> {
+    let units_per_em = font.font_metrics.head.units_per_em as f32;
+
+    line.words
+        .iter()
+        .filter_map(InlineWord::get_text_content)
+        .flat_map(|word| {
+            let word_origin =
+                line.bounds.origin + LogicalPosition::new(word.bounds.origin.x, word.baseline);
+            let scale = word.font_size_px / units_per_em;
+
+            word.glyphs.iter().filter_map(move |glyph| {
+                let outline = font.get_glyph_outline(glyph.glyph_index as u16)?;
+                get_outline_extent_in_band(
+                    &outline,
+                    word_origin + glyph.bounds.origin,
+                    scale * glyph.scale,
+                    band,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the horizontal extent of the part of the glyph outline inside of the
+/// vertical band, `origin` is the position of the glyph on the baseline
+fn get_outline_extent_in_band(
+    outline: &GlyphOutline,
+    origin: LogicalPosition,
+    scale: f32,
+    band: (f32, f32),
+) -> Option<(f32, f32)> {
+    use self::GlyphOutlineOperation::*;
+
+    // font units are y-up, the text is laid out y-down
+    let to_px = |x: f32, y: f32| LogicalPosition::new(origin.x + x * scale, origin.y - y * scale);
+
+    let mut extent: Option<(f32, f32)> = None;
+    let mut add_edge = |from: LogicalPosition, to: LogicalPosition| {
+        if let Some((min_x, max_x)) = clip_edge_to_band(from, to, band) {
+            extent = Some(match extent {
+                Some((a, b)) => (a.min(min_x), b.max(max_x)),
+                None => (min_x, max_x),
+            });
+        }
+    };
+
+    let mut contour_start = LogicalPosition::zero();
+    let mut current = LogicalPosition::zero();
+
+    for operation in outline.operations.iter() {
+        match operation {
+            MoveTo(p) => {
+                current = to_px(p.x, p.y);
+                contour_start = current;
+            }
+            LineTo(p) => {
+                let to = to_px(p.x, p.y);
+                add_edge(current, to);
+                current = to;
+            }
+            QuadraticCurveTo(q) => {
+                let (from, ctrl, to) = (
+                    current,
+                    to_px(q.ctrl_1_x, q.ctrl_1_y),
+                    to_px(q.end_x, q.end_y),
+                );
+                for step in 1..=CURVE_STEPS {
+                    let t = step as f32 / CURVE_STEPS as f32;
+                    let mt = 1.0 - t;
+                    let point = LogicalPosition::new(
+                        mt * mt * from.x + 2.0 * mt * t * ctrl.x + t * t * to.x,
+                        mt * mt * from.y + 2.0 * mt * t * ctrl.y + t * t * to.y,
+                    );
+                    add_edge(current, point);
+                    current = point;
+                }
+            }
+            CubicCurveTo(c) => {
+                let (from, ctrl_1, ctrl_2, to) = (
+                    current,
+                    to_px(c.ctrl_1_x, c.ctrl_1_y),
+                    to_px(c.ctrl_2_x, c.ctrl_2_y),
+                    to_px(c.end_x, c.end_y),
+                );
+                for step in 1..=CURVE_STEPS {
+                    let t = step as f32 / CURVE_STEPS as f32;
+                    let mt = 1.0 - t;
+                    let (a, b, c, d) =
+                        (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+                    let point = LogicalPosition::new(
+                        a * from.x + b * ctrl_1.x + c * ctrl_2.x + d * to.x,
+                        a * from.y + b * ctrl_1.y + c * ctrl_2.y + d * to.y,
+                    );
+                    add_edge(current, point);
+                    current = point;
+                }
+            }
+            ClosePath => {
+                add_edge(current, contour_start);
+                current = contour_start;
+            }
+        }
+    }
+
+    extent
+}
+
+/// Returns the horizontal extent of the part of the edge inside of the vertical band
+fn clip_edge_to_band(
+    from: LogicalPosition,
+    to: LogicalPosition,
+    band: (f32, f32),
+) -> Option<(f32, f32)> {
+    let (top, bottom) = band;
+    if from.y.max(to.y) < top || from.y.min(to.y) > bottom {
+        return None;
+    }
+
+    let mut min_x = f32::INFINITY;
+    let mut max_x = f32::NEG_INFINITY;
+    let mut add_x = |x: f32| {
+        min_x = min_x.min(x);
+        max_x = max_x.max(x);
+    };
+
+    for point in [from, to] {
+        if point.y >= top && point.y <= bottom {
+            add_x(point.x);
+        }
+    }
+
+    // points where the edge crosses the top / bottom of the band
+    for y in [top, bottom] {
+        if (from.y - y) * (to.y - y) < 0.0 {
+            let t = (y - from.y) / (to.y - from.y);
+            add_x(from.x + t * (to.x - from.x));
+        }
+    }
+
+    if min_x <= max_x {
+        Some((min_x, max_x))
+    } else {
+        None
+    }
+}
+
+/// Removes the range `cut` from the (sorted, non-overlapping) ranges
+fn subtract_range(ranges: &[(f32, f32)], cut: (f32, f32)) -> Vec<(f32, f32)> {
+    let (cut_start, cut_end) = cut;
+    let mut result = Vec::with_capacity(ranges.len() + 1);
+
+    for &(start, end) in ranges {
+        if cut_end <= start || cut_start >= end {
+            result.push((start, end));
+            continue;
+        }
+        if cut_start > start {
+            result.push((start, cut_start));
+        }
+        if cut_end < end {
+            result.push((cut_end, end));
+        }
+    }
+
+    result
+}
+
+#[test]
+fn test_text_decorations() {
+    use crate::{
+        test_font::TestFont,
+        text_layout::{position_words, shape_words, split_text_into_words},
+        ui_solver::{InlineSpan, ResolvedTextLayoutOptions},
+        words::{get_inline_text, TextNormalization},
+    };
+
+    // every letter is 500 units wide, its glyph is a box from x = 100 to 400 that
+    // descends to y = -200, the underline is at -100 (thickness 50), the strikeout at
+    // 300 (thickness 50): at 10px, the letters are 5px wide and the baseline is at 8px
+    let font = ParsedFont::from_bytes(&TestFont::default().to_bytes(), 0).unwrap();
+    let text = "ab cd";
+    let words = split_text_into_words(text, Default::default(), TextNormalization::Nfc);
+    let shaped_words = shape_words(&words, &font);
+
+    // returns the (rounded) x, y, width, height and thickness of the segments
+    let decorate = |spans: Vec<InlineSpan>, line: StyleTextDecorationLine, skip_ink: bool| {
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            spans,
+            ..Default::default()
+        };
+        let word_positions = position_words(&words, &shaped_words, &options);
+        let inline_text_layout =
+            crate::text_layout::word_positions_to_inline_text_layout(&word_positions);
+        let inline_text =
+            get_inline_text(&words, &shaped_words, &word_positions, &inline_text_layout);
+        let round = |f: f32| (f * 100.0).round() / 100.0;
+        get_text_decorations(
+            &inline_text,
+            &font,
+            line,
+            StyleTextDecorationStyle::Solid,
+            skip_ink,
+        )
+        .into_iter()
+        .map(|s| {
+            [
+                s.bounds.origin.x,
+                s.bounds.origin.y,
+                s.bounds.size.width,
+                s.bounds.size.height,
+                s.thickness,
+            ]
+            .map(round)
+        })
+        .collect::<Vec<_>>()
+    };
+    use crate::css::StyleTextDecorationLine::*;
+
+    // the space between the words is decorated
+    assert_eq!(
+        decorate(Vec::new(), Underline, false),
+        vec![[0.0, 9.0, 25.0, 0.5, 0.5]]
+    );
+    assert_eq!(
+        decorate(Vec::new(), Overline, false),
+        vec![[0.0, 0.0, 25.0, 0.5, 0.5]]
+    );
+    assert_eq!(
+        decorate(Vec::new(), LineThrough, false),
+        vec![[0.0, 5.0, 25.0, 0.5, 0.5]]
+    );
+
+    // the underline is interrupted at the descenders (from 1px to 4px of every
+    // letter), with a gap of one thickness on each side, strike-throughs are not
+    let underline = |x: f32, width: f32| [x, 9.0, width, 0.5, 0.5];
+    assert_eq!(
+        decorate(Vec::new(), Underline, true),
+        vec![
+            underline(0.0, 0.5),
+            underline(4.5, 1.0),
+            underline(9.5, 6.0),
+            underline(19.5, 1.0),
+            underline(24.5, 0.5),
+        ]
+    );
+    assert_eq!(
+        decorate(Vec::new(), LineThrough, true),
+        vec![[0.0, 5.0, 25.0, 0.5, 0.5]]
+    );
+
+    // words in a span with a larger font size get a thicker, lower underline,
+    // the space before the span is decorated like the word before it
+    let span = InlineSpan {
+        range: 3..5,
+        font_size_px: Some(20.0),
+        vertical_align: Default::default(),
+        font_variant_caps: None,
+    };
+    assert_eq!(
+        decorate(vec![span], Underline, false),
+        vec![[0.0, 17.0, 15.0, 0.5, 0.5], [15.0, 18.0, 20.0, 1.0, 1.0]]
+    );
+}
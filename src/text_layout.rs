@@ -1,16 +1,36 @@
 //! Contains functions for breaking a string into words, calculate
 //! the positions of words / lines and do glyph positioning
 
+use std::collections::BTreeMap;
+use std::ops::{Range, RangeInclusive};
+
+use crate::hanging_punctuation::{
+    get_protrusion, is_closing_punctuation, is_opening_punctuation, is_stop_or_comma,
+};
 use crate::logical::{LogicalPosition, LogicalRect, LogicalSize};
-use crate::text_shaping::ParsedFont;
+use crate::text_shaping::{shape, CapsFeatures, ParsedFont};
+use crate::text_transform::IDEOGRAPHIC_SPACE;
 use crate::{
+    css::{
+        StyleColumnFill, StyleFontVariantCaps, StyleOverflowWrap, StyleTextOverflow,
+        StyleWhiteSpace, StyleWritingMode,
+    },
     ui_solver::{
-        InlineTextLayout, InlineTextLine, ResolvedTextLayoutOptions, DEFAULT_LINE_HEIGHT,
-        DEFAULT_WORD_SPACING,
+        InlineObject, InlineTextLayout, InlineTextLine, IntrinsicSizes, LineTruncation,
+        ResolvedTextLayoutOptions, TextContainerFragment, WordFragment, DEFAULT_TAB_WIDTH,
+    },
+    words::{
+        GlyphCluster, GlyphInfo, ShapedWord, ShapedWords, TextNormalization, TextOffsetChange,
+        TextOffsetMap, Token, Word, WordPosition, WordPositions, Words,
     },
-    words::{ShapedWord, ShapedWords, Token, Word, WordPosition, WordPositions, Words},
 };
 
+/// Unicode paragraph separator (U+2029)
+const PARAGRAPH_SEPARATOR: char = '\u{2029}';
+
+/// Columns are balanced until their height is within this distance of the optimal height
+const COLUMN_BALANCE_PRECISION_PX: f32 = 0.5;
+
 /// Creates a font from a font file (TTF, OTF, WOFF, etc.)
 ///
 /// NOTE: EXPENSIVE function, needs to parse tables, etc.
@@ -18,83 +38,103 @@ pub fn parse_font(font_bytes: &[u8], font_index: usize) -> Option<ParsedFont> {
     ParsedFont::from_bytes(font_bytes, font_index)
 }
 
-/// Splits the text by whitespace into logical units (word, tab, return, whitespace).
-pub fn split_text_into_words(text: &str) -> Words {
-    use unicode_normalization::UnicodeNormalization;
+/// Splits the text into paragraphs at newlines (`\n`, `\r\n`, `\r`) and paragraph
+/// separators (U+2029), returns the byte range of each paragraph (without the separator).
+///
+/// An empty text (or a text ending with a separator) ends with an empty paragraph.
+pub fn split_text_into_paragraphs(text: &str) -> Vec<Range<usize>> {
+    let mut paragraphs = Vec::new();
+    let mut paragraph_start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let separator_end = match c {
+            '\r' if chars.peek().map(|(_, c)| *c) == Some('\n') => {
+                chars.next();
+                i + 2
+            }
+            '\n' | '\r' | PARAGRAPH_SEPARATOR => i + c.len_utf8(),
+            _ => continue,
+        };
+        paragraphs.push(paragraph_start..i);
+        paragraph_start = separator_end;
+    }
+    paragraphs.push(paragraph_start..text.len());
+
+    paragraphs
+}
 
-    // Necessary because we need to handle both \n and \r\n characters
-    // If we just look at the characters one-by-one, this wouldn't be possible.
-    let normalized_string = text.nfc().collect::<String>();
+/// Splits the text by whitespace into logical units (word, tab, return, whitespace).
+///
+/// Depending on the `white_space` mode, sequences of spaces, tabs and returns
+/// are collapsed into a single `Token::Space` (see `StyleWhiteSpace`). Every
+/// `OBJECT_REPLACEMENT_CHARACTER` in the text becomes a `Token::InlineObject`.
+///
+/// The `Word::index` ranges point into the normalized `Words::internal_str`,
+/// use `Words::offset_map` to map them back to the offsets of `text`.
+pub fn split_text_into_words(
+    text: &str,
+    white_space: StyleWhiteSpace,
+    normalization: TextNormalization,
+) -> Words {
+    let (normalized_string, offset_map) = match normalization {
+        TextNormalization::Nfc => normalize_nfc(text),
+        TextNormalization::None => (text.to_string(), TextOffsetMap::default()),
+    };
 
     let mut words = Vec::new();
 
     // Instead of storing the actual word, the word is only stored as an index instead,
     // which reduces allocations and is important for later on introducing RTL text
     // (where the position of the character data does not correspond to the actual glyph order).
-    let mut current_word_start = 0;
-    let mut last_char_idx = 0;
-    let mut last_char = '0';
-    let mut last_char_was_whitespace = false;
+    let mut current_word_start = None;
+    let mut inline_object_count = 0;
+    let mut chars = normalized_string.char_indices().peekable();
 
-    for (ch_idx, ch) in normalized_string.char_indices() {
-        let current_char_is_whitespace = ch == ' ' || ch == '\r' || ch == '\n';
-
-        let should_push_delimiter = match ch {
-            ' ' => Some(Word {
-                index: (last_char_idx + 1)..(ch_idx + 1),
-                word_type: Token::Space,
-            }),
-            '\n' => {
-                Some(if last_char == '\r' {
-                    // "\r\n" return
-                    Word {
-                        index: (last_char_idx)..(ch_idx + 1),
-                        word_type: Token::Return,
-                    }
-                } else {
-                    // "\n" return
-                    Word {
-                        index: (last_char_idx + 1)..(ch_idx + 1),
-                        word_type: Token::Return,
-                    }
-                })
+    while let Some((ch_idx, ch)) = chars.next() {
+        let word_type = match ch {
+            ' ' => Token::Space,
+            '\t' => Token::Tab,
+            '\r' | '\n' => Token::Return,
+            OBJECT_REPLACEMENT_CHARACTER => {
+                inline_object_count += 1;
+                Token::InlineObject(inline_object_count - 1)
+            }
+            _ => {
+                current_word_start.get_or_insert(ch_idx);
+                continue;
             }
-            _ => None,
         };
 
-        // Character is a whitespace or the character is the last character in the text (end of text)
-        let should_push_word = if current_char_is_whitespace && !last_char_was_whitespace {
-            Some(Word {
-                index: (current_word_start)..(ch_idx),
+        if let Some(word_start) = current_word_start.take() {
+            words.push(Word {
+                index: word_start..ch_idx,
                 word_type: Token::Word,
-            })
-        } else {
-            None
-        };
-
-        if current_char_is_whitespace {
-            current_word_start = ch_idx + 1;
+            });
         }
 
-        let mut push_words = |arr: [Option<Word>; 2]| {
-            words.extend(arr.into_iter().flatten());
-        };
-
-        push_words([should_push_word, should_push_delimiter]);
+        // Necessary because we need to handle both \n and \r\n characters
+        let mut delimiter_end = ch_idx + ch.len_utf8();
+        if ch == '\r' && chars.next_if(|(_, next)| *next == '\n').is_some() {
+            delimiter_end += 1;
+        }
 
-        last_char_was_whitespace = current_char_is_whitespace;
-        last_char_idx = ch_idx;
-        last_char = ch;
+        words.push(Word {
+            index: ch_idx..delimiter_end,
+            word_type,
+        });
     }
 
     // Push the last word
-    if current_word_start != last_char_idx + 1 {
+    if let Some(word_start) = current_word_start {
         words.push(Word {
-            index: current_word_start..normalized_string.len(),
+            index: word_start..normalized_string.len(),
             word_type: Token::Word,
         });
     }
 
+    let mut words = collapse_white_space(words, white_space);
+
     // If the last item is a `Return`, remove it
     if let Some(Word {
         word_type: Token::Return,
@@ -107,13 +147,135 @@ pub fn split_text_into_words(text: &str) -> Words {
     Words {
         items: words,
         internal_str: normalized_string,
-        // internal_chars: normalized_chars.iter().map(|c| *c as u32).collect(),
+        offset_map,
     }
 }
 
+/// NFC-normalizes the text and records which parts of the text were changed
+///
+/// The text is normalized in segments that start at a character that can't
+/// combine with the preceding characters, so that the changes stay local
+fn normalize_nfc(text: &str) -> (String, TextOffsetMap) {
+    use unicode_normalization::{
+        char::canonical_combining_class, is_nfc_quick, IsNormalized, UnicodeNormalization,
+    };
+
+    if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        return (text.to_string(), TextOffsetMap::default());
+    }
+
+    let is_segment_start = |c: char| {
+        canonical_combining_class(c) == 0
+            && is_nfc_quick(core::iter::once(c)) != IsNormalized::Maybe
+    };
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut changes = Vec::<TextOffsetChange>::new();
+
+    let segment_starts = text
+        .char_indices()
+        .filter(|(i, c)| *i == 0 || is_segment_start(*c))
+        .map(|(i, _)| i)
+        .chain(core::iter::once(text.len()))
+        .collect::<Vec<_>>();
+
+    for segment in segment_starts.windows(2) {
+        let original = &text[segment[0]..segment[1]];
+        let normalized_start = normalized.len();
+        normalized.extend(original.nfc());
+        if &normalized[normalized_start..] == original {
+            continue;
+        }
+
+        // merge with the directly preceding change
+        match changes.last_mut() {
+            Some(last) if last.original.end == segment[0] => {
+                last.original.end = segment[1];
+                last.normalized.end = normalized.len();
+            }
+            _ => changes.push(TextOffsetChange {
+                original: segment[0]..segment[1],
+                normalized: normalized_start..normalized.len(),
+            }),
+        }
+    }
+
+    (normalized, TextOffsetMap { changes })
+}
+
+/// Placeholder character (U+FFFC) for an inline object, see `Token::InlineObject`
+pub const OBJECT_REPLACEMENT_CHARACTER: char = '\u{FFFC}';
+
+/// Merges runs of spaces, tabs and (if the returns aren't preserved) returns
+/// into a single `Token::Space`. Spaces around a preserved return are removed.
+fn collapse_white_space(words: Vec<Word>, white_space: StyleWhiteSpace) -> Vec<Word> {
+    if !white_space.collapses_spaces() {
+        return words;
+    }
+
+    let mut collapsed = Vec::<Word>::with_capacity(words.len());
+
+    for word in words {
+        let word_type = match word.word_type {
+            Token::Word | Token::InlineObject(_) => {
+                collapsed.push(word);
+                continue;
+            }
+            Token::Return if white_space.preserves_newlines() => Token::Return,
+            Token::Return | Token::Space | Token::Tab => Token::Space,
+        };
+
+        match collapsed.last_mut() {
+            Some(last) if last.word_type == Token::Space => {
+                if word_type == Token::Return {
+                    *last = Word {
+                        index: word.index,
+                        word_type,
+                    };
+                } else {
+                    last.index.end = word.index.end;
+                }
+            }
+            Some(last) if last.word_type == Token::Return && word_type == Token::Space => {}
+            _ => collapsed.push(Word {
+                index: word.index,
+                word_type,
+            }),
+        }
+    }
+
+    collapsed
+}
+
 /// Takes a text broken into semantic items and shape all the words
 /// (does NOT scale the words, only shapes them)
 pub fn shape_words(words: &Words, font: &ParsedFont) -> ShapedWords {
+    shape_words_with_options(words, font, &ResolvedTextLayoutOptions::default())
+}
+
+/// Same as `shape_words`, but for vertical writing modes: upright characters
+/// (CJK, see `GlyphOrientation`) are advanced by their vertical advance and use the
+/// vertical alternates of the font, other characters are rotated sideways
+pub fn shape_words_vertical(words: &Words, font: &ParsedFont) -> ShapedWords {
+    let options = ResolvedTextLayoutOptions {
+        writing_mode: StyleWritingMode::VerticalRl,
+        ..Default::default()
+    };
+    shape_words_with_options(words, font, &options)
+}
+
+/// Shapes the words for the layout options: vertical writing modes are shaped like
+/// `shape_words_vertical`, words are split into parts at the span boundaries and every
+/// part is shaped with the `font_variant_caps` of its span
+pub fn shape_words_with_options(
+    words: &Words,
+    font: &ParsedFont,
+    text_layout_options: &ResolvedTextLayoutOptions,
+) -> ShapedWords {
+    let vertical = text_layout_options.writing_mode.is_vertical();
+    // the features of the font are only looked up once per `font-variant-caps`
+    let mut caps_features = BTreeMap::<StyleFontVariantCaps, CapsFeatures>::new();
+
     // Get the dimensions of the space glyph
     let space_advance = font
         .get_space_width()
@@ -127,18 +289,23 @@ pub fn shape_words(words: &Words, font: &ParsedFont) -> ShapedWords {
         .iter()
         .filter(|w| w.word_type == Token::Word)
         .map(|word| {
-            let chars = words.internal_str.as_str()[word.index.clone()]
-                .chars()
-                .collect::<Vec<_>>();
-            let shaped_word = font.shape(&chars);
-            let word_width = shaped_word.get_word_visual_width_unscaled();
-
-            longest_word_width = longest_word_width.max(word_width);
-
-            ShapedWord {
-                glyph_infos: shaped_word.infos,
-                word_width,
+            let mut shaped_word = ShapedWord::default();
+            for run in get_span_runs(&words.internal_str, word.index.clone(), text_layout_options) {
+                let font_variant_caps = text_layout_options.get_font_variant_caps(run.start);
+                let caps_features = caps_features
+                    .entry(font_variant_caps)
+                    .or_insert_with(|| CapsFeatures::new(font, font_variant_caps));
+                let mut shaped_run = shape_word(
+                    &words.internal_str[run.clone()],
+                    font,
+                    vertical,
+                    caps_features,
+                );
+                shaped_run.offset_clusters(run.start);
+                shaped_word.append(shaped_run);
             }
+            longest_word_width = longest_word_width.max(shaped_word.word_width);
+            shaped_word
         })
         .collect();
 
@@ -150,223 +317,1560 @@ pub fn shape_words(words: &Words, font: &ParsedFont) -> ShapedWords {
         font_metrics_ascender: font.font_metrics.get_ascender_unscaled(),
         font_metrics_descender: font.font_metrics.get_descender_unscaled(),
         font_metrics_line_gap: font.font_metrics.get_line_gap_unscaled(),
+        font_metrics_x_height: font.font_metrics.get_x_height_unscaled(),
+        font_metrics_subscript_y_offset: font.font_metrics.get_subscript_y_offset_unscaled(),
+        font_metrics_superscript_y_offset: font.font_metrics.get_superscript_y_offset_unscaled(),
+        text_overflow: None,
+    }
+}
+
+/// Shapes the marker that is inserted at the end of a truncated line, the result
+/// should be stored in `ShapedWords::text_overflow` before calling `position_words`
+///
+/// Returns `None` for `StyleTextOverflow::Clip` (the text is cut off without a marker)
+pub fn shape_text_overflow(
+    text_overflow: &StyleTextOverflow,
+    font: &ParsedFont,
+) -> Option<ShapedWord> {
+    match text_overflow {
+        StyleTextOverflow::Clip => None,
+        StyleTextOverflow::Ellipsis => Some(shape_word(
+            "\u{2026}",
+            font,
+            false,
+            &CapsFeatures::default(),
+        )),
+        StyleTextOverflow::String(s) => Some(shape_word(s, font, false, &CapsFeatures::default())),
+    }
+}
+
+/// Splits the byte range of a word into the parts that belong to the same span
+fn get_span_runs(
+    text: &str,
+    word: Range<usize>,
+    text_layout_options: &ResolvedTextLayoutOptions,
+) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let word_start = word.start;
+    for (char_offset, c) in text[word].char_indices() {
+        let byte_offset = word_start + char_offset;
+        match runs.last_mut() {
+            Some(run) if text_layout_options.is_same_span(run.start, byte_offset) => {
+                run.end = byte_offset + c.len_utf8()
+            }
+            _ => runs.push(byte_offset..byte_offset + c.len_utf8()),
+        }
+    }
+    runs
+}
+
+fn shape_word(
+    text: &str,
+    font: &ParsedFont,
+    vertical: bool,
+    caps_features: &CapsFeatures,
+) -> ShapedWord {
+    let chars = text.chars().collect::<Vec<_>>();
+    let shaped_word = shape(font, &chars, vertical, caps_features).unwrap_or_default();
+    let word_width = shaped_word.get_word_visual_width_unscaled();
+    let mut glyph_infos = shaped_word.infos;
+    let clusters = get_glyph_clusters(text, &mut glyph_infos);
+
+    ShapedWord {
+        glyph_infos,
+        word_width,
+        clusters,
+    }
+}
+
+/// Maps the glyphs back to the characters they were shaped from and groups them into
+/// clusters: glyphs whose characters overlap (ligatures, decompositions, marks that
+/// were skipped by a ligature) are merged, so that the clusters are monotonic
+///
+/// Sets `GlyphInfo::cluster` and returns the clusters (byte offsets relative to `text`)
+fn get_glyph_clusters(text: &str, glyph_infos: &mut [GlyphInfo]) -> Vec<GlyphCluster> {
+    use allsorts::unicode::VariationSelector;
+
+    let chars = text.char_indices().collect::<Vec<_>>();
+    let mut consumed = vec![false; chars.len()];
+    let mut next_unconsumed = 0;
+
+    // (character range, glyph range) of each cluster
+    let mut clusters: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+
+    for (glyph_index, glyph_info) in glyph_infos.iter().enumerate() {
+        let glyph = &glyph_info.info.glyph;
+
+        // the additional glyphs of a multiple substitution carry the same
+        // characters as the first glyph, they don't consume any more characters
+        let unicodes = if glyph.multi_subst_dup {
+            &[][..]
+        } else {
+            &glyph.unicodes[..]
+        };
+
+        let mut char_range: Option<Range<usize>> = None;
+        let mut search_start = next_unconsumed;
+        for c in unicodes {
+            let found = (search_start..chars.len()).find(|i| !consumed[*i] && chars[*i].1 == *c);
+            if let Some(i) = found {
+                consumed[i] = true;
+                search_start = i + 1;
+                char_range = Some(match char_range {
+                    Some(r) => r.start.min(i)..r.end.max(i + 1),
+                    None => i..i + 1,
+                });
+            }
+        }
+
+        // variation selectors are not shaped, they belong to the preceding character
+        if let Some(r) = char_range.as_mut() {
+            while r.end < chars.len() && VariationSelector::try_from(chars[r.end].1).is_ok() {
+                consumed[r.end] = true;
+                r.end += 1;
+            }
+        }
+
+        while next_unconsumed < chars.len() && consumed[next_unconsumed] {
+            next_unconsumed += 1;
+        }
+
+        match (char_range, clusters.last_mut()) {
+            // glyph doesn't map to any character (inserted glyph) or overlaps the last cluster
+            (None, Some(last)) => last.1.end = glyph_index + 1,
+            (Some(r), Some(last)) if r.start < last.0.end => {
+                // merge all clusters that the characters of the glyph overlap with
+                let mut merged = (
+                    r.start.min(last.0.start)..r.end.max(last.0.end),
+                    last.1.clone(),
+                );
+                clusters.pop();
+                while let Some(previous) = clusters.last() {
+                    if merged.0.start >= previous.0.end {
+                        break;
+                    }
+                    merged = (
+                        previous.0.start.min(merged.0.start)..merged.0.end,
+                        previous.1.start..merged.1.end,
+                    );
+                    clusters.pop();
+                }
+                merged.1.end = glyph_index + 1;
+                clusters.push(merged);
+            }
+            (r, _) => {
+                let r = r.unwrap_or(next_unconsumed..next_unconsumed);
+                clusters.push((r, glyph_index..glyph_index + 1));
+            }
+        }
+    }
+
+    // characters that didn't produce a glyph belong to the preceding cluster
+    let byte_offset = |char_index: usize| chars.get(char_index).map_or(text.len(), |c| c.0);
+    let cluster_count = clusters.len();
+    let clusters = (0..cluster_count)
+        .map(|i| {
+            let start = if i == 0 {
+                0
+            } else {
+                byte_offset(clusters[i].0.start)
+            };
+            let end = clusters
+                .get(i + 1)
+                .map_or(text.len(), |next| byte_offset(next.0.start));
+            GlyphCluster {
+                text_range: start..end,
+                glyph_range: clusters[i].1.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for cluster in clusters.iter() {
+        for glyph_info in glyph_infos[cluster.glyph_range.clone()].iter_mut() {
+            glyph_info.cluster = cluster.text_range.start;
+        }
     }
+
+    clusters
 }
 
 /// Positions the words on the screen (does not layout any glyph positions!), necessary for estimating
 /// the intrinsic width + height of the text content.
+///
+/// If the text flows through `ResolvedTextLayoutOptions::containers`, lines of a paragraph
+/// are moved into the next container until the `widows` / `orphans` constraints are met.
+///
+/// If columns are set (`column_count` / `column_width`), the `max_horizontal_width` is split
+/// into columns and the lines of each column are moved next to the previous column
+/// (see `WordPositions::containers` for the lines and bounds of each column).
 pub fn position_words(
     words: &Words,
     shaped_words: &ShapedWords,
     text_layout_options: &ResolvedTextLayoutOptions,
+) -> WordPositions {
+    match get_column_layout(text_layout_options) {
+        Some((column_count, column_width)) => position_words_in_columns(
+            words,
+            shaped_words,
+            text_layout_options,
+            column_count,
+            column_width,
+        ),
+        None => position_words_in_containers(words, shaped_words, text_layout_options),
+    }
+}
+
+/// Returns the number and width of the columns, `None` if the text isn't laid out in columns
+///
+/// See [CSS Multi-column Layout](https://www.w3.org/TR/css-multicol-1/#pseudo-algorithm)
+fn get_column_layout(text_layout_options: &ResolvedTextLayoutOptions) -> Option<(usize, f32)> {
+    if !text_layout_options.containers.is_empty() {
+        return None;
+    }
+    let available_width = text_layout_options.max_horizontal_width?;
+    let gap = text_layout_options.column_gap.max(0.0);
+
+    let column_count = match (
+        text_layout_options.column_count,
+        text_layout_options.column_width,
+    ) {
+        (None, None) => return None,
+        (Some(count), None) => count.max(1),
+        (count, Some(width)) => {
+            let fitting = ((available_width + gap) / (width.max(0.0) + gap)).floor() as usize;
+            let fitting = fitting.max(1);
+            count.map_or(fitting, |count| count.clamp(1, fitting))
+        }
+    };
+    let column_width = ((available_width + gap) / column_count as f32 - gap).max(0.0);
+    Some((column_count, column_width))
+}
+
+fn position_words_in_columns(
+    words: &Words,
+    shaped_words: &ShapedWords,
+    text_layout_options: &ResolvedTextLayoutOptions,
+    column_count: usize,
+    column_width: f32,
+) -> WordPositions {
+    let max_height = text_layout_options
+        .max_vertical_height
+        .unwrap_or(f32::INFINITY);
+    let layout = |column_height: f32| {
+        let mut options = text_layout_options.clone();
+        options.containers = vec![LogicalSize::new(column_width, column_height); column_count];
+        position_words_in_containers(words, shaped_words, &options)
+    };
+
+    let column_height = match text_layout_options.column_fill {
+        StyleColumnFill::Auto => max_height,
+        StyleColumnFill::Balance => {
+            // the smallest height at which all lines fit into the columns
+            let single_column = {
+                let mut options = text_layout_options.clone();
+                options.containers = vec![LogicalSize::new(column_width, f32::INFINITY)];
+                options.max_vertical_height = None;
+                position_words_in_containers(words, shaped_words, &options)
+            };
+            let total_height = single_column.content_size.height;
+            let line_count = single_column.line_breaks.len();
+            let fits = |word_positions: &WordPositions| {
+                word_positions.line_breaks.len() >= line_count
+                    && word_positions.truncated_at == single_column.truncated_at
+            };
+
+            let mut too_small = total_height / column_count as f32;
+            let mut large_enough = total_height;
+            while large_enough - too_small > COLUMN_BALANCE_PRECISION_PX {
+                let column_height = (too_small + large_enough) / 2.0;
+                if fits(&layout(column_height)) {
+                    large_enough = column_height;
+                } else {
+                    too_small = column_height;
+                }
+            }
+            large_enough.min(max_height)
+        }
+    };
+
+    let mut word_positions = layout(column_height);
+
+    // move the columns next to each other
+    for column in word_positions.containers.iter_mut() {
+        let column_x =
+            column.container_index as f32 * (column_width + text_layout_options.column_gap);
+        column.bounds.origin.x += column_x;
+        if column_height.is_infinite() {
+            column.bounds.size.height = word_positions.content_size.height;
+        }
+        for line in word_positions.line_breaks[column.lines.clone()].iter_mut() {
+            line.bounds.origin.x += column_x;
+        }
+    }
+    word_positions.content_size.width = text_layout_options
+        .max_horizontal_width
+        .unwrap_or(word_positions.content_size.width);
+
+    word_positions
+}
+
+fn position_words_in_containers(
+    words: &Words,
+    shaped_words: &ShapedWords,
+    text_layout_options: &ResolvedTextLayoutOptions,
+) -> WordPositions {
+    // maximum number of lines per container, `None` = as many as fit into the container
+    let mut container_line_limits = Vec::new();
+    let mut word_positions = position_words_internal(
+        words,
+        shaped_words,
+        text_layout_options,
+        &container_line_limits,
+    );
+
+    // moving lines out of a container re-breaks the following containers,
+    // so the containers are fixed one after another
+    while container_line_limits.len() + 1 < word_positions.containers.len() {
+        let container_index = container_line_limits.len();
+        let line_limit = get_widows_orphans_line_limit(
+            words,
+            text_layout_options,
+            &word_positions,
+            container_index,
+        );
+        container_line_limits.push(line_limit);
+        if line_limit.is_some() {
+            word_positions = position_words_internal(
+                words,
+                shaped_words,
+                text_layout_options,
+                &container_line_limits,
+            );
+        }
+    }
+
+    word_positions
+}
+
+/// Returns how many lines the container has to be limited to, so that the paragraph
+/// which continues in the next container satisfies the `widows` / `orphans` constraints
+fn get_widows_orphans_line_limit(
+    words: &Words,
+    text_layout_options: &ResolvedTextLayoutOptions,
+    word_positions: &WordPositions,
+    container_index: usize,
+) -> Option<usize> {
+    let container = word_positions.containers.get(container_index)?;
+    let lines = &word_positions.line_breaks;
+    let (first_line, next_line) = (container.lines.start, container.lines.end);
+    let container_line_count = next_line - first_line;
+
+    // paragraphs are separated by returns, lines of the same paragraph
+    // have the same number of returns before their first word
+    let paragraph_of_line = |line_idx: usize| {
+        let first_word = *lines[line_idx].words.start();
+        words.items[..first_word.min(words.items.len())]
+            .iter()
+            .filter(|w| w.word_type == Token::Return)
+            .count()
+    };
+
+    if next_line == 0 || next_line >= lines.len() {
+        return None;
+    }
+    let paragraph = paragraph_of_line(next_line - 1);
+    if paragraph_of_line(next_line) != paragraph {
+        return None; // the container ends with a complete paragraph
+    }
+
+    let lines_before = (first_line..next_line)
+        .rev()
+        .take_while(|line_idx| paragraph_of_line(*line_idx) == paragraph)
+        .count();
+    let lines_after = (next_line..lines.len())
+        .take_while(|line_idx| paragraph_of_line(*line_idx) == paragraph)
+        .count();
+    // the whole paragraph is moved, as long as something remains in the container
+    let move_paragraph = Some(container_line_count - lines_before).filter(|n| *n > 0);
+
+    if lines_before < text_layout_options.orphans {
+        move_paragraph
+    } else if lines_after < text_layout_options.widows {
+        let missing_lines = text_layout_options.widows - lines_after;
+        if lines_before >= text_layout_options.orphans + missing_lines {
+            Some(container_line_count - missing_lines)
+        } else {
+            move_paragraph
+        }
+    } else {
+        None
+    }
+}
+
+fn position_words_internal(
+    words: &Words,
+    shaped_words: &ShapedWords,
+    text_layout_options: &ResolvedTextLayoutOptions,
+    container_line_limits: &[Option<usize>],
 ) -> WordPositions {
     use self::LineCaretIntersection::*;
     use core::f32;
 
-    let font_size_px = text_layout_options.font_size_px;
-    let space_advance_px = shaped_words.get_space_advance_px(text_layout_options.font_size_px);
-    let word_spacing_px = space_advance_px
-        * text_layout_options
-            .word_spacing
-            .as_ref()
-            .copied()
-            .unwrap_or(DEFAULT_WORD_SPACING);
-    let line_height_px = space_advance_px
-        * text_layout_options
-            .line_height
-            .as_ref()
-            .copied()
-            .unwrap_or(DEFAULT_LINE_HEIGHT);
-    let spacing_multiplier = text_layout_options
-        .letter_spacing
-        .as_ref()
-        .copied()
-        .unwrap_or(0.0);
+    let units_per_em = shaped_words.font_metrics_units_per_em;
+    let white_space = text_layout_options.white_space;
 
-    let mut line_breaks = Vec::new();
-    let mut word_positions = Vec::new();
-    let mut line_caret_x = text_layout_options.leading.as_ref().copied().unwrap_or(0.0);
-    let mut line_caret_y = font_size_px + line_height_px;
+    // every line is at least as high as an empty inline box with the font size of the text
+    let strut = shaped_words.get_inline_box_extent(
+        text_layout_options.font_size_px,
+        text_layout_options.line_height,
+    );
+
+    let mut cursor = LineCursor {
+        words,
+        shaped_words,
+        text_layout_options,
+        strut,
+        line_extent: strut,
+        aligned_heights: (0.0, 0.0),
+        line_breaks: Vec::new(),
+        word_positions: Vec::new(),
+        fragments: Vec::new(),
+        x: text_layout_options.leading.as_ref().copied().unwrap_or(0.0),
+        y: 0.0,
+        hanging_space_px: 0.0,
+        hanging_start_px: 0.0,
+        hanging_end: EndHang::default(),
+        line_start_idx: 0,
+        is_clamped: false,
+        container_index: 0,
+        container_line_limits,
+        container_starts: vec![0],
+    };
     let mut shaped_word_idx = 0;
     let mut last_shaped_word_word_idx = 0;
-    let mut last_line_start_idx = 0;
 
     let last_word_idx = words.items.len().saturating_sub(1);
 
     // The last word is a bit special: Any text must have at least one line break!
-    for (word_idx, word) in words.items.iter().enumerate() {
+    'words: for (word_idx, word) in words.items.iter().enumerate() {
         match word.word_type {
             Token::Word => {
                 // shaped words only contains the actual shaped words, not spaces / tabs / return chars
                 let shaped_word = match shaped_words.items.get(shaped_word_idx) {
                     Some(s) => s,
-                    None => continue,
+                    None => {
+                        // every word needs a position, the inline text is built by index
+                        cursor.word_positions.push(WordPosition {
+                            shaped_word_index: None,
+                            position: LogicalPosition::new(cursor.x, cursor.y),
+                            size: LogicalSize::new(0.0, cursor.get_strut_height()),
+                        });
+                        continue;
+                    }
+                };
+                let glyphs_width = |glyphs: Range<usize>| {
+                    shaped_word.get_glyphs_width(glyphs, units_per_em, text_layout_options)
+                };
+                let glyph_count = shaped_word.glyph_infos.len();
+                let mut glyph_start = 0;
+
+                // Put the rest of the word onto the current line or - if it doesn't fit -
+                // break the word (or the line before the word) until everything is placed
+                loop {
+                    let rest_width = glyphs_width(glyph_start..glyph_count);
+                    let max_width = match cursor.get_max_width() {
+                        Some(max)
+                            if !cursor.fits(
+                                word_idx,
+                                shaped_word,
+                                glyph_start..glyph_count,
+                                rest_width,
+                                max,
+                            ) =>
+                        {
+                            max
+                        }
+                        _ => {
+                            cursor.push_word(
+                                word_idx,
+                                (shaped_word_idx, shaped_word),
+                                glyph_start..glyph_count,
+                                rest_width,
+                            );
+                            break;
+                        }
+                    };
+
+                    // last glyph index before which the word can be broken, so that
+                    // the first part of the word still fits onto the current line
+                    let fitting_break = |can_break: &dyn Fn(usize) -> bool| {
+                        ((glyph_start + 1)..glyph_count).rev().find(|glyph_idx| {
+                            can_break(*glyph_idx)
+                                && cursor.fits(
+                                    word_idx,
+                                    shaped_word,
+                                    glyph_start..*glyph_idx,
+                                    glyphs_width(glyph_start..*glyph_idx),
+                                    max_width,
+                                )
+                        })
+                    };
+
+                    let regular_break = fitting_break(&|glyph_idx| {
+                        shaped_word.can_break_before(glyph_idx, text_layout_options.word_break)
+                    });
+
+                    let break_idx = match regular_break {
+                        Some(s) => s,
+                        None if cursor.x != 0.0 => {
+                            // move the word onto the next line
+                            if !cursor.break_line(word_idx.saturating_sub(1), word_idx) {
+                                break 'words;
+                            }
+                            continue;
+                        }
+                        None if text_layout_options.overflow_wrap != StyleOverflowWrap::Normal => {
+                            // the word is longer than the entire line: break it anywhere,
+                            // but put at least one grapheme onto every line
+                            match fitting_break(&|glyph_idx| {
+                                shaped_word.is_grapheme_boundary(glyph_idx)
+                            }) {
+                                Some(s) => s,
+                                None => match ((glyph_start + 1)..glyph_count)
+                                    .find(|glyph_idx| shaped_word.is_grapheme_boundary(*glyph_idx))
+                                {
+                                    Some(s) => s,
+                                    None => {
+                                        cursor.push_word(
+                                            word_idx,
+                                            (shaped_word_idx, shaped_word),
+                                            glyph_start..glyph_count,
+                                            rest_width,
+                                        );
+                                        break;
+                                    }
+                                },
+                            }
+                        }
+                        None => {
+                            // window smaller than minimum word content: don't break the word
+                            cursor.push_word(
+                                word_idx,
+                                (shaped_word_idx, shaped_word),
+                                glyph_start..glyph_count,
+                                rest_width,
+                            );
+                            break;
+                        }
+                    };
+
+                    cursor.push_word(
+                        word_idx,
+                        (shaped_word_idx, shaped_word),
+                        glyph_start..break_idx,
+                        glyphs_width(glyph_start..break_idx),
+                    );
+                    glyph_start = break_idx;
+                    last_shaped_word_word_idx = word_idx;
+
+                    // the rest of the word is continued on the next line
+                    if !cursor.break_line(word_idx, word_idx) {
+                        break 'words;
+                    }
+                }
+
+                shaped_word_idx += 1;
+                last_shaped_word_word_idx = word_idx;
+            }
+            Token::InlineObject(object_index) => {
+                let object = text_layout_options
+                    .inline_objects
+                    .get(object_index)
+                    .copied()
+                    .unwrap_or_default();
+
+                // objects are never broken, they are moved onto the next line like a word
+                if cursor
+                    .get_max_width()
+                    .is_some_and(|max| cursor.x != 0.0 && cursor.x + object.size.width > max)
+                    && !cursor.break_line(word_idx.saturating_sub(1), word_idx)
+                {
+                    break;
+                }
+
+                cursor.push_inline_object(word_idx, &object);
+                cursor.hanging_end = EndHang::default();
+                last_shaped_word_word_idx = word_idx;
+            }
+            Token::Return => {
+                cursor.word_positions.push(WordPosition {
+                    shaped_word_index: None,
+                    position: LogicalPosition::new(cursor.x, cursor.y),
+                    size: LogicalSize::new(0.0, cursor.get_strut_height()),
+                });
+                // don't include the return char in the next line again
+                if word_idx != last_word_idx
+                    && !cursor.break_line(word_idx.saturating_sub(1), word_idx + 1)
+                {
+                    break;
+                }
+            }
+            Token::Space | Token::Tab => {
+                let x_advance = get_white_space_advance(
+                    word,
+                    cursor.x,
+                    cursor.is_at_line_start(),
+                    get_space_advance_px(words, word, shaped_words, text_layout_options),
+                    get_preceding_letter_spacing_px(words, word_idx, text_layout_options),
+                    text_layout_options,
+                );
+
+                // hanging spaces never cause a line break, other spaces
+                // wrap onto the next line like a word would
+                let caret_intersection = LineCaretIntersection::new(
+                    cursor.x,
+                    x_advance,
+                    cursor.y,
+                    cursor.get_strut_height(),
+                    cursor
+                        .get_max_width()
+                        .filter(|_| !white_space.hangs_trailing_spaces()),
+                );
+
+                if let LineBreak { .. } = caret_intersection {
+                    if !cursor.break_line(word_idx.saturating_sub(1), word_idx) {
+                        break;
+                    }
+                }
+
+                cursor.word_positions.push(WordPosition {
+                    shaped_word_index: None,
+                    position: LogicalPosition::new(cursor.x, cursor.y),
+                    size: LogicalSize::new(x_advance, cursor.get_strut_height()),
+                });
+                cursor.x += x_advance;
+                if white_space.hangs_trailing_spaces() {
+                    cursor.hanging_space_px += x_advance;
+                } else if x_advance > 0.0 {
+                    // the punctuation before the space isn't at the end of the line anymore
+                    cursor.hanging_end = EndHang::default();
+                }
+            }
+        }
+    }
+
+    cursor.push_line(cursor.line_start_idx..=last_shaped_word_word_idx);
+
+    let LineCursor {
+        mut word_positions,
+        mut line_breaks,
+        x: trailing,
+        is_clamped,
+        container_starts,
+        ..
+    } = cursor;
+
+    let containers = if text_layout_options.containers.is_empty() {
+        Vec::new()
+    } else {
+        get_container_fragments(
+            &text_layout_options.containers,
+            &container_starts,
+            &line_breaks,
+        )
+    };
+
+    // lines are cut off at the width of their container
+    let line_max_widths = match containers.as_slice() {
+        [] => vec![text_layout_options.max_horizontal_width; line_breaks.len()],
+        containers => containers
+            .iter()
+            .flat_map(|c| {
+                let width = text_layout_options
+                    .containers
+                    .get(c.container_index)
+                    .map(|size| size.width);
+                c.lines.clone().map(move |_| width)
+            })
+            .collect(),
+    };
+
+    let truncated_at = truncate_lines(
+        words,
+        shaped_words,
+        text_layout_options,
+        &mut word_positions,
+        &mut line_breaks,
+        &line_max_widths,
+        is_clamped,
+    );
+
+    let longest_line_width = line_breaks
+        .iter()
+        .map(|line| line.bounds.size.width)
+        .fold(0.0_f32, f32::max);
+
+    // the lines of each container start at the top again
+    let content_size_y = line_breaks
+        .iter()
+        .map(|line| line.bounds.origin.y + line.bounds.size.height)
+        .fold(0.0_f32, f32::max);
+    let max_horizontal_width = if white_space.wraps() {
+        match containers.as_slice() {
+            [] => text_layout_options.max_horizontal_width,
+            containers => containers
+                .iter()
+                .filter_map(|c| text_layout_options.containers.get(c.container_index))
+                .map(|size| size.width)
+                .reduce(f32::max),
+        }
+    } else {
+        None
+    };
+    let content_size_x = max_horizontal_width.unwrap_or(longest_line_width);
+    let content_size = LogicalSize::new(content_size_x, content_size_y);
+
+    WordPositions {
+        text_layout_options: text_layout_options.clone(),
+        trailing,
+        number_of_shaped_words: shaped_word_idx,
+        number_of_lines: line_breaks.len(),
+        content_size,
+        word_positions,
+        line_breaks,
+        truncated_at,
+        containers,
+    }
+}
+
+/// Groups the lines by the container that they were laid out in
+fn get_container_fragments(
+    containers: &[LogicalSize],
+    container_starts: &[usize],
+    line_breaks: &[InlineTextLine],
+) -> Vec<TextContainerFragment> {
+    container_starts
+        .iter()
+        .enumerate()
+        .map(|(container_index, line_start)| {
+            let line_end = container_starts
+                .get(container_index + 1)
+                .copied()
+                .unwrap_or(line_breaks.len());
+            let words = match (
+                line_breaks.get(*line_start),
+                line_breaks.get(line_end.max(1) - 1),
+            ) {
+                (Some(first), Some(last)) if *line_start < line_end => {
+                    *first.words.start()..(*last.words.end() + 1)
+                }
+                _ => 0..0,
+            };
+            TextContainerFragment {
+                container_index,
+                bounds: LogicalRect::new(
+                    LogicalPosition::zero(),
+                    containers.get(container_index).copied().unwrap_or_default(),
+                ),
+                lines: *line_start..line_end,
+                words,
+            }
+        })
+        .collect()
+}
+
+/// Returns how far the caret advances for a space or tab at the given caret position,
+/// `at_line_start` is set if nothing has been put onto the line yet
+///
+/// The letter spacing after the last letter of a word is only added if the word is
+/// followed by a space (`preceding_letter_spacing_px`), so that there's no letter
+/// spacing at the end of a line.
+fn get_white_space_advance(
+    word: &Word,
+    caret_x: f32,
+    at_line_start: bool,
+    space_advance_px: f32,
+    preceding_letter_spacing_px: f32,
+    text_layout_options: &ResolvedTextLayoutOptions,
+) -> f32 {
+    let tab_stop_px = space_advance_px
+        * text_layout_options
+            .tab_width
+            .as_ref()
+            .copied()
+            .unwrap_or(DEFAULT_TAB_WIDTH);
+
+    if text_layout_options.white_space.collapses_spaces() && at_line_start {
+        0.0 // collapsible spaces at the start of a line are removed
+    } else if word.word_type == Token::Tab && tab_stop_px > 0.0 {
+        tab_stop_px - caret_x % tab_stop_px // advance to the next tab stop
+    } else {
+        preceding_letter_spacing_px
+            + space_advance_px
+            + text_layout_options.get_word_spacing_px(word.index.start, space_advance_px)
+            + text_layout_options.get_letter_spacing_px(word.index.start)
+    }
+}
+
+/// Returns the advance (in pixels) of a space at the font size of its span, ideographic
+/// spaces (i.e. from `text-transform: full-width`) are one em wide
+fn get_space_advance_px(
+    words: &Words,
+    word: &Word,
+    shaped_words: &ShapedWords,
+    text_layout_options: &ResolvedTextLayoutOptions,
+) -> f32 {
+    let font_size_px = text_layout_options.get_font_size_px(word.index.start);
+    match words.internal_str.get(word.index.clone()) {
+        Some(s) if s.starts_with(IDEOGRAPHIC_SPACE) => font_size_px,
+        _ => shaped_words.get_space_advance_px(font_size_px),
+    }
+}
+
+/// Returns the letter spacing after the last letter of the word before
+/// `word_idx`, zero if the item before `word_idx` is not a word
+fn get_preceding_letter_spacing_px(
+    words: &Words,
+    word_idx: usize,
+    text_layout_options: &ResolvedTextLayoutOptions,
+) -> f32 {
+    match word_idx.checked_sub(1).and_then(|i| words.items.get(i)) {
+        Some(word) if word.word_type == Token::Word => {
+            text_layout_options.get_letter_spacing_px(word.index.start)
+        }
+        _ => 0.0,
+    }
+}
+
+/// State of the line breaking in `position_words`
+struct LineCursor<'a> {
+    words: &'a Words,
+    shaped_words: &'a ShapedWords,
+    text_layout_options: &'a ResolvedTextLayoutOptions,
+    /// Ascent and descent of an empty inline box with the font size of the text
+    strut: (f32, f32),
+    /// Ascent and descent of the current line (relative to its baseline)
+    line_extent: (f32, f32),
+    /// Heights of the tallest inline boxes on the current line that are
+    /// aligned to the top / bottom of the line box (`vertical-align: top / bottom`)
+    aligned_heights: (f32, f32),
+    line_breaks: Vec<InlineTextLine>,
+    word_positions: Vec<WordPosition>,
+    /// Parts of broken words on the current line
+    fragments: Vec<WordFragment>,
+    /// Position of the caret (the y position is the TOP of the current line)
+    x: f32,
+    y: f32,
+    /// Width of the spaces after the last word on the current line, which
+    /// hang over the end of the line (they don't count towards the line width)
+    hanging_space_px: f32,
+    /// How far the first glyph of the current line hangs over the start of the line
+    /// (`hanging-punctuation` / optical margin alignment)
+    hanging_start_px: f32,
+    /// How far the last glyph of the current line hangs over the end of the line
+    hanging_end: EndHang,
+    /// Index of the first word on the current line
+    line_start_idx: usize,
+    /// Set if the text was cut off because it exceeds the `max_lines` / `max_vertical_height`
+    /// (or because it doesn't fit into the last container)
+    is_clamped: bool,
+    /// Index into `ResolvedTextLayoutOptions::containers` of the container that is filled
+    container_index: usize,
+    /// Maximum number of lines per container (to satisfy `widows` / `orphans`)
+    container_line_limits: &'a [Option<usize>],
+    /// Index of the first line of each container
+    container_starts: Vec<usize>,
+}
+
+/// Punctuation at the end of a line that is placed past the end of the line
+#[derive(Debug, Copy, Clone, Default)]
+struct EndHang {
+    width_px: f32,
+    /// Only hangs if the line would overflow otherwise (`hanging-punctuation: allow-end`)
+    if_overflowing: bool,
+}
+
+impl<'a> LineCursor<'a> {
+    /// Puts the glyphs of a (part of a) word at the current caret position and advances the caret
+    fn push_word(
+        &mut self,
+        word_idx: usize,
+        shaped_word: (usize, &ShapedWord),
+        glyphs: Range<usize>,
+        width: f32,
+    ) {
+        let (shaped_word_idx, shaped_word) = shaped_word;
+        let position = LogicalPosition::new(self.x, self.y);
+
+        if self.is_at_line_start() {
+            self.hanging_start_px = self.get_start_hang(shaped_word, glyphs.start);
+        }
+        self.hanging_end = match glyphs.end.checked_sub(1) {
+            Some(last_glyph) => self.get_end_hang(word_idx, shaped_word, last_glyph),
+            None => EndHang::default(),
+        };
+        // the height is updated once the line is finished
+        let size = LogicalSize::new(width, self.get_strut_height());
+
+        if glyphs.start == 0 {
+            self.word_positions.push(WordPosition {
+                shaped_word_index: Some(shaped_word_idx),
+                position,
+                size,
+            });
+        }
+
+        // every part of the word in a different span has its own inline box
+        for run in shaped_word.get_span_runs(glyphs.clone(), self.text_layout_options) {
+            let byte_offset = shaped_word.glyph_infos[run.start].cluster;
+            let box_extent = self
+                .shaped_words
+                .get_word_box_extent(self.text_layout_options, byte_offset);
+            self.extend_line_box(byte_offset, box_extent);
+        }
+
+        // the word is broken across multiple lines
+        if glyphs.start != 0 || glyphs.end != shaped_word.glyph_infos.len() {
+            self.fragments.push(WordFragment {
+                word_index: word_idx,
+                glyphs,
+                position,
+                size,
+            });
+        }
+
+        self.x += width;
+        // the spaces before the word don't end the line anymore
+        self.hanging_space_px = 0.0;
+    }
+
+    /// Puts an inline object at the current caret position and advances the caret
+    fn push_inline_object(&mut self, word_idx: usize, object: &InlineObject) {
+        self.word_positions.push(WordPosition {
+            shaped_word_index: None,
+            position: LogicalPosition::new(self.x, self.y),
+            size: LogicalSize::new(object.size.width, self.get_strut_height()),
+        });
+
+        if let Some(word) = self.words.items.get(word_idx) {
+            self.extend_line_box(word.index.start, object.get_extent());
+        }
+        self.x += object.size.width;
+        self.hanging_space_px = 0.0;
+    }
+
+    /// Grows the current line so that the inline box (with the given ascent and descent)
+    /// of the word / inline object at the given byte offset fits into it
+    fn extend_line_box(&mut self, byte_offset: usize, box_extent: (f32, f32)) {
+        use crate::css::StyleInlineVerticalAlign::*;
+
+        let (ascent, descent) = box_extent;
+
+        match self
+            .text_layout_options
+            .get_span(byte_offset)
+            .map(|span| span.vertical_align)
+        {
+            // aligned to the line box itself, which isn't known yet
+            Some(Top) => self.aligned_heights.0 = self.aligned_heights.0.max(ascent + descent),
+            Some(Bottom) => self.aligned_heights.1 = self.aligned_heights.1.max(ascent + descent),
+            _ => {
+                let shift = self.shaped_words.get_baseline_shift(
+                    self.text_layout_options,
+                    byte_offset,
+                    box_extent,
+                    (0.0, 0.0),
+                );
+                self.line_extent.0 = self.line_extent.0.max(ascent + shift);
+                self.line_extent.1 = self.line_extent.1.max(descent - shift);
+            }
+        }
+    }
+
+    /// Returns the final ascent and descent of the current line
+    fn get_line_extent(&self) -> (f32, f32) {
+        let (mut ascent, mut descent) = self.line_extent;
+        let (top_height, bottom_height) = self.aligned_heights;
+        // boxes aligned to the bottom grow the line upwards, boxes aligned to the top downwards
+        if bottom_height > ascent + descent {
+            ascent = bottom_height - descent;
+        }
+        if top_height > ascent + descent {
+            descent = top_height - ascent;
+        }
+        (ascent, descent)
+    }
+
+    /// Whether nothing has been put onto the current line yet
+    /// (the first line starts after the `leading`)
+    fn is_at_line_start(&self) -> bool {
+        let line_start_x = if self.line_breaks.is_empty() {
+            self.text_layout_options.leading.unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        self.x == line_start_x
+    }
+
+    /// Whether the glyphs of the word (with the given width) fit onto the current line,
+    /// not counting the punctuation that would hang over the start or end of the line
+    fn fits(
+        &self,
+        word_idx: usize,
+        shaped_word: &ShapedWord,
+        glyphs: Range<usize>,
+        width: f32,
+        max_width: f32,
+    ) -> bool {
+        let start_hang_px = if self.is_at_line_start() {
+            self.get_start_hang(shaped_word, glyphs.start)
+        } else {
+            self.hanging_start_px
+        };
+        let end_hang_px = match glyphs.end.checked_sub(1) {
+            Some(last_glyph) => {
+                self.get_end_hang(word_idx, shaped_word, last_glyph)
+                    .width_px
+            }
+            None => 0.0,
+        };
+        self.x + width - start_hang_px - end_hang_px <= max_width
+    }
+
+    /// Returns the character and the advance (in pixels) of a glyph of the word
+    fn get_glyph(&self, shaped_word: &ShapedWord, glyph_idx: usize) -> Option<(char, f32)> {
+        let glyph_info = shaped_word.glyph_infos.get(glyph_idx)?;
+        let c = self
+            .words
+            .internal_str
+            .get(glyph_info.cluster..)?
+            .chars()
+            .next()?;
+        let advance_px = shaped_word.get_glyphs_width(
+            glyph_idx..(glyph_idx + 1),
+            self.shaped_words.font_metrics_units_per_em,
+            self.text_layout_options,
+        );
+        Some((c, advance_px))
+    }
+
+    /// Returns how far the glyph hangs over the start of the line if it is the first
+    /// glyph of the current line: opening punctuation at the start of a paragraph hangs
+    /// completely with `hanging-punctuation: first`, otherwise the optical margin protrusion
+    fn get_start_hang(&self, shaped_word: &ShapedWord, glyph_idx: usize) -> f32 {
+        let (c, advance_px) = match self.get_glyph(shaped_word, glyph_idx) {
+            Some(s) => s,
+            None => return 0.0,
+        };
+        let starts_paragraph = self.line_start_idx == 0
+            || self
+                .words
+                .items
+                .get(self.line_start_idx - 1)
+                .is_some_and(|w| w.word_type == Token::Return);
+
+        if self.text_layout_options.hanging_punctuation.first
+            && starts_paragraph
+            && glyph_idx == 0
+            && is_opening_punctuation(c)
+        {
+            advance_px
+        } else if self.text_layout_options.optical_margin_alignment {
+            get_protrusion(c).0 * advance_px
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns how far the glyph hangs over the end of the line if it is the last glyph
+    /// of the line: stops and commas with `hanging-punctuation: allow-end / force-end`,
+    /// closing punctuation at the end of a paragraph with `hanging-punctuation: last`,
+    /// otherwise the optical margin protrusion
+    fn get_end_hang(&self, word_idx: usize, shaped_word: &ShapedWord, glyph_idx: usize) -> EndHang {
+        use crate::css::StyleHangingPunctuationEnd;
+
+        let (c, advance_px) = match self.get_glyph(shaped_word, glyph_idx) {
+            Some(s) => s,
+            None => return EndHang::default(),
+        };
+        let hanging_punctuation = self.text_layout_options.hanging_punctuation;
+        let ends_paragraph = || {
+            let next_word = self
+                .words
+                .items
+                .iter()
+                .skip(word_idx + 1)
+                .find(|w| !matches!(w.word_type, Token::Space | Token::Tab));
+            match next_word {
+                Some(w) => w.word_type == Token::Return,
+                None => true,
+            }
+        };
+
+        if hanging_punctuation.end != StyleHangingPunctuationEnd::None && is_stop_or_comma(c) {
+            EndHang {
+                width_px: advance_px,
+                if_overflowing: hanging_punctuation.end == StyleHangingPunctuationEnd::AllowEnd,
+            }
+        } else if hanging_punctuation.last
+            && glyph_idx + 1 == shaped_word.glyph_infos.len()
+            && is_closing_punctuation(c)
+            && ends_paragraph()
+        {
+            EndHang {
+                width_px: advance_px,
+                if_overflowing: false,
+            }
+        } else if self.text_layout_options.optical_margin_alignment {
+            EndHang {
+                width_px: get_protrusion(c).1 * advance_px,
+                if_overflowing: false,
+            }
+        } else {
+            EndHang::default()
+        }
+    }
+
+    /// Height of a line without any inline boxes that are larger than the text
+    fn get_strut_height(&self) -> f32 {
+        self.strut.0 + self.strut.1
+    }
+
+    /// Maximum width of the current line: the width of the current container
+    /// if the text flows through containers, `None` if lines are never wrapped
+    fn get_max_width(&self) -> Option<f32> {
+        if !self.text_layout_options.white_space.wraps() {
+            return None;
+        }
+        match self
+            .text_layout_options
+            .containers
+            .get(self.container_index)
+        {
+            Some(container) => Some(container.width),
+            None => self.text_layout_options.max_horizontal_width,
+        }
+    }
+
+    /// Whether the next line (with the given bottom edge) has to be put into the next container
+    fn exceeds_container(&self, next_line_bottom: f32) -> bool {
+        let container = match self
+            .text_layout_options
+            .containers
+            .get(self.container_index)
+        {
+            Some(s) => s,
+            None => return false,
+        };
+        let container_start = self.container_starts.last().copied().unwrap_or(0);
+        // including the line that is about to be finished
+        let container_line_count = self.line_breaks.len() + 1 - container_start;
+        next_line_bottom > container.height
+            || self
+                .container_line_limits
+                .get(self.container_index)
+                .copied()
+                .flatten()
+                .is_some_and(|limit| container_line_count >= limit)
+    }
+
+    /// Finishes the current line (ending with the word at `last_word_idx`) and moves the
+    /// caret to the start of the next line (starting with the word at `next_line_start_idx`),
+    /// which is at the top of the next container if the current container is full
+    ///
+    /// Returns `false` if the text is cut off instead, because the next line would exceed
+    /// the `max_lines` / `max_vertical_height` or there is no container left
+    fn break_line(&mut self, last_word_idx: usize, next_line_start_idx: usize) -> bool {
+        let (ascent, descent) = self.get_line_extent();
+        let next_line_bottom = self.y + ascent + descent + self.get_strut_height();
+        let exceeds_container = self.exceeds_container(next_line_bottom);
+        if self.exceeds_line_clamp(self.line_breaks.len() + 2, next_line_bottom)
+            || (exceeds_container
+                && self.container_index + 1 >= self.text_layout_options.containers.len())
+        {
+            self.is_clamped = true;
+            return false;
+        }
+
+        self.push_line(self.line_start_idx..=last_word_idx.max(self.line_start_idx));
+        self.line_start_idx = next_line_start_idx;
+        self.x = 0.0;
+        if exceeds_container {
+            self.container_index += 1;
+            self.container_starts.push(self.line_breaks.len());
+            self.y = 0.0;
+        } else {
+            self.y += ascent + descent;
+        }
+        self.hanging_space_px = 0.0;
+        self.hanging_start_px = 0.0;
+        self.hanging_end = EndHang::default();
+        self.line_extent = self.strut;
+        self.aligned_heights = (0.0, 0.0);
+        true
+    }
+
+    fn push_line(&mut self, words: RangeInclusive<usize>) {
+        let (ascent, descent) = self.get_line_extent();
+        let line_height_px = ascent + descent;
+
+        // hanging punctuation is placed outside of the line, so that
+        // it is ignored when the line is aligned
+        let line_end_x = self.x - self.hanging_space_px;
+        let start_hang_px = self.hanging_start_px;
+        let overflows = self
+            .get_max_width()
+            .is_some_and(|max_width| line_end_x - start_hang_px > max_width);
+        let end_hang_px = if self.hanging_end.if_overflowing && !overflows {
+            0.0
+        } else {
+            self.hanging_end.width_px
+        };
+
+        // now that the height of the line is known, stretch the words to the full line height
+        let y = self.y;
+        let word_positions = self.word_positions.iter_mut().skip(*words.start());
+        for position in word_positions.filter(|p| p.position.y == y) {
+            position.position.x -= start_hang_px;
+            position.size.height = line_height_px;
+        }
+        for fragment in self.fragments.iter_mut() {
+            fragment.position.x -= start_hang_px;
+            fragment.size.height = line_height_px;
+        }
+
+        self.line_breaks.push(InlineTextLine {
+            words,
+            bounds: LogicalRect::new(
+                LogicalPosition::new(0.0, self.y),
+                LogicalSize::new(line_end_x - start_hang_px - end_hang_px, line_height_px),
+            ),
+            baseline: ascent,
+            ascent,
+            descent,
+            truncation: None,
+            fragments: core::mem::take(&mut self.fragments),
+        });
+    }
+
+    /// Whether the line with the given (1-based) number and bottom edge would
+    /// exceed the max. number of lines or the max. height of the text
+    /// (if the text flows through containers, their heights are used instead)
+    fn exceeds_line_clamp(&self, line_number: usize, line_bottom_px: f32) -> bool {
+        self.text_layout_options
+            .max_lines
+            .is_some_and(|max_lines| line_number > max_lines)
+            || (self.text_layout_options.containers.is_empty()
+                && self
+                    .text_layout_options
+                    .max_vertical_height
+                    .is_some_and(|max_height| line_bottom_px > max_height))
+    }
+}
+
+/// Cuts off the lines that overflow the `max_horizontal_width` (and the last line
+/// if the text was clamped) at a grapheme boundary, so that the
+/// `ShapedWords::text_overflow` marker fits at the end of the line.
+///
+/// Returns the byte offset of the first character that is not visible anymore
+fn truncate_lines(
+    words: &Words,
+    shaped_words: &ShapedWords,
+    text_layout_options: &ResolvedTextLayoutOptions,
+    word_positions: &mut [WordPosition],
+    line_breaks: &mut [InlineTextLine],
+    line_max_widths: &[Option<f32>],
+    is_clamped: bool,
+) -> Option<usize> {
+    let font_size_px = text_layout_options.font_size_px;
+    let units_per_em = shaped_words.font_metrics_units_per_em;
+    let marker_width_px = shaped_words
+        .text_overflow
+        .as_ref()
+        .map(|marker| marker.get_word_width(units_per_em, font_size_px));
+
+    let mut truncated_at = None;
+    let last_line_idx = line_breaks.len().saturating_sub(1);
+
+    for (line_idx, line) in line_breaks.iter_mut().enumerate() {
+        let must_truncate = is_clamped && line_idx == last_line_idx;
+        let max_width = line_max_widths.get(line_idx).copied().flatten();
+        let overflows = max_width.is_some_and(|max_width| line.bounds.size.width > max_width);
+
+        let marker_width_px = match marker_width_px {
+            Some(s) if must_truncate || overflows => s,
+            // without a marker, the line is just clipped
+            _ => {
+                if must_truncate {
+                    truncated_at = words
+                        .items
+                        .get(*line.words.end() + 1)
+                        .map(|w| w.index.start)
+                        .or(Some(words.internal_str.len()));
+                }
+                continue;
+            }
+        };
+
+        let available_width = max_width.unwrap_or(f32::INFINITY) - marker_width_px;
+
+        // end of the last visible glyph on this line
+        let mut visible_end_x = 0.0;
+        let mut truncation = None;
+
+        for word_idx in line.words.clone() {
+            let word_position = match word_positions.get_mut(word_idx) {
+                Some(s) => s,
+                None => break,
+            };
+
+            // inline objects are either completely visible or cut off
+            if let Some(Token::InlineObject(_)) = words.items.get(word_idx).map(|w| w.word_type) {
+                let object_end_x = word_position.position.x + word_position.size.width;
+                if object_end_x <= available_width {
+                    visible_end_x = object_end_x;
+                    continue;
+                }
+                truncation = Some((
+                    word_idx,
+                    0,
+                    words.items.get(word_idx).map(|w| w.index.start),
+                ));
+                break;
+            }
+
+            let shaped_word = match word_position
+                .shaped_word_index
+                .and_then(|i| shaped_words.items.get(i))
+            {
+                Some(s) => s,
+                None => continue,
+            };
+
+            // the word may only be partially on this line
+            let (glyphs, word_x) = match line.fragments.iter().find(|f| f.word_index == word_idx) {
+                Some(fragment) => (fragment.glyphs.clone(), fragment.position.x),
+                None => (0..shaped_word.glyph_infos.len(), word_position.position.x),
+            };
+            let glyphs_width = |glyph_end: usize| {
+                shaped_word.get_glyphs_width(
+                    glyphs.start..glyph_end,
+                    units_per_em,
+                    text_layout_options,
+                )
+            };
+
+            let word_end_x = word_x + glyphs_width(glyphs.end);
+            if word_end_x <= available_width {
+                visible_end_x = word_end_x;
+                continue;
+            }
+
+            let visible_glyphs = glyphs
+                .clone()
+                .rev()
+                .find(|glyph_idx| {
+                    shaped_word.is_grapheme_boundary(*glyph_idx)
+                        && word_x + glyphs_width(*glyph_idx) <= available_width
+                })
+                .unwrap_or(glyphs.start);
+            let visible_width = glyphs_width(visible_glyphs);
+
+            if visible_glyphs > glyphs.start {
+                visible_end_x = word_x + visible_width;
+            }
+            if glyphs.start == 0 {
+                word_position.size.width = visible_width;
+            }
+            if let Some(fragment) = line.fragments.iter_mut().find(|f| f.word_index == word_idx) {
+                fragment.glyphs.end = visible_glyphs;
+                fragment.size.width = visible_width;
+            }
+
+            // the first hidden character is the start of the cluster of the first hidden glyph
+            let byte_offset = match shaped_word.get_cluster_of_glyph(visible_glyphs) {
+                Some(cluster) => Some(cluster.text_range.start),
+                None => words
+                    .items
+                    .get(word_idx)
+                    .map(|word| word.index.start + shaped_word.get_byte_len(0..visible_glyphs)),
+            };
+
+            truncation = Some((word_idx, visible_glyphs, byte_offset));
+            break;
+        }
+
+        let (word_index, glyph_end, byte_offset) = match truncation {
+            Some(s) => s,
+            // everything fits (only possible if the text was clamped)
+            None => {
+                let last_word_idx = *line.words.end();
+                let glyph_end = match words.items.get(last_word_idx).map(|w| w.word_type) {
+                    Some(Token::InlineObject(_)) => 1,
+                    _ => word_positions
+                        .get(last_word_idx)
+                        .and_then(|p| p.shaped_word_index)
+                        .and_then(|i| shaped_words.items.get(i))
+                        .map(|w| w.glyph_infos.len())
+                        .unwrap_or(0),
                 };
+                let byte_offset = words.items.get(last_word_idx + 1).map(|w| w.index.start);
+                (last_word_idx, glyph_end, byte_offset)
+            }
+        };
+
+        let byte_offset = byte_offset.unwrap_or(words.internal_str.len());
+        truncated_at = Some(truncated_at.unwrap_or(byte_offset).min(byte_offset));
+
+        line.words = *line.words.start()..=word_index;
+        line.bounds.size.width = visible_end_x + marker_width_px;
+        line.truncation = Some(LineTruncation {
+            word_index,
+            glyph_end,
+            byte_offset,
+            marker_position: LogicalPosition::new(visible_end_x, line.bounds.origin.y),
+        });
+    }
 
-                let letter_spacing_px =
-                    spacing_multiplier * shaped_word.number_of_glyphs().saturating_sub(1) as f32;
+    truncated_at
+}
 
-                // Calculate where the caret would be for the next word
-                let shaped_word_width = shaped_word.get_word_width(
-                    shaped_words.font_metrics_units_per_em,
-                    text_layout_options.font_size_px,
-                ) + letter_spacing_px;
+/// Calculates the min-content and max-content width of the text (for sizing the text box
+/// before the final width is known), without having to position the words
+pub fn get_intrinsic_sizes(
+    words: &Words,
+    shaped_words: &ShapedWords,
+    text_layout_options: &ResolvedTextLayoutOptions,
+) -> IntrinsicSizes {
+    let units_per_em = shaped_words.font_metrics_units_per_em;
+    let white_space = text_layout_options.white_space;
 
-                // Determine if a line break is necessary
-                let caret_intersection = LineCaretIntersection::new(
-                    line_caret_x,
-                    shaped_word_width,
-                    line_caret_y,
-                    font_size_px + line_height_px,
-                    text_layout_options.max_horizontal_width.as_ref().copied(),
-                );
+    // min-content: break at every soft wrap opportunity
+    let mut min_content_width = 0.0_f32;
+    // max-content: only break at forced line breaks
+    let mut max_content_width = 0.0_f32;
+    let mut line_width = text_layout_options.leading.unwrap_or(0.0);
+    // nothing has been put onto the current line yet (see `get_white_space_advance`)
+    let mut at_line_start = true;
+    let mut hanging_space_px = 0.0;
+    let mut shaped_word_idx = 0;
 
-                // Correct and advance the line caret position
-                match caret_intersection {
-                    NoLineBreak { new_x, new_y } => {
-                        word_positions.push(WordPosition {
-                            shaped_word_index: Some(shaped_word_idx),
-                            position: LogicalPosition::new(line_caret_x, line_caret_y),
-                            size: LogicalSize::new(
-                                shaped_word_width,
-                                font_size_px + line_height_px,
-                            ),
-                        });
-                        line_caret_x = new_x;
-                        line_caret_y = new_y;
-                    }
-                    LineBreak { new_x, new_y } => {
-                        // push the line break first
-                        line_breaks.push(InlineTextLine {
-                            words: last_line_start_idx
-                                ..=word_idx.saturating_sub(1).max(last_line_start_idx),
-                            bounds: LogicalRect::new(
-                                LogicalPosition::new(0.0, line_caret_y),
-                                LogicalSize::new(line_caret_x, font_size_px + line_height_px),
-                            ),
-                        });
-                        last_line_start_idx = word_idx;
-
-                        word_positions.push(WordPosition {
-                            shaped_word_index: Some(shaped_word_idx),
-                            position: LogicalPosition::new(new_x, new_y),
-                            size: LogicalSize::new(
-                                shaped_word_width,
-                                font_size_px + line_height_px,
-                            ),
-                        });
-                        line_caret_x = new_x + shaped_word_width; // add word width for the next word
-                        line_caret_y = new_y;
-                    }
+    for (word_idx, word) in words.items.iter().enumerate() {
+        match word.word_type {
+            Token::Word => {
+                if let Some(shaped_word) = shaped_words.items.get(shaped_word_idx) {
+                    line_width += shaped_word.get_glyphs_width(
+                        0..shaped_word.glyph_infos.len(),
+                        units_per_em,
+                        text_layout_options,
+                    );
+                    min_content_width = min_content_width.max(shaped_word.get_min_content_width(
+                        units_per_em,
+                        text_layout_options,
+                        |glyph_idx| {
+                            match text_layout_options.overflow_wrap {
+                                StyleOverflowWrap::Anywhere => {
+                                    shaped_word.is_grapheme_boundary(glyph_idx)
+                                }
+                                StyleOverflowWrap::Normal | StyleOverflowWrap::BreakWord => {
+                                    shaped_word
+                                        .can_break_before(glyph_idx, text_layout_options.word_break)
+                                }
+                            }
+                        },
+                    ));
                 }
-
+                hanging_space_px = 0.0;
+                at_line_start = false;
                 shaped_word_idx += 1;
-                last_shaped_word_word_idx = word_idx;
             }
-            Token::Return => {
-                if word_idx != last_word_idx {
-                    line_breaks.push(InlineTextLine {
-                        words: last_line_start_idx
-                            ..=word_idx.saturating_sub(1).max(last_line_start_idx),
-                        bounds: LogicalRect::new(
-                            LogicalPosition::new(0.0, line_caret_y),
-                            LogicalSize::new(line_caret_x, font_size_px + line_height_px),
-                        ),
-                    });
-                    // don't include the return char in the next line again
-                    last_line_start_idx = word_idx + 1;
-                }
-                word_positions.push(WordPosition {
-                    shaped_word_index: None,
-                    position: LogicalPosition::new(line_caret_x, line_caret_y),
-                    size: LogicalSize::new(0.0, font_size_px + line_height_px),
-                });
-                if word_idx != last_word_idx {
-                    line_caret_x = 0.0;
-                    line_caret_y = line_caret_y + font_size_px + line_height_px;
-                }
+            Token::InlineObject(object_index) => {
+                let object_width = text_layout_options
+                    .inline_objects
+                    .get(object_index)
+                    .map(|object| object.size.width)
+                    .unwrap_or(0.0);
+                line_width += object_width;
+                min_content_width = min_content_width.max(object_width);
+                hanging_space_px = 0.0;
+                at_line_start = false;
             }
-            Token::Space => {
-                let x_advance = word_spacing_px;
-
-                let caret_intersection = LineCaretIntersection::new(
-                    line_caret_x,
-                    x_advance, // advance by space / tab width
-                    line_caret_y,
-                    font_size_px + line_height_px,
-                    text_layout_options.max_horizontal_width.as_ref().copied(),
+            Token::Space | Token::Tab => {
+                let x_advance = get_white_space_advance(
+                    word,
+                    line_width,
+                    at_line_start,
+                    get_space_advance_px(words, word, shaped_words, text_layout_options),
+                    get_preceding_letter_spacing_px(words, word_idx, text_layout_options),
+                    text_layout_options,
                 );
-
-                match caret_intersection {
-                    NoLineBreak { new_x, new_y } => {
-                        word_positions.push(WordPosition {
-                            shaped_word_index: None,
-                            position: LogicalPosition::new(line_caret_x, line_caret_y),
-                            size: LogicalSize::new(x_advance, font_size_px + line_height_px),
-                        });
-                        line_caret_x = new_x;
-                        line_caret_y = new_y;
-                    }
-                    LineBreak { new_x, new_y } => {
-                        // push the line break before increasing
-                        if word_idx != last_word_idx {
-                            line_breaks.push(InlineTextLine {
-                                words: last_line_start_idx
-                                    ..=word_idx.saturating_sub(1).max(last_line_start_idx),
-                                bounds: LogicalRect::new(
-                                    LogicalPosition::new(0.0, line_caret_y),
-                                    LogicalSize::new(line_caret_x, font_size_px + line_height_px),
-                                ),
-                            });
-                            last_line_start_idx = word_idx;
-                        }
-                        word_positions.push(WordPosition {
-                            shaped_word_index: None,
-                            position: LogicalPosition::new(line_caret_x, line_caret_y),
-                            size: LogicalSize::new(x_advance, font_size_px + line_height_px),
-                        });
-                        if word_idx != last_word_idx {
-                            line_caret_x = new_x; // don't add the space width here when pushing onto new line
-                            line_caret_y = new_y;
-                        }
-                    }
+                line_width += x_advance;
+                at_line_start &= x_advance == 0.0;
+                if white_space.hangs_trailing_spaces() {
+                    hanging_space_px += x_advance;
                 }
             }
+            Token::Return => {
+                max_content_width = max_content_width.max(line_width - hanging_space_px);
+                line_width = 0.0;
+                hanging_space_px = 0.0;
+                at_line_start = true;
+            }
         }
     }
 
-    line_breaks.push(InlineTextLine {
-        words: last_line_start_idx..=last_shaped_word_word_idx,
-        bounds: LogicalRect::new(
-            LogicalPosition::new(0.0, line_caret_y),
-            LogicalSize::new(line_caret_x, font_size_px + line_height_px),
-        ),
-    });
-
-    let longest_line_width = line_breaks
-        .iter()
-        .map(|line| line.bounds.size.width)
-        .fold(0.0_f32, f32::max);
+    max_content_width = max_content_width.max(line_width - hanging_space_px);
 
-    let content_size_y = line_breaks.len() as f32 * (font_size_px + line_height_px);
-    let content_size_x = text_layout_options
-        .max_horizontal_width
-        .as_ref()
-        .copied()
-        .unwrap_or(longest_line_width);
-    let content_size = LogicalSize::new(content_size_x, content_size_y);
+    let min_content_width = if !white_space.wraps() {
+        max_content_width
+    } else if white_space == StyleWhiteSpace::BreakSpaces {
+        // preserved spaces don't hang, so they can't be narrower than a single space
+        let space_width_px = words
+            .items
+            .iter()
+            .filter(|w| matches!(w.word_type, Token::Space | Token::Tab))
+            .map(|w| {
+                let space = Word {
+                    index: w.index.clone(),
+                    word_type: Token::Space,
+                };
+                get_white_space_advance(
+                    &space,
+                    f32::INFINITY,
+                    false,
+                    get_space_advance_px(words, w, shaped_words, text_layout_options),
+                    0.0,
+                    text_layout_options,
+                )
+            })
+            .fold(0.0_f32, f32::max);
+        min_content_width.max(space_width_px)
+    } else {
+        min_content_width
+    };
 
-    WordPositions {
-        text_layout_options: text_layout_options.clone(),
-        trailing: line_caret_x,
-        number_of_shaped_words: shaped_word_idx,
-        number_of_lines: line_breaks.len(),
-        content_size,
-        word_positions,
-        line_breaks,
+    IntrinsicSizes {
+        min_content_width: min_content_width.min(max_content_width),
+        max_content_width,
     }
 }
 
+/// Returns the height (in pixels) that the text would have if it is laid out with the given width
+pub fn get_height_for_width(
+    words: &Words,
+    shaped_words: &ShapedWords,
+    text_layout_options: &ResolvedTextLayoutOptions,
+    width: f32,
+) -> f32 {
+    let text_layout_options = ResolvedTextLayoutOptions {
+        max_horizontal_width: Some(width),
+        ..text_layout_options.clone()
+    };
+    position_words(words, shaped_words, &text_layout_options)
+        .content_size
+        .height
+}
+
 /// Returns the (left-aligned!) bounding boxes of the indidividual text lines
 pub fn word_positions_to_inline_text_layout(word_positions: &WordPositions) -> InlineTextLayout {
     InlineTextLayout {
@@ -455,9 +1959,11 @@ fn test_split_words() {
     }
 
     let ascii_str = String::from("abc def  \nghi\r\njkl");
-    let words_ascii = split_text_into_words(&ascii_str);
+    let words_ascii =
+        split_text_into_words(&ascii_str, StyleWhiteSpace::PreWrap, TextNormalization::Nfc);
     let words_ascii_expected = Words {
         internal_str: ascii_str,
+        offset_map: TextOffsetMap::default(),
         items: vec![
             Word {
                 index: 0..3,
@@ -501,21 +2007,26 @@ fn test_split_words() {
     assert_words(&words_ascii_expected, &words_ascii);
 
     let unicode_str = String::from("㌊㌋㌌㌍㌎㌏㌐㌑ ㌒㌓㌔㌕㌖㌗");
-    let words_unicode = split_text_into_words(&unicode_str);
+    let words_unicode = split_text_into_words(
+        &unicode_str,
+        StyleWhiteSpace::PreWrap,
+        TextNormalization::Nfc,
+    );
     let words_unicode_expected = Words {
         internal_str: unicode_str,
+        offset_map: TextOffsetMap::default(),
         // internal_chars: string_to_vec(unicode_str),
         items: vec![
             Word {
-                index: 0..8,
+                index: 0..24,
                 word_type: Token::Word,
             }, // "㌊㌋㌌㌍㌎㌏㌐㌑"
             Word {
-                index: 8..9,
+                index: 24..25,
                 word_type: Token::Space,
             }, // " "
             Word {
-                index: 9..15,
+                index: 25..43,
                 word_type: Token::Word,
             }, // "㌒㌓㌔㌕㌖㌗"
         ],
@@ -524,9 +2035,14 @@ fn test_split_words() {
     assert_words(&words_unicode_expected, &words_unicode);
 
     let single_str = String::from("A");
-    let words_single_str = split_text_into_words(&single_str);
+    let words_single_str = split_text_into_words(
+        &single_str,
+        StyleWhiteSpace::PreWrap,
+        TextNormalization::Nfc,
+    );
     let words_single_str_expected = Words {
         internal_str: single_str,
+        offset_map: TextOffsetMap::default(),
         // internal_chars: string_to_vec(single_str),
         items: vec![
             Word {
@@ -538,3 +2054,1169 @@ fn test_split_words() {
 
     assert_words(&words_single_str_expected, &words_single_str);
 }
+
+#[test]
+fn test_split_words_offset_map() {
+    // "é" (decomposed) "x" "Å" (Angstrom sign) "q" + acute (has no precomposed form)
+    let text = "e\u{301}x \u{212B}q\u{301}";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    assert_eq!(words.internal_str, "\u{E9}x \u{C5}q\u{301}");
+    assert_eq!(
+        words.offset_map.changes,
+        vec![
+            TextOffsetChange {
+                original: 0..3,
+                normalized: 0..2,
+            },
+            TextOffsetChange {
+                original: 5..8,
+                normalized: 4..6,
+            },
+        ]
+    );
+
+    let offset_map = &words.offset_map;
+    assert_eq!(offset_map.to_original(2), 3);
+    assert_eq!(offset_map.to_original(3), 4);
+    assert_eq!(offset_map.to_original(6), 8);
+    assert_eq!(offset_map.to_normalized(1), 0);
+    assert_eq!(offset_map.to_normalized(8), 6);
+    assert_eq!(offset_map.range_to_original(1..2), 0..3);
+    assert_eq!(offset_map.range_to_normalized(1..4), 0..3);
+
+    // the words point into the normalized string, but can be mapped back
+    let word_ranges = words
+        .items
+        .iter()
+        .filter(|w| w.word_type == Token::Word)
+        .map(|w| &text[offset_map.range_to_original(w.index.clone())])
+        .collect::<Vec<_>>();
+    assert_eq!(word_ranges, vec!["e\u{301}x", "\u{212B}q\u{301}"]);
+
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::None);
+    assert_eq!(words.internal_str, text);
+    assert!(words.offset_map.changes.is_empty());
+}
+
+#[test]
+fn test_split_words_white_space() {
+    fn word_types(text: &str, white_space: StyleWhiteSpace) -> Vec<(&str, Token)> {
+        let words = split_text_into_words(text, white_space, TextNormalization::Nfc);
+        words
+            .items
+            .iter()
+            .map(|w| (&text[w.index.clone()], w.word_type))
+            .collect()
+    }
+
+    let text = "ab \t cd \n ef\tgh";
+
+    assert_eq!(
+        word_types(text, StyleWhiteSpace::Normal),
+        vec![
+            ("ab", Token::Word),
+            (" \t ", Token::Space),
+            ("cd", Token::Word),
+            (" \n ", Token::Space),
+            ("ef", Token::Word),
+            ("\t", Token::Space),
+            ("gh", Token::Word),
+        ]
+    );
+
+    assert_eq!(
+        word_types(text, StyleWhiteSpace::PreLine),
+        vec![
+            ("ab", Token::Word),
+            (" \t ", Token::Space),
+            ("cd", Token::Word),
+            ("\n", Token::Return),
+            ("ef", Token::Word),
+            ("\t", Token::Space),
+            ("gh", Token::Word),
+        ]
+    );
+
+    assert_eq!(
+        word_types(text, StyleWhiteSpace::Pre),
+        vec![
+            ("ab", Token::Word),
+            (" ", Token::Space),
+            ("\t", Token::Tab),
+            (" ", Token::Space),
+            ("cd", Token::Word),
+            (" ", Token::Space),
+            ("\n", Token::Return),
+            (" ", Token::Space),
+            ("ef", Token::Word),
+            ("\t", Token::Tab),
+            ("gh", Token::Word),
+        ]
+    );
+}
+
+#[test]
+fn test_split_words_inline_objects() {
+    let text = "ab\u{FFFC}cd \u{FFFC}";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let word_types = words
+        .items
+        .iter()
+        .map(|w| (&text[w.index.clone()], w.word_type))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        word_types,
+        vec![
+            ("ab", Token::Word),
+            ("\u{FFFC}", Token::InlineObject(0)),
+            ("cd", Token::Word),
+            (" ", Token::Space),
+            ("\u{FFFC}", Token::InlineObject(1)),
+        ]
+    );
+}
+
+#[test]
+fn test_glyph_clusters() {
+    use crate::words::{Advance, GlyphOrientation};
+    use allsorts::{
+        gpos::Info,
+        gsub::{GlyphOrigin, RawGlyph},
+        tinyvec::TinyVec,
+    };
+
+    let glyph = |unicodes: &[char], multi_subst_dup: bool| RawGlyph {
+        unicodes: unicodes.iter().copied().collect::<TinyVec<[char; 1]>>(),
+        glyph_index: 1,
+        liga_component_pos: 0,
+        glyph_origin: GlyphOrigin::Direct,
+        small_caps: false,
+        multi_subst_dup,
+        is_vert_alt: false,
+        fake_bold: false,
+        fake_italic: false,
+        extra_data: (),
+        variation: None,
+    };
+
+    // "ffi" ligature, "é" decomposed into two glyphs, "x" + variation selector
+    let text = "ffié\u{FE0F}x\u{FE0F}";
+    let glyphs = vec![
+        glyph(&['f', 'f', 'i'], false),
+        glyph(&['é'], false),
+        glyph(&['é'], true),
+        glyph(&['x'], false),
+    ];
+    let mut glyph_infos = Info::init_from_glyphs(None, glyphs)
+        .into_iter()
+        .map(|info| GlyphInfo {
+            info,
+            advance: Advance {
+                advance_x: 0,
+                size_x: 0,
+                size_y: 0,
+            },
+            orientation: GlyphOrientation::Horizontal,
+            cluster: 0,
+            scale: 1.0,
+        })
+        .collect::<Vec<_>>();
+
+    let clusters = get_glyph_clusters(text, &mut glyph_infos);
+
+    assert_eq!(
+        clusters,
+        vec![
+            GlyphCluster {
+                text_range: 0..3,
+                glyph_range: 0..1,
+            },
+            GlyphCluster {
+                text_range: 3..8,
+                glyph_range: 1..3,
+            },
+            GlyphCluster {
+                text_range: 8..12,
+                glyph_range: 3..4,
+            },
+        ]
+    );
+    assert_eq!(
+        glyph_infos.iter().map(|g| g.cluster).collect::<Vec<_>>(),
+        vec![0, 3, 3, 8]
+    );
+}
+
+/// Shapes the words with a fake monospaced font without any font file: every
+/// character is 500 units wide (units per em = 1000, ascender 800, descender -200)
+#[cfg(test)]
+fn get_test_shaped_words(words: &Words) -> ShapedWords {
+    let items = words
+        .items
+        .iter()
+        .filter(|word| word.word_type == Token::Word)
+        .map(|word| {
+            let mut shaped_word = get_test_shaped_word(&words.internal_str[word.index.clone()]);
+            shaped_word.offset_clusters(word.index.start);
+            shaped_word
+        })
+        .collect::<Vec<_>>();
+
+    ShapedWords {
+        longest_word_width: items.iter().map(|w| w.word_width).max().unwrap_or(0),
+        items,
+        space_advance: 500,
+        font_metrics_units_per_em: 1000,
+        font_metrics_ascender: 800,
+        font_metrics_descender: -200,
+        font_metrics_line_gap: 0,
+        font_metrics_x_height: 500,
+        font_metrics_subscript_y_offset: 200,
+        font_metrics_superscript_y_offset: 300,
+        text_overflow: None,
+    }
+}
+
+/// Shapes a single word with the fake font of `get_test_shaped_words`
+/// (one glyph per character, the clusters are relative to the word)
+#[cfg(test)]
+fn get_test_shaped_word(text: &str) -> ShapedWord {
+    use crate::words::{Advance, GlyphOrientation};
+    use allsorts::{
+        gpos::Info,
+        gsub::{GlyphOrigin, RawGlyph},
+        tinyvec::TinyVec,
+    };
+
+    let glyphs = text
+        .chars()
+        .map(|c| RawGlyph {
+            unicodes: core::iter::once(c).collect::<TinyVec<[char; 1]>>(),
+            glyph_index: 1,
+            liga_component_pos: 0,
+            glyph_origin: GlyphOrigin::Direct,
+            small_caps: false,
+            multi_subst_dup: false,
+            is_vert_alt: false,
+            fake_bold: false,
+            fake_italic: false,
+            extra_data: (),
+            variation: None,
+        })
+        .collect();
+    let mut glyph_infos = Info::init_from_glyphs(None, glyphs)
+        .into_iter()
+        .map(|info| GlyphInfo {
+            info,
+            advance: Advance {
+                advance_x: 500,
+                size_x: 500,
+                size_y: 1000,
+            },
+            orientation: GlyphOrientation::Horizontal,
+            cluster: 0,
+            scale: 1.0,
+        })
+        .collect::<Vec<_>>();
+    let clusters = get_glyph_clusters(text, &mut glyph_infos);
+    ShapedWord {
+        word_width: glyph_infos.len() * 500,
+        glyph_infos,
+        clusters,
+    }
+}
+
+#[test]
+fn test_position_words_text_overflow() {
+    // 5px per character and space
+    let layout = |text: &str,
+                  white_space: StyleWhiteSpace,
+                  marker: &str,
+                  max_horizontal_width: f32,
+                  max_lines: Option<usize>| {
+        let words = split_text_into_words(text, white_space, TextNormalization::Nfc);
+        let mut shaped_words = get_test_shaped_words(&words);
+        shaped_words.text_overflow = Some(get_test_shaped_word(marker));
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            white_space,
+            max_horizontal_width: Some(max_horizontal_width),
+            max_lines,
+            ..Default::default()
+        };
+        let word_positions = position_words(&words, &shaped_words, &options);
+        let truncations = word_positions
+            .line_breaks
+            .iter()
+            .map(|line| {
+                line.truncation
+                    .as_ref()
+                    .map(|t| (t.word_index, t.glyph_end, t.marker_position.x))
+            })
+            .collect::<Vec<_>>();
+        (word_positions.truncated_at, truncations)
+    };
+
+    // the last line of a clamped text is cut off, so that the ellipsis fits
+    assert_eq!(
+        layout(
+            "aa bb cc dd",
+            StyleWhiteSpace::Normal,
+            "\u{2026}",
+            25.0,
+            Some(1)
+        ),
+        (Some(4), vec![Some((2, 1, 20.0))])
+    );
+    // a custom marker hides the whole word "bb"
+    assert_eq!(
+        layout(
+            "aa bb cc dd",
+            StyleWhiteSpace::Normal,
+            "[more]",
+            40.0,
+            Some(1)
+        ),
+        (Some(3), vec![Some((2, 0, 10.0))])
+    );
+    // the acute accent isn't separated from its base character
+    assert_eq!(
+        layout(
+            "q\u{301}q\u{301}q",
+            StyleWhiteSpace::Nowrap,
+            "\u{2026}",
+            20.0,
+            None
+        ),
+        (Some(3), vec![Some((0, 2, 10.0))])
+    );
+    // a marker that is wider than the line hides everything
+    assert_eq!(
+        layout("aaaaaa", StyleWhiteSpace::Nowrap, "[more]", 20.0, None),
+        (Some(0), vec![Some((0, 0, 0.0))])
+    );
+
+    // flags (pairs of regional indicators) and hangul jamo are single grapheme clusters
+    let boundaries = |text: &str| {
+        let shaped_word = get_test_shaped_word(text);
+        (0..=shaped_word.glyph_infos.len())
+            .filter(|i| shaped_word.is_grapheme_boundary(*i))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        boundaries("\u{1F1E9}\u{1F1EA}\u{1F1EB}\u{1F1F7}"),
+        vec![0, 2, 4]
+    );
+    assert_eq!(boundaries("\u{1100}\u{1161}\u{11A8}a"), vec![0, 3, 4]);
+}
+
+#[test]
+fn test_position_words_overflow_wrap() {
+    use crate::css::StyleWordBreak;
+
+    // 5px per character and space, 20px per line
+    let line_widths = |text: &str, overflow_wrap: StyleOverflowWrap, word_break: StyleWordBreak| {
+        let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+        let shaped_words = get_test_shaped_words(&words);
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            max_horizontal_width: Some(20.0),
+            overflow_wrap,
+            word_break,
+            ..Default::default()
+        };
+        position_words(&words, &shaped_words, &options)
+            .line_breaks
+            .iter()
+            .map(|line| {
+                let fragment = line.fragments.first().map(|f| f.glyphs.clone());
+                (line.bounds.size.width, fragment)
+            })
+            .collect::<Vec<_>>()
+    };
+    use StyleOverflowWrap::{Anywhere, Normal as NoWrap};
+    use StyleWordBreak::{BreakAll, KeepAll, Normal};
+
+    // long words overflow the line, unless they may be broken anywhere
+    assert_eq!(
+        line_widths("aaaaaaa bb", NoWrap, Normal),
+        vec![(35.0, None), (10.0, None)]
+    );
+    assert_eq!(
+        line_widths("aaaaaaa bb", Anywhere, Normal),
+        vec![(20.0, Some(0..4)), (15.0, Some(4..7)), (10.0, None)]
+    );
+    // a grapheme cluster is never broken, even if it overflows the line
+    assert_eq!(
+        line_widths("aaaq\u{301}", Anywhere, Normal),
+        vec![(15.0, Some(0..3)), (10.0, Some(3..5))]
+    );
+    // word-break: break-all breaks the word at the end of the line instead of moving it
+    assert_eq!(
+        line_widths("aa bbbbb", NoWrap, BreakAll),
+        vec![(20.0, Some(0..1)), (20.0, Some(1..5))]
+    );
+    // lines can be broken between CJK characters, except with keep-all
+    assert_eq!(
+        line_widths("\u{4E2D}\u{6587}\u{4E2D}\u{6587}\u{4E2D}", NoWrap, Normal),
+        vec![(20.0, Some(0..4)), (5.0, Some(4..5))]
+    );
+    assert_eq!(
+        line_widths("\u{4E2D}\u{6587}\u{4E2D}\u{6587}\u{4E2D}", NoWrap, KeepAll),
+        vec![(25.0, None)]
+    );
+}
+
+#[test]
+fn test_get_intrinsic_sizes() {
+    let text = "aa bbb c";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let shaped_words = get_test_shaped_words(&words);
+    let options = |white_space: StyleWhiteSpace, overflow_wrap: StyleOverflowWrap| {
+        ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            white_space,
+            overflow_wrap,
+            ..Default::default()
+        }
+    };
+    let intrinsic_sizes = |white_space: StyleWhiteSpace, overflow_wrap: StyleOverflowWrap| {
+        let sizes =
+            get_intrinsic_sizes(&words, &shaped_words, &options(white_space, overflow_wrap));
+        (sizes.min_content_width, sizes.max_content_width)
+    };
+
+    // min-content: the widest word, max-content: the whole text on one line
+    assert_eq!(
+        intrinsic_sizes(StyleWhiteSpace::Normal, StyleOverflowWrap::Normal),
+        (15.0, 40.0)
+    );
+    // only `anywhere` makes the word breaks count towards the min-content width
+    assert_eq!(
+        intrinsic_sizes(StyleWhiteSpace::Normal, StyleOverflowWrap::Anywhere),
+        (5.0, 40.0)
+    );
+    assert_eq!(
+        intrinsic_sizes(StyleWhiteSpace::Normal, StyleOverflowWrap::BreakWord),
+        (15.0, 40.0)
+    );
+    // text that doesn't wrap can't get narrower
+    assert_eq!(
+        intrinsic_sizes(StyleWhiteSpace::Nowrap, StyleOverflowWrap::Normal),
+        (40.0, 40.0)
+    );
+
+    // lines are 10px high: "aa" / "bbb" / "c" at 20px, everything on one line at 40px
+    let normal = options(StyleWhiteSpace::Normal, StyleOverflowWrap::Normal);
+    assert_eq!(
+        get_height_for_width(&words, &shaped_words, &normal, 20.0),
+        30.0
+    );
+    assert_eq!(
+        get_height_for_width(&words, &shaped_words, &normal, 40.0),
+        10.0
+    );
+}
+
+#[test]
+fn test_leading_spaces_with_leading() {
+    // the first line starts at the leading, its collapsible spaces are still removed
+    let layout = |white_space: StyleWhiteSpace| {
+        let words = split_text_into_words("  ab", white_space, TextNormalization::Nfc);
+        let shaped_words = get_test_shaped_words(&words);
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            leading: Some(10.0),
+            white_space,
+            ..Default::default()
+        };
+        let word_positions = position_words(&words, &shaped_words, &options);
+        let word_x = word_positions.word_positions.last().unwrap().position.x;
+        let intrinsic_sizes = get_intrinsic_sizes(&words, &shaped_words, &options);
+        (word_x, intrinsic_sizes.max_content_width)
+    };
+
+    assert_eq!(layout(StyleWhiteSpace::Normal), (10.0, 20.0));
+    assert_eq!(layout(StyleWhiteSpace::PreWrap), (20.0, 30.0));
+}
+
+#[test]
+fn test_position_words_line_height() {
+    use crate::css::StyleLineHeight;
+
+    // ascender 8px, descender 2px at 10px, two lines
+    let text = "aa bb";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let mut shaped_words = get_test_shaped_words(&words);
+    let lines = |shaped_words: &ShapedWords, line_height: StyleLineHeight| {
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            line_height,
+            max_horizontal_width: Some(10.0),
+            ..Default::default()
+        };
+        position_words(&words, shaped_words, &options)
+            .line_breaks
+            .iter()
+            .map(|line| (line.bounds.origin.y, line.bounds.size.height, line.baseline))
+            .collect::<Vec<_>>()
+    };
+
+    // `normal`: ascender - descender + line gap
+    assert_eq!(
+        lines(&shaped_words, StyleLineHeight::Normal),
+        vec![(0.0, 10.0, 8.0), (10.0, 10.0, 8.0)]
+    );
+    // the leading is split evenly above and below the glyphs
+    assert_eq!(
+        lines(&shaped_words, StyleLineHeight::Multiplier(2.0)),
+        vec![(0.0, 20.0, 13.0), (20.0, 20.0, 13.0)]
+    );
+    assert_eq!(
+        lines(&shaped_words, StyleLineHeight::Px(6.0)),
+        vec![(0.0, 6.0, 6.0), (6.0, 6.0, 6.0)]
+    );
+    shaped_words.font_metrics_line_gap = 200;
+    assert_eq!(
+        lines(&shaped_words, StyleLineHeight::Normal),
+        vec![(0.0, 12.0, 9.0), (12.0, 12.0, 9.0)]
+    );
+}
+
+#[test]
+fn test_position_words_spans() {
+    use crate::{css::StyleInlineVerticalAlign, ui_solver::InlineSpan};
+
+    // ascender 8px, descender 2px at 10px, superscripts are raised by 3px, subscripts
+    // lowered by 2px: returns the x positions of the words "aa", "bb", "cc" and
+    // the height and baseline of the line
+    let text = "aa bb cc";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let shaped_words = get_test_shaped_words(&words);
+    let layout = |range: Range<usize>,
+                  font_size_px: Option<f32>,
+                  vertical_align: StyleInlineVerticalAlign| {
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            spans: vec![InlineSpan {
+                range,
+                font_size_px,
+                vertical_align,
+                font_variant_caps: None,
+            }],
+            ..Default::default()
+        };
+        let word_positions = position_words(&words, &shaped_words, &options);
+        let line = &word_positions.line_breaks[0];
+        let word_x = [0, 2, 4].map(|i| word_positions.word_positions[i].position.x);
+        (word_x, line.bounds.size.height, line.baseline)
+    };
+    use StyleInlineVerticalAlign::*;
+
+    assert_eq!(layout(0..0, None, Baseline), ([0.0, 15.0, 30.0], 10.0, 8.0));
+    // the larger font size makes "bb" wider and the line taller
+    assert_eq!(
+        layout(3..5, Some(20.0), Baseline),
+        ([0.0, 15.0, 40.0], 20.0, 16.0)
+    );
+    // the span starts inside of "bb": only the second "b" is larger
+    assert_eq!(
+        layout(4..8, Some(20.0), Baseline),
+        ([0.0, 15.0, 40.0], 20.0, 16.0)
+    );
+
+    // shifted baselines extend the line
+    assert_eq!(layout(3..5, None, Super), ([0.0, 15.0, 30.0], 13.0, 11.0));
+    assert_eq!(layout(3..5, None, Sub), ([0.0, 15.0, 30.0], 12.0, 8.0));
+    assert_eq!(
+        layout(3..5, None, Length(5.0)),
+        ([0.0, 15.0, 30.0], 15.0, 13.0)
+    );
+    // a box aligned to the top of the line grows the line downwards
+    assert_eq!(
+        layout(3..5, Some(20.0), Top),
+        ([0.0, 15.0, 40.0], 20.0, 8.0)
+    );
+    assert_eq!(
+        layout(3..5, Some(20.0), Bottom),
+        ([0.0, 15.0, 40.0], 20.0, 18.0)
+    );
+}
+
+#[test]
+fn test_position_words_inline_objects() {
+    use crate::{
+        css::StyleInlineVerticalAlign,
+        logical::{LogicalRect, LogicalSize},
+        ui_solver::{InlineObject, InlineSpan},
+        words::get_inline_text,
+    };
+
+    // a 30x20px object with its baseline 5px above its bottom edge, the text has an
+    // ascender of 8px and a descender of 2px: returns the bounds of the object and
+    // the height and baseline of the lines
+    let text = "aa \u{FFFC} b";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let shaped_words = get_test_shaped_words(&words);
+    let layout = |max_width: Option<f32>, vertical_align: StyleInlineVerticalAlign| {
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            max_horizontal_width: max_width,
+            spans: vec![InlineSpan {
+                range: 3..6,
+                font_size_px: None,
+                vertical_align,
+                font_variant_caps: None,
+            }],
+            inline_objects: vec![InlineObject {
+                size: LogicalSize::new(30.0, 20.0),
+                baseline_offset: 5.0,
+            }],
+            ..Default::default()
+        };
+        let word_positions = position_words(&words, &shaped_words, &options);
+        let inline_text_layout = word_positions_to_inline_text_layout(&word_positions);
+        let inline_text =
+            get_inline_text(&words, &shaped_words, &word_positions, &inline_text_layout);
+        let lines = inline_text
+            .lines
+            .iter()
+            .map(|line| (line.bounds.size.height, line.baseline))
+            .collect::<Vec<_>>();
+        (inline_text.get_inline_object_bounds(), lines)
+    };
+    let bounds = |x: f32, y: f32| {
+        vec![(
+            0,
+            LogicalRect::new(LogicalPosition::new(x, y), LogicalSize::new(30.0, 20.0)),
+        )]
+    };
+    use StyleInlineVerticalAlign::*;
+
+    // the object sits on the baseline and makes the line taller
+    assert_eq!(
+        layout(None, Baseline),
+        (bounds(15.0, 0.0), vec![(20.0, 15.0)])
+    );
+    // the object is as wide as a word: "aa " + object don't fit into 40px
+    assert_eq!(
+        layout(Some(40.0), Baseline),
+        (bounds(0.0, 10.0), vec![(10.0, 8.0), (20.0, 15.0)])
+    );
+    // raised objects extend the line upwards, objects aligned to the top downwards
+    assert_eq!(
+        layout(None, Length(5.0)),
+        (bounds(15.0, 0.0), vec![(22.0, 20.0)])
+    );
+    assert_eq!(layout(None, Top), (bounds(15.0, 0.0), vec![(20.0, 8.0)]));
+}
+
+#[test]
+fn test_synthetic_small_caps() {
+    use crate::{
+        css::{StyleFontVariantCaps, StyleInlineVerticalAlign},
+        test_font::TestFont,
+        text_shaping::SYNTHETIC_SMALL_CAPS_SCALE,
+        ui_solver::InlineSpan,
+        words::get_inline_text,
+    };
+
+    // the test font has no `smcp` feature: lower case letters are replaced by
+    // capital letters at 0.7 times the size, "ß" is expanded to "SS", the span
+    // over "Bb" switches back to normal caps
+    let font = ParsedFont::from_bytes(&TestFont::default().to_bytes(), 0).unwrap();
+    let text = "aß Bb";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let options = ResolvedTextLayoutOptions {
+        font_size_px: 10.0,
+        font_variant_caps: StyleFontVariantCaps::SmallCaps,
+        spans: vec![InlineSpan {
+            range: 4..6,
+            font_size_px: None,
+            vertical_align: StyleInlineVerticalAlign::Baseline,
+            font_variant_caps: Some(StyleFontVariantCaps::Normal),
+        }],
+        ..Default::default()
+    };
+    let shaped_words = shape_words_with_options(&words, &font, &options);
+
+    let glyphs = |word: &ShapedWord| {
+        word.glyph_infos
+            .iter()
+            .map(|g| {
+                (
+                    g.info.glyph.glyph_index,
+                    g.advance.advance_x,
+                    g.scale,
+                    g.info.glyph.multi_subst_dup,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(SYNTHETIC_SMALL_CAPS_SCALE, 0.7);
+    assert_eq!(
+        glyphs(&shaped_words.items[0]),
+        vec![
+            (1, 350, 0.7, false),
+            (1, 350, 0.7, false),
+            (1, 350, 0.7, true),
+        ]
+    );
+    assert_eq!(shaped_words.items[0].word_width, 1050);
+    let clusters = shaped_words.items[0]
+        .clusters
+        .iter()
+        .map(|c| (c.text_range.clone(), c.glyph_range.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(clusters, vec![(0..1, 0..1), (1..3, 1..3)]);
+    assert_eq!(
+        glyphs(&shaped_words.items[1]),
+        vec![(1, 500, 1.0, false), (1, 500, 1.0, false)]
+    );
+
+    // the glyph instances carry the scale that they are drawn with
+    let word_positions = position_words(&words, &shaped_words, &options);
+    let inline_text_layout = word_positions_to_inline_text_layout(&word_positions);
+    let glyphs = get_inline_text(&words, &shaped_words, &word_positions, &inline_text_layout)
+        .get_layouted_glyphs()
+        .glyphs
+        .into_iter()
+        .map(|g| (g.point.x, g.scale))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        glyphs,
+        vec![(0.0, 0.7), (3.5, 0.7), (7.0, 0.7), (15.5, 1.0), (20.5, 1.0)]
+    );
+}
+
+#[test]
+fn test_spans_inside_of_words() {
+    use crate::{
+        css::{StyleFontVariantCaps, StyleInlineVerticalAlign},
+        test_font::TestFont,
+        ui_solver::InlineSpan,
+        words::{get_inline_text, InlineWord},
+    };
+
+    // the middle "a" of "aab" is a superscript in small caps at twice the size, the
+    // word is shaped in three parts and every part is positioned on its own baseline
+    let font = ParsedFont::from_bytes(&TestFont::default().to_bytes(), 0).unwrap();
+    let text = "aab c";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let options = ResolvedTextLayoutOptions {
+        font_size_px: 10.0,
+        spans: vec![InlineSpan {
+            range: 1..2,
+            font_size_px: Some(20.0),
+            vertical_align: StyleInlineVerticalAlign::Super,
+            font_variant_caps: Some(StyleFontVariantCaps::SmallCaps),
+        }],
+        ..Default::default()
+    };
+    let shaped_words = shape_words_with_options(&words, &font, &options);
+    let scales = shaped_words.items[0]
+        .glyph_infos
+        .iter()
+        .map(|g| g.scale)
+        .collect::<Vec<_>>();
+    assert_eq!(scales, vec![1.0, 0.7, 1.0]);
+
+    let word_positions = position_words(&words, &shaped_words, &options);
+    assert_eq!(word_positions.word_positions[0].size.width, 17.0);
+    assert_eq!(word_positions.word_positions[2].position.x, 22.0);
+
+    let inline_text_layout = word_positions_to_inline_text_layout(&word_positions);
+    let inline_text = get_inline_text(&words, &shaped_words, &word_positions, &inline_text_layout);
+    let line = &inline_text.lines[0];
+    let parts = line
+        .words
+        .iter()
+        .filter_map(InlineWord::get_text_content)
+        .map(|w| {
+            (
+                w.bounds.origin.x,
+                w.bounds.size.width,
+                w.font_size_px,
+                w.baseline,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(line.baseline, 19.0);
+    assert_eq!(
+        parts,
+        vec![
+            (0.0, 5.0, 10.0, 19.0),
+            (5.0, 7.0, 20.0, 16.0),
+            (12.0, 5.0, 10.0, 19.0),
+            (22.0, 5.0, 10.0, 19.0),
+        ]
+    );
+    let glyphs = inline_text
+        .get_layouted_glyphs()
+        .glyphs
+        .into_iter()
+        .map(|g| (g.point.x, g.point.y))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        glyphs,
+        vec![(0.0, 19.0), (5.0, 16.0), (12.0, 19.0), (22.0, 19.0)]
+    );
+}
+
+#[test]
+fn test_vertical_writing_modes() {
+    use crate::{
+        css::StyleWritingMode,
+        test_font::{TestFont, TestGlyph},
+        words::get_inline_text,
+    };
+
+    // latin letters are 500 units wide and rotated sideways, "中" is a 1000 units
+    // wide, upright glyph: without vertical metrics, it is advanced by one em and its
+    // vertical origin is the ascender (800), the lines are 10px thick at 10px
+    let mut test_font = TestFont::default();
+    test_font
+        .glyphs
+        .push((1000, TestGlyph::rect(0, -100, 1000, 800)));
+    test_font.cmap.push(('中', 3));
+    let font = ParsedFont::from_bytes(&test_font.to_bytes(), 0).unwrap();
+
+    let text = "ab 中\ncd";
+    let words = split_text_into_words(text, StyleWhiteSpace::PreLine, TextNormalization::Nfc);
+    let shaped_words = shape_words_vertical(&words, &font);
+    let layout = |writing_mode: StyleWritingMode| {
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            white_space: StyleWhiteSpace::PreLine,
+            writing_mode,
+            ..Default::default()
+        };
+        let word_positions = position_words(&words, &shaped_words, &options);
+        let inline_text_layout = word_positions_to_inline_text_layout(&word_positions);
+        get_inline_text(&words, &shaped_words, &word_positions, &inline_text_layout)
+            .get_layouted_glyphs()
+            .glyphs
+            .into_iter()
+            .map(|g| (g.index, g.point.x, g.point.y, g.rotated))
+            .collect::<Vec<_>>()
+    };
+
+    // the first line is on the right: the baseline of the sideways glyphs is 8px left
+    // of the right edge of the line, "中" is centered on the line and its origin is
+    // 8px below the top of its glyph box (the advance along the line starts at 15px)
+    assert_eq!(
+        layout(StyleWritingMode::VerticalRl),
+        vec![
+            (1, 12.0, 0.0, true),
+            (1, 12.0, 5.0, true),
+            (3, 10.0, 23.0, false),
+            (1, 2.0, 0.0, true),
+            (1, 2.0, 5.0, true),
+        ]
+    );
+    // the first line is on the left
+    assert_eq!(
+        layout(StyleWritingMode::VerticalLr),
+        vec![
+            (1, 2.0, 0.0, true),
+            (1, 2.0, 5.0, true),
+            (3, 0.0, 23.0, false),
+            (1, 12.0, 0.0, true),
+            (1, 12.0, 5.0, true),
+        ]
+    );
+
+    // horizontal text isn't rotated: "中" is only advanced by its horizontal advance
+    let shaped_words = shape_words(&words, &font);
+    let options = ResolvedTextLayoutOptions {
+        font_size_px: 10.0,
+        white_space: StyleWhiteSpace::PreLine,
+        ..Default::default()
+    };
+    let word_positions = position_words(&words, &shaped_words, &options);
+    let inline_text_layout = word_positions_to_inline_text_layout(&word_positions);
+    let glyphs = get_inline_text(&words, &shaped_words, &word_positions, &inline_text_layout)
+        .get_layouted_glyphs()
+        .glyphs;
+    assert_eq!(
+        glyphs
+            .iter()
+            .map(|g| (g.index, g.point.x, g.point.y, g.rotated))
+            .collect::<Vec<_>>(),
+        vec![
+            (1, 0.0, 8.0, false),
+            (1, 5.0, 8.0, false),
+            (3, 15.0, 8.0, false),
+            (1, 0.0, 18.0, false),
+            (1, 5.0, 18.0, false),
+        ]
+    );
+}
+
+#[test]
+fn test_position_words_containers() {
+    // 5px per character and space, 10px per line: at most "aa bb" fits into a line
+    let text = "aa bb cc dd ee\nff gg";
+    let words = split_text_into_words(text, StyleWhiteSpace::PreLine, TextNormalization::Nfc);
+    let shaped_words = get_test_shaped_words(&words);
+    let options = |containers: Vec<LogicalSize>, widows: usize| ResolvedTextLayoutOptions {
+        font_size_px: 10.0,
+        white_space: StyleWhiteSpace::PreLine,
+        containers,
+        widows,
+        orphans: 1,
+        ..Default::default()
+    };
+    let container_lines = |word_positions: &WordPositions| {
+        word_positions
+            .containers
+            .iter()
+            .map(|c| (c.lines.clone(), c.words.clone()))
+            .collect::<Vec<_>>()
+    };
+    let container = LogicalSize::new(30.0, 20.0);
+
+    // two lines per container
+    let word_positions = position_words(&words, &shaped_words, &options(vec![container; 3], 0));
+    assert_eq!(
+        container_lines(&word_positions),
+        vec![(0..2, 0..8), (2..4, 8..13)]
+    );
+    assert_eq!(word_positions.line_breaks[2].bounds.origin.y, 0.0);
+    assert_eq!(word_positions.truncated_at, None);
+
+    // the last line of the first paragraph ("ee") would be a widow
+    let word_positions = position_words(&words, &shaped_words, &options(vec![container; 3], 2));
+    assert_eq!(
+        container_lines(&word_positions),
+        vec![(0..1, 0..4), (1..3, 4..9), (3..4, 10..13)]
+    );
+
+    // the text that doesn't fit into the last container is cut off
+    let word_positions = position_words(&words, &shaped_words, &options(vec![container], 0));
+    assert_eq!(container_lines(&word_positions), vec![(0..2, 0..7)]);
+    assert_eq!(word_positions.truncated_at, Some(11));
+}
+
+#[test]
+fn test_position_words_columns() {
+    use crate::css::StyleColumnFill;
+
+    // 5px per character and space, 10px per line: at most "aa bb" fits into a column,
+    // single lines may be left at the top or bottom of a column
+    let text = "aa bb cc dd ee ff gg hh";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let shaped_words = get_test_shaped_words(&words);
+    let options =
+        |column_count: Option<usize>, column_fill: StyleColumnFill| ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            max_horizontal_width: Some(65.0),
+            max_vertical_height: Some(30.0),
+            column_count,
+            column_width: Some(30.0),
+            column_gap: 5.0,
+            column_fill,
+            widows: 1,
+            orphans: 1,
+            ..Default::default()
+        };
+    let columns = |word_positions: &WordPositions| {
+        word_positions
+            .containers
+            .iter()
+            .map(|c| (c.lines.clone(), c.bounds.origin.x, c.bounds.size.width))
+            .collect::<Vec<_>>()
+    };
+
+    // two columns of 30px fit into 65px, the four lines are balanced
+    let word_positions = position_words(
+        &words,
+        &shaped_words,
+        &options(None, StyleColumnFill::Balance),
+    );
+    assert_eq!(
+        columns(&word_positions),
+        vec![(0..2, 0.0, 30.0), (2..4, 35.0, 30.0)]
+    );
+    assert_eq!(
+        word_positions.line_breaks[2].bounds.origin,
+        LogicalPosition::new(35.0, 0.0)
+    );
+    assert_eq!(word_positions.content_size.width, 65.0);
+
+    // the first column is filled up to the maximum height
+    let word_positions =
+        position_words(&words, &shaped_words, &options(None, StyleColumnFill::Auto));
+    assert_eq!(
+        columns(&word_positions),
+        vec![(0..3, 0.0, 30.0), (3..4, 35.0, 30.0)]
+    );
+
+    // the column count limits the number of columns
+    let word_positions = position_words(
+        &words,
+        &shaped_words,
+        &options(Some(1), StyleColumnFill::Balance),
+    );
+    assert_eq!(columns(&word_positions), vec![(0..2, 0.0, 65.0)]);
+}
+
+#[test]
+fn test_split_text_into_paragraphs() {
+    let text = "first\nsecond\r\n\rthird\u{2029}";
+    let paragraphs = split_text_into_paragraphs(text)
+        .into_iter()
+        .map(|range| &text[range])
+        .collect::<Vec<_>>();
+    assert_eq!(paragraphs, vec!["first", "second", "", "third", ""]);
+    assert_eq!(split_text_into_paragraphs(""), vec![0..0]);
+}
+
+#[test]
+fn test_letter_and_word_spacing() {
+    use crate::{css::StyleTextSpacing, words::get_inline_text};
+    use allsorts::gpos::Placement;
+
+    // 5px per character and space, 1px letter spacing and a word spacing of 5px
+    let text = "aa bb cc";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let shaped_words = get_test_shaped_words(&words);
+    let options = |max_horizontal_width: Option<f32>| ResolvedTextLayoutOptions {
+        font_size_px: 10.0,
+        letter_spacing: Some(StyleTextSpacing::Em(0.1)),
+        word_spacing: Some(StyleTextSpacing::Percent(100.0)),
+        max_horizontal_width,
+        ..Default::default()
+    };
+
+    // a space advances by the letter spacing after the previous word, the space
+    // itself (5px), the word spacing and the letter spacing after the space
+    let word_positions = position_words(&words, &shaped_words, &options(None));
+    let word_x = word_positions
+        .word_positions
+        .iter()
+        .map(|p| p.position.x)
+        .collect::<Vec<_>>();
+    assert_eq!(word_x, vec![0.0, 11.0, 23.0, 34.0, 46.0]);
+    // no letter spacing after the last letter of the line
+    assert_eq!(word_positions.content_size.width, 57.0);
+
+    let word_positions = position_words(&words, &shaped_words, &options(Some(40.0)));
+    assert_eq!(word_positions.number_of_lines, 2);
+    assert_eq!(word_positions.line_breaks[0].bounds.size.width, 34.0);
+
+    let intrinsic_sizes = get_intrinsic_sizes(&words, &shaped_words, &options(None));
+    assert_eq!(intrinsic_sizes.min_content_width, 11.0);
+    assert_eq!(intrinsic_sizes.max_content_width, 57.0);
+
+    // glyphs that are moved by GPOS are letter spaced like any other glyph, marks aren't:
+    // the second "b" is moved by 1px, the second "c" is an (empty) mark on the first one
+    let mut shaped_words = shaped_words;
+    shaped_words.items[1].glyph_infos[1].info.placement = Placement::Distance(100, 0);
+    let mark = &mut shaped_words.items[2].glyph_infos[1];
+    mark.info.placement = Placement::MarkOverprint(0);
+    mark.advance.advance_x = 0;
+    let word_positions = position_words(&words, &shaped_words, &options(None));
+    let word_widths = [0, 2, 4].map(|i| word_positions.word_positions[i].size.width);
+    assert_eq!(word_widths, [11.0, 11.0, 5.0]);
+    let inline_text_layout = word_positions_to_inline_text_layout(&word_positions);
+    let glyph_x = get_inline_text(&words, &shaped_words, &word_positions, &inline_text_layout)
+        .get_layouted_glyphs()
+        .glyphs
+        .into_iter()
+        .map(|g| g.point.x)
+        .collect::<Vec<_>>();
+    assert_eq!(glyph_x, vec![0.0, 6.0, 23.0, 30.0, 46.0, 46.0]);
+}
+
+#[test]
+fn test_hanging_punctuation() {
+    use crate::css::{StyleHangingPunctuation, StyleHangingPunctuationEnd};
+
+    // 5px per character and space
+    let layout = |text: &str,
+                  hanging_punctuation: StyleHangingPunctuation,
+                  optical_margin_alignment: bool,
+                  max_horizontal_width: Option<f32>| {
+        let words = split_text_into_words(text, StyleWhiteSpace::PreLine, TextNormalization::Nfc);
+        let shaped_words = get_test_shaped_words(&words);
+        let options = ResolvedTextLayoutOptions {
+            font_size_px: 10.0,
+            white_space: StyleWhiteSpace::PreLine,
+            hanging_punctuation,
+            optical_margin_alignment,
+            max_horizontal_width,
+            ..Default::default()
+        };
+        let word_positions = position_words(&words, &shaped_words, &options);
+        word_positions
+            .line_breaks
+            .iter()
+            .map(|line| {
+                let first_word_x = word_positions.word_positions[*line.words.start()]
+                    .position
+                    .x;
+                (first_word_x, line.bounds.size.width)
+            })
+            .collect::<Vec<_>>()
+    };
+    let end = |end: StyleHangingPunctuationEnd| StyleHangingPunctuation {
+        end,
+        ..Default::default()
+    };
+
+    // the period only fits onto the first line if it hangs
+    let text = "bb aa. cc";
+    assert_eq!(layout(text, Default::default(), false, Some(25.0)).len(), 3);
+    for hang_end in [
+        StyleHangingPunctuationEnd::AllowEnd,
+        StyleHangingPunctuationEnd::ForceEnd,
+    ] {
+        assert_eq!(
+            layout(text, end(hang_end), false, Some(25.0)),
+            vec![(0.0, 25.0), (0.0, 10.0)]
+        );
+    }
+    // `allow-end` only hangs the period if it doesn't fit otherwise
+    assert_eq!(
+        layout(
+            "b.",
+            end(StyleHangingPunctuationEnd::AllowEnd),
+            false,
+            Some(25.0)
+        ),
+        vec![(0.0, 10.0)]
+    );
+    assert_eq!(
+        layout(
+            "b.",
+            end(StyleHangingPunctuationEnd::ForceEnd),
+            false,
+            Some(25.0)
+        ),
+        vec![(0.0, 5.0)]
+    );
+
+    // opening quotes hang at the start of every paragraph, closing quotes at the end
+    let first_last = StyleHangingPunctuation {
+        first: true,
+        last: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        layout("\u{201C}aa bb\u{201D}\n\u{201C}c", first_last, false, None),
+        vec![(-5.0, 25.0), (-5.0, 5.0)]
+    );
+    // ... but not at the start or end of the lines in between
+    assert_eq!(
+        layout("(aa (bb) cc)", first_last, false, Some(25.0)),
+        vec![(-5.0, 10.0), (0.0, 20.0), (0.0, 10.0)]
+    );
+
+    // optical margin alignment: hyphens and periods protrude by 70% of their advance
+    assert_eq!(
+        layout("-aa.", Default::default(), true, None),
+        vec![(-3.5, 13.0)]
+    );
+}
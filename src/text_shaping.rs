@@ -1,17 +1,21 @@
 use crate::{
-    css::FontMetrics,
-    words::{Advance, GlyphInfo},
+    color_font::{ColorGlyphTables, GlyphData, GlyphKind},
+    css::{FontMetrics, StyleFontVariantCaps},
+    font_database::FontSynthesis,
+    words::{is_upright_in_vertical_text, Advance, GlyphInfo, GlyphOrientation},
 };
 use allsorts::{
     binary::read::ReadScope,
     font_data::FontData,
     layout::{GDEFTable, LayoutCache, GPOS, GSUB},
+    post::PostTable,
     tables::cmap::owned::CmapSubtable as OwnedCmapSubtable,
     tables::{
         cmap::CmapSubtable,
         glyf::{GlyfRecord, GlyfTable, Glyph},
         loca::LocaTable,
-        FontTableProvider, HeadTable, MaxpTable,
+        os2::Os2,
+        FontTableProvider, HeadTable, HheaTable, HmtxTable, MaxpTable,
     },
     tinyvec::tiny_vec,
     DOTTED_CIRCLE,
@@ -19,18 +23,75 @@ use allsorts::{
 use std::collections::btree_map::BTreeMap;
 use std::rc::Rc;
 
-fn get_font_metrics(font_bytes: &[u8], font_index: usize) -> FontMetrics {
+fn get_font_metrics(font_bytes: &[u8], font_index: usize) -> Option<FontMetrics> {
     let scope = ReadScope::new(font_bytes);
-    let font_file = scope.read::<FontData<'_>>().unwrap();
-    let provider = font_file.table_provider(font_index).unwrap();
-    let font = allsorts::font::Font::new(provider).unwrap().unwrap();
+    let font_file = scope.read::<FontData<'_>>().ok()?;
+    let provider = font_file.table_provider(font_index).ok()?;
+    let font = allsorts::font::Font::new(provider).ok()??;
 
     // read the HHEA table to get the metrics for horizontal layout
-    let head = font.head_table().unwrap().unwrap();
-    let os2 = font.os2_table().unwrap().unwrap();
+    let head = font.head_table().ok()??;
+    // the `OS/2` table is optional (i.e. in old Mac fonts), the getters
+    // of the metrics fall back to the `hhea` table or the em size
+    let os2 = font
+        .os2_table()
+        .ok()
+        .flatten()
+        .unwrap_or_else(get_empty_os2_table);
     let hhea = font.hhea_table;
+    let post = font
+        .font_table_provider
+        .table_data(allsorts::tag::POST)
+        .ok()
+        .flatten()
+        .and_then(|data| {
+            ReadScope::new(&data)
+                .read::<PostTable<'_>>()
+                .ok()
+                .map(|post| post.header)
+        });
 
-    FontMetrics { hhea, head, os2 }
+    Some(FontMetrics {
+        hhea,
+        head,
+        os2,
+        post,
+    })
+}
+
+/// `OS/2` table of a font that doesn't have one: a regular face, all metrics are unset
+fn get_empty_os2_table() -> Os2 {
+    Os2 {
+        version: 0,
+        x_avg_char_width: 0,
+        us_weight_class: 400,
+        us_width_class: 5,
+        fs_type: 0,
+        y_subscript_x_size: 0,
+        y_subscript_y_size: 0,
+        y_subscript_x_offset: 0,
+        y_subscript_y_offset: 0,
+        y_superscript_x_size: 0,
+        y_superscript_y_size: 0,
+        y_superscript_x_offset: 0,
+        y_superscript_y_offset: 0,
+        y_strikeout_size: 0,
+        y_strikeout_position: 0,
+        s_family_class: 0,
+        panose: [0; 10],
+        ul_unicode_range1: 0,
+        ul_unicode_range2: 0,
+        ul_unicode_range3: 0,
+        ul_unicode_range4: 0,
+        ach_vend_id: 0,
+        fs_selection: 0,
+        us_first_char_index: 0,
+        us_last_char_index: 0,
+        version0: None,
+        version1: None,
+        version2to4: None,
+        version5: None,
+    }
 }
 
 pub struct ParsedFont {
@@ -38,12 +99,76 @@ pub struct ParsedFont {
     pub num_glyphs: u16,
     pub hmtx_data: Box<[u8]>,
     pub maxp_table: MaxpTable,
-    pub gsub_cache: LayoutCache<GSUB>,
-    pub gpos_cache: LayoutCache<GPOS>,
+    /// `None` if the font has no `GSUB` table (no ligatures, no alternate glyphs)
+    pub gsub_cache: Option<LayoutCache<GSUB>>,
+    /// `None` if the font has no `GPOS` table (no kerning, no mark positioning)
+    pub gpos_cache: Option<LayoutCache<GPOS>>,
     pub opt_gdef_table: Option<Rc<GDEFTable>>,
     pub glyph_records_decoded: BTreeMap<u16, OwnedGlyph>,
     pub space_width: Option<usize>,
     pub cmap_subtable: OwnedCmapSubtable,
+    /// `None` if the font has no `vhea` / `vmtx` tables (no metrics for vertical text)
+    pub vertical_metrics: Option<VerticalMetrics>,
+    /// `COLR`, `CPAL`, `sbix`, `CBDT` / `CBLC` and `SVG ` tables
+    pub color_glyphs: ColorGlyphTables,
+    /// `None` if ttf-parser can't read the font (e.g. WOFF2), then the glyphs have no outlines
+    pub outline_source: Option<OutlineSource>,
+    /// Synthetic bold / oblique that is applied to the advances, bounds and
+    /// outlines of the glyphs (for families that don't have a bold / italic face)
+    pub synthesis: FontSynthesis,
+}
+
+/// Stroke width that synthetic bold adds to the glyphs (as a fraction of the em)
+pub const SYNTHETIC_BOLD_STRENGTH: f32 = 1.0 / 24.0;
+
+/// Slant of synthetic oblique glyphs (in degrees)
+pub const SYNTHETIC_OBLIQUE_ANGLE: f32 = 14.0;
+
+/// Size of synthetic small caps (capital letters that are scaled down, if the
+/// font doesn't have small caps), relative to the font size
+pub const SYNTHETIC_SMALL_CAPS_SCALE: f32 = 0.7;
+
+/// Script that the `GSUB` / `GPOS` features are looked up for
+const SHAPING_SCRIPT: u32 = allsorts::tag::LATN;
+
+/// Metrics for vertical text (`vhea`, `vmtx` and `VORG` tables)
+pub struct VerticalMetrics {
+    /// Same layout as the `hhea` table, but the values are for vertical text
+    pub vhea: HheaTable,
+    pub vmtx_data: Box<[u8]>,
+    /// Only present in CFF fonts, TrueType fonts use the `vmtx` top side bearing instead
+    pub vorg: Option<VorgTable>,
+}
+
+/// Parsed `VORG` table: the y coordinate of the vertical origin of each glyph
+#[derive(Debug, Clone, PartialEq)]
+pub struct VorgTable {
+    pub default_vert_origin_y: i16,
+    pub vert_origin_y_metrics: BTreeMap<u16, i16>,
+}
+
+impl VorgTable {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let read_u16 = |offset: usize| {
+            data.get(offset..offset + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        };
+
+        // majorVersion, minorVersion, defaultVertOriginY, numVertOriginYMetrics
+        let default_vert_origin_y = read_u16(4)? as i16;
+        let num_metrics = read_u16(6)? as usize;
+        let vert_origin_y_metrics = (0..num_metrics)
+            .map(|i| {
+                let offset = 8 + i * 4;
+                Some((read_u16(offset)?, read_u16(offset + 2)? as i16))
+            })
+            .collect::<Option<BTreeMap<_, _>>>()?;
+
+        Some(Self {
+            default_vert_origin_y,
+            vert_origin_y_metrics,
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -96,6 +221,150 @@ pub struct GlyphOutline {
     pub operations: Vec<GlyphOutlineOperation>,
 }
 
+impl GlyphOutline {
+    /// Dilates the outline by `strength / 2` on each side and moves it right by
+    /// `strength / 2`, so that the left side bearing stays the same (synthetic bold)
+    pub fn embolden(&mut self, strength: f32) {
+        let offset = strength / 2.0;
+        let mut contour_start = 0;
+        while contour_start < self.operations.len() {
+            let contour_end = self.operations[contour_start + 1..]
+                .iter()
+                .position(|op| matches!(op, GlyphOutlineOperation::MoveTo(_)))
+                .map_or(self.operations.len(), |i| contour_start + 1 + i);
+            let contour = &mut self.operations[contour_start..contour_end];
+
+            let points = get_outline_points(contour);
+            let moved = dilate_contour(&points, offset);
+            let mut moved = moved.into_iter();
+            for_each_outline_point_mut(contour, |x, y| {
+                if let Some((new_x, new_y)) = moved.next() {
+                    *x = new_x + offset;
+                    *y = new_y;
+                }
+            });
+
+            contour_start = contour_end;
+        }
+    }
+
+    /// Slants the outline to the right: x' = x + y * skew_x (synthetic oblique)
+    pub fn skew(&mut self, skew_x: f32) {
+        for_each_outline_point_mut(&mut self.operations, |x, y| *x += *y * skew_x);
+    }
+}
+
+fn for_each_outline_point_mut(
+    operations: &mut [GlyphOutlineOperation],
+    mut f: impl FnMut(&mut f32, &mut f32),
+) {
+    for operation in operations.iter_mut() {
+        match operation {
+            GlyphOutlineOperation::MoveTo(p) => f(&mut p.x, &mut p.y),
+            GlyphOutlineOperation::LineTo(p) => f(&mut p.x, &mut p.y),
+            GlyphOutlineOperation::QuadraticCurveTo(q) => {
+                f(&mut q.ctrl_1_x, &mut q.ctrl_1_y);
+                f(&mut q.end_x, &mut q.end_y);
+            }
+            GlyphOutlineOperation::CubicCurveTo(c) => {
+                f(&mut c.ctrl_1_x, &mut c.ctrl_1_y);
+                f(&mut c.ctrl_2_x, &mut c.ctrl_2_y);
+                f(&mut c.end_x, &mut c.end_y);
+            }
+            GlyphOutlineOperation::ClosePath => {}
+        }
+    }
+}
+
+fn get_outline_points(operations: &mut [GlyphOutlineOperation]) -> Vec<(f32, f32)> {
+    let mut points = Vec::new();
+    for_each_outline_point_mut(operations, |x, y| points.push((*x, *y)));
+    points
+}
+
+/// Moves every point of a closed contour (including control points) outwards,
+/// so that every edge moves by `offset` along its normal
+fn dilate_contour(points: &[(f32, f32)], offset: f32) -> Vec<(f32, f32)> {
+    let n = points.len();
+    if n < 3 {
+        return points.to_vec();
+    }
+
+    // positive area = counter-clockwise contour (font units, y pointing up)
+    let area = (0..n)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f32>();
+    let outward_normal = |from: (f32, f32), to: (f32, f32)| {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length < f32::EPSILON {
+            return None;
+        }
+        let (dx, dy) = (dx / length, dy / length);
+        Some(if area > 0.0 { (dy, -dx) } else { (-dy, dx) })
+    };
+
+    // finds the closest point before / after `i` that is not at the same position
+    let neighbour = |i: usize, step: usize| {
+        (1..n).map(|k| points[(i + k * step) % n]).find(|p| {
+            (p.0 - points[i].0).abs() > f32::EPSILON || (p.1 - points[i].1).abs() > f32::EPSILON
+        })
+    };
+
+    (0..n)
+        .map(|i| {
+            let p = points[i];
+            let (previous, next) = match (neighbour(i, n - 1), neighbour(i, 1)) {
+                (Some(previous), Some(next)) => (previous, next),
+                _ => return p,
+            };
+            let (n1, n2) = match (outward_normal(previous, p), outward_normal(p, next)) {
+                (Some(n1), Some(n2)) => (n1, n2),
+                _ => return p,
+            };
+            // the bisector of the two normals, scaled so that both edges move by `offset`
+            // (limited for very sharp corners, which would otherwise create long spikes)
+            let d = (1.0 + n1.0 * n2.0 + n1.1 * n2.1).max(0.5);
+            (
+                p.0 + (n1.0 + n2.0) * offset / d,
+                p.1 + (n1.1 + n2.1) * offset / d,
+            )
+        })
+        .collect()
+}
+
+/// Font file that the glyph outlines are decoded from when they are needed
+/// (`text-decoration-skip-ink`, vector glyph data): decoding the outlines of
+/// all glyphs up front would take lots of memory
+pub struct OutlineSource {
+    font_bytes: Box<[u8]>,
+    font_index: u32,
+}
+
+impl OutlineSource {
+    fn new(font_bytes: &[u8], font_index: usize) -> Option<Self> {
+        let font_index = u32::try_from(font_index).ok()?;
+        ttf_parser::Face::parse(font_bytes, font_index).ok()?;
+        Some(Self {
+            font_bytes: font_bytes.into(),
+            font_index,
+        })
+    }
+
+    /// Decodes the outline of the glyph (in unscaled units)
+    pub fn get_outline(&self, glyph_index: u16) -> Option<GlyphOutline> {
+        let face = ttf_parser::Face::parse(&self.font_bytes, self.font_index).ok()?;
+        let mut builder = GlyphOutlineBuilder::default();
+        face.outline_glyph(ttf_parser::GlyphId(glyph_index), &mut builder)?;
+        Some(GlyphOutline {
+            operations: builder.operations,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
 struct GlyphOutlineBuilder {
     operations: Vec<GlyphOutlineOperation>,
@@ -166,6 +435,83 @@ impl OwnedGlyph {
     }
 }
 
+/// Decodes the glyph records of the `glyf` table, returns `None` if the font has no `glyf` table
+fn decode_glyf_table<P: FontTableProvider>(
+    provider: &P,
+    head_table: &HeadTable,
+    maxp_table: &MaxpTable,
+    get_advance: &dyn Fn(u16) -> u16,
+) -> Option<BTreeMap<u16, OwnedGlyph>> {
+    use allsorts::tag;
+
+    let loca_data = provider.table_data(tag::LOCA).ok()??.into_owned();
+    let loca_table = ReadScope::new(&loca_data)
+        .read_dep::<LocaTable<'_>>((
+            maxp_table.num_glyphs as usize,
+            head_table.index_to_loc_format,
+        ))
+        .ok()?;
+
+    let glyf_data = provider.table_data(tag::GLYF).ok()??.into_owned();
+    let glyf_table = ReadScope::new(&glyf_data)
+        .read_dep::<GlyfTable<'_>>(&loca_table)
+        .ok()?;
+
+    // not parsing glyph outlines can save lots of memory
+    let glyph_records_decoded = glyf_table
+        .records
+        .into_iter()
+        .enumerate()
+        .filter_map(|(glyph_index, mut glyph_record)| {
+            if glyph_index > (u16::MAX as usize) {
+                return None;
+            }
+            glyph_record.parse().ok()?;
+            let glyph_index = glyph_index as u16;
+
+            match glyph_record {
+                GlyfRecord::Empty | GlyfRecord::Present { .. } => None,
+                GlyfRecord::Parsed(g) => Some((
+                    glyph_index,
+                    OwnedGlyph::from_glyph_data(g, get_advance(glyph_index)),
+                )),
+            }
+        })
+        .collect();
+
+    Some(glyph_records_decoded)
+}
+
+/// Bounding box of a glyph without an outline: the size of the bitmap
+/// (converted to font units) or the em box for SVG glyphs
+fn get_color_glyph_bounding_box(
+    color_glyphs: &ColorGlyphTables,
+    font_metrics: &FontMetrics,
+    glyph_index: u16,
+    horz_advance: u16,
+) -> Option<OwnedGlyphBoundingBox> {
+    let units_per_em = font_metrics.head.units_per_em;
+    if let Some(bitmap) = color_glyphs.get_bitmap(glyph_index, units_per_em) {
+        let scale = units_per_em as f32 / bitmap.pixels_per_em.max(1) as f32;
+        let to_units = |px: f32| (px * scale).round() as i16;
+        return Some(OwnedGlyphBoundingBox {
+            max_x: to_units(bitmap.x as f32 + bitmap.width as f32),
+            max_y: to_units(bitmap.y as f32 + bitmap.height as f32),
+            min_x: to_units(bitmap.x as f32),
+            min_y: to_units(bitmap.y as f32),
+        });
+    }
+
+    color_glyphs
+        .get_svg(glyph_index)
+        .map(|_| OwnedGlyphBoundingBox {
+            max_x: horz_advance as i16,
+            max_y: font_metrics.get_ascender_unscaled(),
+            min_x: 0,
+            min_y: font_metrics.get_descender_unscaled(),
+        })
+}
+
 impl ParsedFont {
     pub fn from_bytes(font_bytes: &[u8], font_index: usize) -> Option<Self> {
         use allsorts::tag;
@@ -180,62 +526,67 @@ impl ParsedFont {
         let maxp_data = provider.table_data(tag::MAXP).ok()??.into_owned();
         let maxp_table = ReadScope::new(&maxp_data).read::<MaxpTable>().ok()?;
 
-        let loca_data = provider.table_data(tag::LOCA).ok()??.into_owned();
-        let loca_table = ReadScope::new(&loca_data)
-            .read_dep::<LocaTable<'_>>((
-                maxp_table.num_glyphs as usize,
-                head_table.index_to_loc_format,
-            ))
-            .ok()?;
-
-        let glyf_data = provider.table_data(tag::GLYF).ok()??.into_owned();
-        let glyf_table = ReadScope::new(&glyf_data)
-            .read_dep::<GlyfTable<'_>>(&loca_table)
-            .ok()?;
-
         let hmtx_data = provider
             .table_data(tag::HMTX)
             .ok()??
             .into_owned()
             .into_boxed_slice();
 
-        let font_metrics = get_font_metrics(font_bytes, font_index);
-
-        // not parsing glyph outlines can save lots of memory
-        let glyph_records_decoded = glyf_table
-            .records
-            .into_iter()
-            .enumerate()
-            .filter_map(|(glyph_index, mut glyph_record)| {
-                if glyph_index > (u16::MAX as usize) {
-                    return None;
-                }
-                glyph_record.parse().ok()?;
-                let glyph_index = glyph_index as u16;
-                let horz_advance = allsorts::glyph_info::advance(
-                    &maxp_table,
-                    &font_metrics.hhea,
-                    &hmtx_data,
-                    glyph_index,
-                )
-                .unwrap_or_default();
+        let font_metrics = get_font_metrics(font_bytes, font_index)?;
+        let color_glyphs = ColorGlyphTables::new(&provider, maxp_table.num_glyphs);
 
-                match glyph_record {
-                    GlyfRecord::Empty | GlyfRecord::Present { .. } => None,
-                    GlyfRecord::Parsed(g) => {
-                        Some((glyph_index, OwnedGlyph::from_glyph_data(g, horz_advance)))
-                    }
-                }
-            })
-            .collect::<Vec<_>>();
+        // the outlines are only decoded when they are needed, so fonts that
+        // ttf-parser can't read (e.g. WOFF2) don't have any outlines
+        let outline_source = OutlineSource::new(font_bytes, font_index);
+        let outline_face = ttf_parser::Face::parse(font_bytes, font_index as u32).ok();
+        let get_advance = |glyph_index: u16| {
+            allsorts::glyph_info::advance(&maxp_table, &font_metrics.hhea, &hmtx_data, glyph_index)
+                .unwrap_or_default()
+        };
 
-        let glyph_records_decoded = glyph_records_decoded.into_iter().collect();
+        let glyph_records_decoded =
+            match decode_glyf_table(&provider, &head_table, &maxp_table, &get_advance) {
+                Some(records) => records,
+                // CFF fonts and bitmap / SVG only fonts (emoji) have no `glyf` table
+                None => (0..maxp_table.num_glyphs)
+                    .filter_map(|glyph_index| {
+                        let bounding_box = outline_face
+                            .as_ref()
+                            .and_then(|face| {
+                                face.glyph_bounding_box(ttf_parser::GlyphId(glyph_index))
+                            })
+                            .map(|rect| OwnedGlyphBoundingBox {
+                                max_x: rect.x_max,
+                                max_y: rect.y_max,
+                                min_x: rect.x_min,
+                                min_y: rect.y_min,
+                            })
+                            .or_else(|| {
+                                get_color_glyph_bounding_box(
+                                    &color_glyphs,
+                                    &font_metrics,
+                                    glyph_index,
+                                    get_advance(glyph_index),
+                                )
+                            })?;
+                        Some((
+                            glyph_index,
+                            OwnedGlyph {
+                                bounding_box,
+                                horz_advance: get_advance(glyph_index),
+                                outline: None,
+                            },
+                        ))
+                    })
+                    .collect(),
+            };
 
         let mut font_data_impl = allsorts::font::Font::new(provider).ok()??;
 
-        // required for font layout: gsub_cache, gpos_cache and gdef_table
-        let gsub_cache = font_data_impl.gsub_cache().ok()??;
-        let gpos_cache = font_data_impl.gpos_cache().ok()??;
+        // used for font layout: gsub_cache, gpos_cache and gdef_table
+        // (emoji and icon fonts often don't have any layout tables)
+        let gsub_cache = font_data_impl.gsub_cache().ok()?;
+        let gpos_cache = font_data_impl.gpos_cache().ok()?;
         let opt_gdef_table = font_data_impl.gdef_table().ok().and_then(|o| o);
         let num_glyphs = font_data_impl.num_glyphs();
 
@@ -244,6 +595,27 @@ impl ParsedFont {
             .ok()?
             .to_owned()?;
 
+        let provider = &font_data_impl.font_table_provider;
+        let vertical_metrics = (|| {
+            let vhea_data = provider.table_data(tag::VHEA).ok()??;
+            let vhea = ReadScope::new(&vhea_data).read::<HheaTable>().ok()?;
+            let vmtx_data = provider
+                .table_data(tag::VMTX)
+                .ok()??
+                .into_owned()
+                .into_boxed_slice();
+            let vorg = provider
+                .table_data(tag::VORG)
+                .ok()
+                .flatten()
+                .and_then(|data| VorgTable::parse(&data));
+            Some(VerticalMetrics {
+                vhea,
+                vmtx_data,
+                vorg,
+            })
+        })();
+
         let mut font = ParsedFont {
             font_metrics,
             num_glyphs,
@@ -255,6 +627,10 @@ impl ParsedFont {
             cmap_subtable,
             glyph_records_decoded,
             space_width: None,
+            vertical_metrics,
+            color_glyphs,
+            outline_source,
+            synthesis: FontSynthesis::default(),
         };
 
         let space_width = font.get_space_width_internal();
@@ -281,23 +657,188 @@ impl ParsedFont {
         self.space_width
     }
 
+    /// Returns the horizontal advance, including the extra width of synthetic bold
+    /// (zero-width glyphs such as combining marks stay zero-width)
     pub fn get_horizontal_advance(&self, glyph_index: u16) -> u16 {
-        self.glyph_records_decoded
+        let advance = self
+            .glyph_records_decoded
             .get(&glyph_index)
             .map(|gi| gi.horz_advance)
-            .unwrap_or_default()
+            .unwrap_or_default();
+        if self.synthesis.bold && advance > 0 {
+            advance.saturating_add(self.get_synthetic_bold_strength().round() as u16)
+        } else {
+            advance
+        }
     }
 
     // get the x and y size of a glyph in unscaled units
     pub fn get_glyph_size(&self, glyph_index: u16) -> Option<(i32, i32)> {
-        let g = self.glyph_records_decoded.get(&glyph_index)?;
-        let glyph_width = g.bounding_box.max_x as i32 - g.bounding_box.min_x as i32; // width
-        let glyph_height = g.bounding_box.max_y as i32 - g.bounding_box.min_y as i32; // height
+        let b = self.get_glyph_bounding_box(glyph_index)?;
+        let glyph_width = b.max_x as i32 - b.min_x as i32; // width
+        let glyph_height = b.max_y as i32 - b.min_y as i32; // height
         Some((glyph_width, glyph_height))
     }
 
+    /// Returns the bounding box of the glyph (in unscaled units), including synthetic bold / oblique
+    pub fn get_glyph_bounding_box(&self, glyph_index: u16) -> Option<OwnedGlyphBoundingBox> {
+        let mut b = self
+            .glyph_records_decoded
+            .get(&glyph_index)?
+            .bounding_box
+            .clone();
+
+        if self.synthesis.bold {
+            let strength = self.get_synthetic_bold_strength();
+            let offset = (strength / 2.0).round() as i16;
+            b.max_x = b.max_x.saturating_add(strength.round() as i16);
+            b.min_y = b.min_y.saturating_sub(offset);
+            b.max_y = b.max_y.saturating_add(offset);
+        }
+
+        if self.synthesis.oblique {
+            let skew_x = self.get_synthetic_oblique_skew();
+            let skew = |x: i16, y: i16| (x as f32 + y as f32 * skew_x).round() as i16;
+            let (min_x, max_x) = (skew(b.min_x, b.min_y), skew(b.max_x, b.max_y));
+            b.min_x = min_x.min(skew(b.min_x, b.max_y));
+            b.max_x = max_x.max(skew(b.max_x, b.min_y));
+        }
+
+        Some(b)
+    }
+
+    /// Returns the outline of the glyph (in unscaled units), including synthetic bold / oblique
+    pub fn get_glyph_outline(&self, glyph_index: u16) -> Option<GlyphOutline> {
+        let glyph = self.glyph_records_decoded.get(&glyph_index)?;
+        let mut outline = match glyph.outline.clone() {
+            Some(s) => s,
+            None => self.outline_source.as_ref()?.get_outline(glyph_index)?,
+        };
+
+        if self.synthesis.bold {
+            outline.embolden(self.get_synthetic_bold_strength());
+        }
+        if self.synthesis.oblique {
+            outline.skew(self.get_synthetic_oblique_skew());
+        }
+
+        Some(outline)
+    }
+
+    /// Returns the stroke width (in unscaled units) that synthetic bold adds
+    pub fn get_synthetic_bold_strength(&self) -> f32 {
+        self.font_metrics.head.units_per_em as f32 * SYNTHETIC_BOLD_STRENGTH
+    }
+
+    /// Returns the horizontal shift per unit of height of synthetic oblique glyphs
+    pub fn get_synthetic_oblique_skew(&self) -> f32 {
+        SYNTHETIC_OBLIQUE_ANGLE.to_radians().tan()
+    }
+
+    /// Returns what kind of data the glyph is drawn from
+    /// (`None` for glyphs without any outline, e.g. the space glyph)
+    pub fn get_glyph_kind(&self, glyph_index: u16) -> Option<GlyphKind> {
+        self.color_glyphs.get_glyph_kind(glyph_index).or_else(|| {
+            let glyph = self.glyph_records_decoded.get(&glyph_index)?;
+            if glyph.outline.is_some() || self.outline_source.is_some() {
+                Some(GlyphKind::Outline)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the data to draw the glyph at the given size with: bitmaps are taken
+    /// from the strike closest to the font size, colors from the palette at
+    /// `palette_index` (falls back to the first palette). Vector data is unscaled.
+    pub fn get_glyph_data(
+        &self,
+        glyph_index: u16,
+        font_size_px: f32,
+        palette_index: usize,
+    ) -> Option<GlyphData> {
+        match self.get_glyph_kind(glyph_index)? {
+            GlyphKind::ColorPaint => self
+                .color_glyphs
+                .get_color_paint(glyph_index, palette_index)
+                .map(GlyphData::ColorPaint),
+            GlyphKind::ColorLayers => self
+                .color_glyphs
+                .get_color_layers(glyph_index, palette_index)
+                .map(GlyphData::ColorLayers),
+            GlyphKind::Svg => self.color_glyphs.get_svg(glyph_index).map(GlyphData::Svg),
+            GlyphKind::Bitmap => {
+                let pixels_per_em = font_size_px.ceil().clamp(1.0, u16::MAX as f32) as u16;
+                self.color_glyphs
+                    .get_bitmap(glyph_index, pixels_per_em)
+                    .map(GlyphData::Bitmap)
+            }
+            GlyphKind::Outline => self.get_glyph_outline(glyph_index).map(GlyphData::Outline),
+        }
+    }
+
+    /// Returns the advance of the glyph in vertical text (in unscaled units),
+    /// falls back to one em if the font has no vertical metrics
+    pub fn get_vertical_advance(&self, glyph_index: u16) -> u16 {
+        self.get_vertical_metric(glyph_index)
+            .map(|(advance, _)| advance)
+            .unwrap_or(self.font_metrics.head.units_per_em)
+    }
+
+    /// Returns the distance from the vertical origin (the top center of the glyph in
+    /// vertical text) to the baseline of the glyph (in unscaled units, POSITIVE = up)
+    ///
+    /// Uses the `VORG` table if present, otherwise the `vmtx` top side bearing,
+    /// otherwise the ascender of the font
+    pub fn get_vertical_origin_y(&self, glyph_index: u16) -> i16 {
+        if let Some(vorg) = self.vertical_metrics.as_ref().and_then(|v| v.vorg.as_ref()) {
+            return vorg
+                .vert_origin_y_metrics
+                .get(&glyph_index)
+                .copied()
+                .unwrap_or(vorg.default_vert_origin_y);
+        }
+
+        let max_y = self
+            .glyph_records_decoded
+            .get(&glyph_index)
+            .map(|g| g.bounding_box.max_y);
+        match (self.get_vertical_metric(glyph_index), max_y) {
+            (Some((_, top_side_bearing)), Some(max_y)) => max_y.saturating_add(top_side_bearing),
+            _ => self.font_metrics.get_ascender_unscaled(),
+        }
+    }
+
+    /// Returns the advance height and top side bearing from the `vmtx` table
+    fn get_vertical_metric(&self, glyph_index: u16) -> Option<(u16, i16)> {
+        let vertical_metrics = self.vertical_metrics.as_ref()?;
+        let num_glyphs = self.maxp_table.num_glyphs as usize;
+        let num_metrics = vertical_metrics.vhea.num_h_metrics as usize;
+        let vmtx = ReadScope::new(&vertical_metrics.vmtx_data)
+            .read_dep::<HmtxTable<'_>>((num_glyphs, num_metrics))
+            .ok()?;
+
+        let glyph_index = glyph_index as usize;
+        let last_metric = vmtx.h_metrics.get_item(num_metrics.checked_sub(1)?);
+        if glyph_index < num_metrics {
+            let metric = vmtx.h_metrics.get_item(glyph_index);
+            Some((metric.advance_width, metric.lsb))
+        } else if glyph_index < num_glyphs {
+            let top_side_bearing = vmtx.left_side_bearings.get_item(glyph_index - num_metrics);
+            Some((last_metric.advance_width, top_side_bearing))
+        } else {
+            None
+        }
+    }
+
     pub fn shape(&self, text: &[char]) -> ShapedTextBufferUnsized {
-        shape(self, text).unwrap_or_default()
+        shape(self, text, false, &CapsFeatures::default()).unwrap_or_default()
+    }
+
+    /// Same as `shape`, but for vertical text: applies the `vert` / `vrt2` features and
+    /// advances upright glyphs by their vertical advance (see `GlyphOrientation`)
+    pub fn shape_vertical(&self, text: &[char]) -> ShapedTextBufferUnsized {
+        shape(self, text, true, &CapsFeatures::default()).unwrap_or_default()
     }
 
     pub fn lookup_glyph_index(&self, c: u32) -> Option<u16> {
@@ -306,6 +847,106 @@ impl ParsedFont {
             _ => None,
         }
     }
+
+    /// Returns whether the `GSUB` table has the feature (e.g. `smcp`) for the default language
+    pub fn has_gsub_feature(&self, feature_tag: u32) -> bool {
+        (|| {
+            let gsub_table = &self.gsub_cache.as_ref()?.layout_table;
+            let script = gsub_table.find_script_or_default(SHAPING_SCRIPT).ok()??;
+            let langsys = script.find_langsys_or_default(None).ok()??;
+            gsub_table
+                .find_langsys_feature(langsys, feature_tag)
+                .ok()?
+                .map(|_| ())
+        })()
+        .is_some()
+    }
+}
+
+/// How the letters of one case are displayed as small caps
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CapsGlyphs {
+    /// Substituted by the OpenType feature with the tag
+    Feature(u32),
+    /// Replaced by scaled down capital letters (see `SYNTHETIC_SMALL_CAPS_SCALE`)
+    Synthesized,
+}
+
+/// OpenType features that implement a `font-variant-caps` with a font, looked up
+/// once per font and value (see `shape_words_with_options`)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct CapsFeatures {
+    lowercase: Option<CapsGlyphs>,
+    uppercase: Option<CapsGlyphs>,
+    /// Feature that applies to all glyphs (`titl`)
+    other: Option<u32>,
+}
+
+impl CapsFeatures {
+    pub(crate) fn new(font: &ParsedFont, font_variant_caps: StyleFontVariantCaps) -> Self {
+        use allsorts::{tag, tag::*};
+
+        // the first feature that the font has, synthesized small caps otherwise
+        let find_feature = |feature_tags: &[u32]| {
+            let feature_tag = feature_tags
+                .iter()
+                .copied()
+                .find(|t| font.has_gsub_feature(*t));
+            Some(feature_tag.map_or(CapsGlyphs::Synthesized, CapsGlyphs::Feature))
+        };
+
+        match font_variant_caps {
+            StyleFontVariantCaps::Normal => CapsFeatures::default(),
+            StyleFontVariantCaps::SmallCaps => CapsFeatures {
+                lowercase: find_feature(&[SMCP]),
+                ..Default::default()
+            },
+            StyleFontVariantCaps::AllSmallCaps => CapsFeatures {
+                lowercase: find_feature(&[SMCP]),
+                uppercase: find_feature(&[C2SC]),
+                other: None,
+            },
+            StyleFontVariantCaps::PetiteCaps => CapsFeatures {
+                lowercase: find_feature(&[tag!(b"pcap"), SMCP]),
+                ..Default::default()
+            },
+            StyleFontVariantCaps::AllPetiteCaps => CapsFeatures {
+                lowercase: find_feature(&[tag!(b"pcap"), SMCP]),
+                uppercase: find_feature(&[tag!(b"c2pc"), C2SC]),
+                other: None,
+            },
+            StyleFontVariantCaps::Unicase => CapsFeatures {
+                uppercase: find_feature(&[tag!(b"unic"), C2SC]),
+                ..Default::default()
+            },
+            StyleFontVariantCaps::TitlingCaps => CapsFeatures {
+                other: Some(tag!(b"titl")).filter(|t| font.has_gsub_feature(*t)),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns how the character is displayed, `None` if it isn't displayed as small caps
+    fn get_caps_glyphs(&self, c: char) -> Option<CapsGlyphs> {
+        if c.is_lowercase() {
+            self.lowercase
+        } else if c.is_uppercase() {
+            self.uppercase
+        } else {
+            None
+        }
+    }
+
+    fn get_feature_tags(&self) -> Vec<u32> {
+        let mut feature_tags = Vec::new();
+        for glyphs in [self.lowercase, self.uppercase] {
+            if let Some(CapsGlyphs::Feature(feature_tag)) = glyphs {
+                feature_tags.push(feature_tag);
+            }
+        }
+        feature_tags.extend(self.other);
+        feature_tags
+    }
 }
 
 #[derive(Debug, Default)]
@@ -323,10 +964,17 @@ impl ShapedTextBufferUnsized {
     }
 }
 
-fn shape(font: &ParsedFont, text: &[char]) -> Option<ShapedTextBufferUnsized> {
+/// Shapes the text with the `caps_features` and the vertical alternates for vertical text
+pub(crate) fn shape(
+    font: &ParsedFont,
+    text: &[char],
+    vertical: bool,
+    caps_features: &CapsFeatures,
+) -> Option<ShapedTextBufferUnsized> {
     use allsorts::gpos::apply as gpos_apply;
     use allsorts::gsub::apply as gsub_apply;
-    use allsorts::gsub::{FeatureMask, Features};
+    use allsorts::gsub::{FeatureInfo, FeatureMask, Features};
+    use allsorts::tag;
 
     // Map glyphs
     //
@@ -344,60 +992,168 @@ fn shape(font: &ParsedFont, text: &[char]) -> Option<ShapedTextBufferUnsized> {
                     .peek()
                     .and_then(|&&next| allsorts::unicode::VariationSelector::try_from(next).ok());
 
+                let caps_glyphs = caps_features.get_caps_glyphs(ch);
+                if caps_glyphs == Some(CapsGlyphs::Synthesized) {
+                    // synthetic small caps are the capital letters of the character, the
+                    // additional letters of an expansion ("ß" → "SS") are marked like the
+                    // additional glyphs of a multiple substitution
+                    for (i, capital) in ch.to_uppercase().enumerate() {
+                        let glyph_index = font.lookup_glyph_index(capital as u32).unwrap_or(0);
+                        let mut glyph = make_raw_glyph(ch, glyph_index, vs.filter(|_| i == 0));
+                        glyph.small_caps = true;
+                        glyph.multi_subst_dup = i > 0;
+                        glyph.fake_bold = font.synthesis.bold;
+                        glyph.fake_italic = font.synthesis.oblique;
+                        glyphs.push(glyph);
+                    }
+                    continue;
+                }
+
                 let glyph_index = font.lookup_glyph_index(ch as u32).unwrap_or(0);
-                glyphs.push(make_raw_glyph(ch, glyph_index, vs));
+                let mut glyph = make_raw_glyph(ch, glyph_index, vs);
+                glyph.small_caps = caps_glyphs.is_some();
+                glyph.fake_bold = font.synthesis.bold;
+                glyph.fake_italic = font.synthesis.oblique;
+                glyphs.push(glyph);
             }
         }
     }
 
-    const SCRIPT: u32 = allsorts::tag::LATN;
     let dotted_circle_index = font.lookup_glyph_index(DOTTED_CIRCLE as u32).unwrap_or(0);
 
+    // only the features that the text asks for are applied (no default features)
+    let mut feature_mask = FeatureMask::empty();
+    // vertical text uses the alternate glyphs for upright punctuation, brackets, etc.
+    if vertical {
+        feature_mask |= FeatureMask::VRT2_OR_VERT;
+    }
+
+    // the caps features are not part of the `FeatureMask`, so all features have to be listed
+    let caps_feature_tags = caps_features.get_feature_tags();
+    let gsub_features = if caps_feature_tags.is_empty() {
+        Features::Mask(feature_mask)
+    } else {
+        let vertical_feature_tag = if font.has_gsub_feature(tag::VRT2) {
+            tag::VRT2
+        } else {
+            tag::VERT
+        };
+        Features::Custom(
+            caps_feature_tags
+                .into_iter()
+                .chain(Some(vertical_feature_tag).filter(|_| vertical))
+                .map(|feature_tag| FeatureInfo {
+                    feature_tag,
+                    alternate: None,
+                })
+                .collect(),
+        )
+    };
+
     // Apply glyph substitution if table is present
-    gsub_apply(
-        dotted_circle_index,
-        &font.gsub_cache,
-        font.opt_gdef_table.as_ref().map(Rc::as_ref),
-        SCRIPT,
-        None,
-        &Features::Mask(FeatureMask::empty()),
-        font.num_glyphs,
-        &mut glyphs,
-    )
-    .ok()?;
+    if let Some(gsub_cache) = font.gsub_cache.as_ref() {
+        gsub_apply(
+            dotted_circle_index,
+            gsub_cache,
+            font.opt_gdef_table.as_ref().map(Rc::as_ref),
+            SHAPING_SCRIPT,
+            None,
+            &gsub_features,
+            font.num_glyphs,
+            &mut glyphs,
+        )
+        .ok()?;
+    }
 
     // Apply glyph positioning if table is present
 
-    let kerning = true;
+    // the horizontal kerning doesn't apply to glyphs stacked on top of each other
+    let kerning = !vertical;
     let mut infos = allsorts::gpos::Info::init_from_glyphs(
         font.opt_gdef_table.as_ref().map(Rc::as_ref),
         glyphs,
     );
 
-    gpos_apply(
-        &font.gpos_cache,
-        font.opt_gdef_table.as_ref().map(Rc::as_ref),
-        kerning,
-        &Features::Mask(FeatureMask::all()),
-        SCRIPT,
-        None,
-        &mut infos,
-    )
-    .ok()?;
+    if let Some(gpos_cache) = font.gpos_cache.as_ref() {
+        gpos_apply(
+            gpos_cache,
+            font.opt_gdef_table.as_ref().map(Rc::as_ref),
+            kerning,
+            &Features::Mask(FeatureMask::all()),
+            SHAPING_SCRIPT,
+            None,
+            &mut infos,
+        )
+        .ok()?;
+    }
 
     // calculate the horizontal advance for each char
     let infos = infos
         .into_iter()
-        .filter_map(|info| {
+        .filter_map(|mut info| {
             let glyph_index = info.glyph.glyph_index;
-            let adv_x = font.get_horizontal_advance(glyph_index);
+
+            // synthetic small caps are scaled down capital letters
+            let is_synthetic_small_caps = info.glyph.small_caps
+                && info
+                    .glyph
+                    .unicodes
+                    .first()
+                    .and_then(|c| caps_features.get_caps_glyphs(*c))
+                    == Some(CapsGlyphs::Synthesized);
+            let scale = if is_synthetic_small_caps {
+                SYNTHETIC_SMALL_CAPS_SCALE
+            } else {
+                1.0
+            };
+            let scale_units = |units: f32| (units * scale).round();
+            info.kerning = scale_units(info.kerning as f32) as i16;
+
+            let adv_x = scale_units(font.get_horizontal_advance(glyph_index) as f32) as u16;
             let (size_x, size_y) = font.get_glyph_size(glyph_index)?;
+            let (size_x, size_y) = (
+                scale_units(size_x as f32) as i32,
+                scale_units(size_y as f32) as i32,
+            );
+
+            let is_upright = vertical
+                && (info.glyph.is_vert_alt
+                    || info
+                        .glyph
+                        .unicodes
+                        .first()
+                        .is_some_and(|c| is_upright_in_vertical_text(*c)));
+
+            // the advance is always the advance along the line
+            let (advance_x, orientation) = if is_upright {
+                (
+                    scale_units(font.get_vertical_advance(glyph_index) as f32) as u16,
+                    GlyphOrientation::Upright {
+                        horizontal_advance: adv_x,
+                        vertical_origin_y: scale_units(
+                            font.get_vertical_origin_y(glyph_index) as f32
+                        ) as i16,
+                    },
+                )
+            } else if vertical {
+                (adv_x, GlyphOrientation::Sideways)
+            } else {
+                (adv_x, GlyphOrientation::Horizontal)
+            };
+
             let advance = Advance {
-                advance_x: adv_x,
+                advance_x,
                 size_x,
                 size_y,
             };
-            Some(GlyphInfo { info, advance })
+            Some(GlyphInfo {
+                info,
+                advance,
+                orientation,
+                // set after shaping, see `ShapedWord::clusters`
+                cluster: 0,
+                scale,
+            })
         })
         .collect();
 
@@ -423,3 +1179,106 @@ fn make_raw_glyph(
         variation,
     }
 }
+
+#[test]
+fn test_synthetic_bold_and_oblique() {
+    use crate::test_font::{TestFont, TestGlyph};
+
+    // glyph 1 is a box from (100, -200) to (400, 700), glyph 3 a zero-width mark
+    let mut test_font = TestFont::default();
+    test_font
+        .glyphs
+        .push((0, TestGlyph::rect(-200, 500, -100, 600)));
+    let mut font = ParsedFont::from_bytes(&test_font.to_bytes(), 0).unwrap();
+    let bounds = |font: &ParsedFont, glyph_index: u16| {
+        font.get_glyph_bounding_box(glyph_index)
+            .map(|b| (b.min_x, b.min_y, b.max_x, b.max_y))
+    };
+    let points = |outline: Option<GlyphOutline>| {
+        let mut operations = outline.unwrap().operations;
+        let mut points = Vec::new();
+        for_each_outline_point_mut(&mut operations, |x, y| {
+            points.push(((*x * 100.0).round() / 100.0, (*y * 100.0).round() / 100.0))
+        });
+        points
+    };
+    // the outline is closed with a line back to the first point
+    let rect = [
+        (100.0, -200.0),
+        (100.0, 700.0),
+        (400.0, 700.0),
+        (400.0, -200.0),
+        (100.0, -200.0),
+    ];
+
+    assert_eq!(font.get_horizontal_advance(1), 500);
+    assert_eq!(bounds(&font, 1), Some((100, -200, 400, 700)));
+    assert_eq!(points(font.get_glyph_outline(1)), rect.to_vec());
+
+    // synthetic bold: the stroke gets 1/24 em (41.67 units) wider, half of it on each
+    // side, the outline is moved right so that the left side bearing stays the same
+    font.synthesis = FontSynthesis {
+        bold: true,
+        oblique: false,
+    };
+    assert_eq!(font.get_horizontal_advance(1), 542);
+    assert_eq!(font.get_horizontal_advance(3), 0);
+    assert_eq!(bounds(&font, 1), Some((100, -221, 442, 721)));
+    assert_eq!(
+        points(font.get_glyph_outline(1)),
+        vec![
+            (100.0, -220.83),
+            (100.0, 720.83),
+            (441.67, 720.83),
+            (441.67, -220.83),
+            (100.0, -220.83)
+        ]
+    );
+
+    // synthetic oblique: slanted by 14° (x' = x + y * tan 14°), the advance stays the same
+    font.synthesis = FontSynthesis {
+        bold: false,
+        oblique: true,
+    };
+    assert_eq!(font.get_horizontal_advance(1), 500);
+    assert_eq!(bounds(&font, 1), Some((50, -200, 575, 700)));
+    assert_eq!(
+        points(font.get_glyph_outline(1)),
+        vec![
+            (50.13, -200.0),
+            (274.53, 700.0),
+            (574.53, 700.0),
+            (350.13, -200.0),
+            (50.13, -200.0)
+        ]
+    );
+
+    // both: the outline is emboldened before it is slanted
+    font.synthesis = FontSynthesis {
+        bold: true,
+        oblique: true,
+    };
+    let mut expected = GlyphOutline {
+        operations: vec![
+            GlyphOutlineOperation::MoveTo(OutlineMoveTo {
+                x: 100.0,
+                y: -200.0,
+            }),
+            GlyphOutlineOperation::LineTo(OutlineLineTo { x: 100.0, y: 700.0 }),
+            GlyphOutlineOperation::LineTo(OutlineLineTo { x: 400.0, y: 700.0 }),
+            GlyphOutlineOperation::LineTo(OutlineLineTo {
+                x: 400.0,
+                y: -200.0,
+            }),
+            GlyphOutlineOperation::LineTo(OutlineLineTo {
+                x: 100.0,
+                y: -200.0,
+            }),
+            GlyphOutlineOperation::ClosePath,
+        ],
+    };
+    expected.embolden(font.get_synthetic_bold_strength());
+    expected.skew(font.get_synthetic_oblique_skew());
+    assert_eq!(points(font.get_glyph_outline(1)), points(Some(expected)));
+    assert_eq!(font.get_horizontal_advance(1), 542);
+}
@@ -0,0 +1,420 @@
+//! Case and width conversion of the text before it is shaped (`text-transform`)
+
+use crate::{
+    css::StyleTextTransform,
+    words::{TextOffsetChange, TextOffsetMap, Token, Word, Words},
+};
+use core::ops::Range;
+use unicode_normalization::{
+    char::{canonical_combining_class, decompose_canonical},
+    UnicodeNormalization,
+};
+
+/// Full-width space (U+3000), which `text-transform: full-width` replaces the spaces with
+pub(crate) const IDEOGRAPHIC_SPACE: char = '\u{3000}';
+
+/// Half-width katakana (semi-)voiced sound marks, which combine with the preceding katakana
+const HALF_WIDTH_SOUND_MARKS: [char; 2] = ['\u{FF9E}', '\u{FF9F}'];
+
+/// Full-width forms of the half-width katakana U+FF61 - U+FF9F
+const FULL_WIDTH_KATAKANA: [char; 63] = [
+    '。', '「', '」', '、', '・', 'ヲ', 'ァ', 'ィ', 'ゥ', 'ェ', 'ォ', 'ャ', 'ュ', 'ョ', 'ッ', 'ー',
+    'ア', 'イ', 'ウ', 'エ', 'オ', 'カ', 'キ', 'ク', 'ケ', 'コ', 'サ', 'シ', 'ス', 'セ', 'ソ', 'タ',
+    'チ', 'ツ', 'テ', 'ト', 'ナ', 'ニ', 'ヌ', 'ネ', 'ノ', 'ハ', 'ヒ', 'フ', 'ヘ', 'ホ', 'マ', 'ミ',
+    'ム', 'メ', 'モ', 'ヤ', 'ユ', 'ヨ', 'ラ', 'リ', 'ル', 'レ', 'ロ', 'ワ', 'ン', '\u{3099}',
+    '\u{309A}',
+];
+
+/// Languages whose case mappings differ from the default Unicode case mappings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Language {
+    /// Turkish and Azeri: "i" ↔ "İ" and "ı" ↔ "I"
+    Turkic,
+    /// Accents and breathing marks are removed in upper case
+    Greek,
+    /// "ij" is capitalized as "IJ"
+    Dutch,
+    Other,
+}
+
+impl Language {
+    fn from_tag(language_tag: &str) -> Self {
+        let primary_tag = language_tag.split(['-', '_']).next().unwrap_or_default();
+        match primary_tag.to_ascii_lowercase().as_str() {
+            "tr" | "az" => Language::Turkic,
+            "el" => Language::Greek,
+            "nl" => Language::Dutch,
+            _ => Language::Other,
+        }
+    }
+}
+
+/// Applies the `text_transform` to all `Token::Word`s (tabs and returns are not changed,
+/// spaces only become ideographic spaces with `full-width`), the resulting words have
+/// to be shaped again.
+///
+/// `language` is the BCP 47 language tag of the text (e.g. "tr" or "el-GR"), which
+/// selects the language-specific case mappings of Turkish and Azeri (dotted and dotless i),
+/// Greek (no accents in upper case) and Dutch ("ij" is capitalized as "IJ").
+///
+/// The `Word::index` ranges of the result point into the transformed text, its
+/// `Words::offset_map` still maps them back to the original (untransformed) text.
+pub fn apply_text_transform(
+    words: &Words,
+    text_transform: StyleTextTransform,
+    language: &str,
+) -> Words {
+    if text_transform == StyleTextTransform::None {
+        return words.clone();
+    }
+
+    let language = Language::from_tag(language);
+    let text = words.internal_str.as_str();
+    let mut replacements = Vec::<(Range<usize>, String)>::new();
+
+    for word in words.items.iter() {
+        let word_str = &text[word.index.clone()];
+        if word.word_type == Token::Space && text_transform == StyleTextTransform::FullWidth {
+            for (i, _) in word_str.match_indices(' ') {
+                let start = word.index.start + i;
+                replacements.push((start..start + 1, IDEOGRAPHIC_SPACE.to_string()));
+            }
+        }
+        if word.word_type != Token::Word {
+            continue;
+        }
+
+        let letter_units = get_letter_units(word_str)
+            .into_iter()
+            .map(|unit| &word_str[unit])
+            .collect::<Vec<_>>();
+        let mut capitalized_unit = None;
+        let mut unit_start = word.index.start;
+
+        for (unit_index, unit) in letter_units.iter().copied().enumerate() {
+            let previous = unit_index
+                .checked_sub(1)
+                .and_then(|i| letter_units.get(i).copied());
+            let next = letter_units.get(unit_index + 1).copied();
+
+            let replacement = match text_transform {
+                StyleTextTransform::None => None,
+                StyleTextTransform::Uppercase if language == Language::Greek => {
+                    Some(to_greek_uppercase(unit, previous, letter_units.len() == 1))
+                }
+                StyleTextTransform::Uppercase => Some(to_uppercase(unit, language)),
+                StyleTextTransform::Lowercase => Some(to_lowercase(unit, previous, next, language)),
+                StyleTextTransform::Capitalize => match capitalized_unit {
+                    // the Dutch "ij" is capitalized as a single letter
+                    Some(capitalized)
+                        if language == Language::Dutch
+                            && capitalized + 1 == unit_index
+                            && matches!(previous, Some("i" | "I"))
+                            && unit == "j" =>
+                    {
+                        Some("J".to_string())
+                    }
+                    Some(_) => None,
+                    None if unit.starts_with(char::is_alphabetic) => {
+                        capitalized_unit = Some(unit_index);
+                        Some(to_titlecase(unit, language))
+                    }
+                    None => None,
+                },
+                StyleTextTransform::FullWidth => Some(to_full_width(unit)),
+            };
+
+            let unit_range = unit_start..unit_start + unit.len();
+            unit_start = unit_range.end;
+            if let Some(replacement) = replacement.filter(|r| r != unit) {
+                replacements.push((unit_range, replacement));
+            }
+        }
+    }
+
+    // build the transformed text, every letter is a separate change so
+    // that offsets inside of a word can still be mapped to the original text
+    let mut transformed = String::with_capacity(text.len());
+    let mut changes = Vec::<TextOffsetChange>::new();
+    let mut copied_until = 0;
+
+    for (original, replacement) in replacements {
+        transformed.push_str(&text[copied_until..original.start]);
+        let transformed_start = transformed.len();
+        transformed.push_str(&replacement);
+        copied_until = original.end;
+        changes.push(TextOffsetChange {
+            original,
+            normalized: transformed_start..transformed.len(),
+        });
+    }
+    transformed.push_str(&text[copied_until..]);
+
+    let transform_map = TextOffsetMap { changes };
+
+    Words {
+        items: words
+            .items
+            .iter()
+            .map(|word| Word {
+                index: transform_map.range_to_normalized(word.index.clone()),
+                word_type: word.word_type,
+            })
+            .collect(),
+        internal_str: transformed,
+        offset_map: words.offset_map.chain(&transform_map),
+    }
+}
+
+/// Splits the word into letters with their combining marks, which are transformed together
+fn get_letter_units(word: &str) -> Vec<Range<usize>> {
+    let mut units = Vec::<Range<usize>>::new();
+    for (i, c) in word.char_indices() {
+        let is_combining = canonical_combining_class(c) != 0 || HALF_WIDTH_SOUND_MARKS.contains(&c);
+        match units.last_mut() {
+            Some(last) if is_combining => last.end = i + c.len_utf8(),
+            _ => units.push(i..i + c.len_utf8()),
+        }
+    }
+    units
+}
+
+fn to_uppercase(unit: &str, language: Language) -> String {
+    let mut uppercase = String::with_capacity(unit.len());
+    for c in unit.chars() {
+        match (c, language) {
+            ('i', Language::Turkic) => uppercase.push('İ'),
+            _ => uppercase.extend(c.to_uppercase()),
+        }
+    }
+    uppercase
+}
+
+fn to_lowercase(
+    unit: &str,
+    previous: Option<&str>,
+    next: Option<&str>,
+    language: Language,
+) -> String {
+    let mut chars = unit.chars();
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return String::new(),
+    };
+    let rest = chars.as_str();
+
+    match (first, language) {
+        ('İ', Language::Turkic) => return format!("i{}", rest.to_lowercase()),
+        // "I" + combining dot above is the decomposed "İ"
+        ('I', Language::Turkic) if rest.starts_with('\u{307}') => {
+            return format!("i{}", rest['\u{307}'.len_utf8()..].to_lowercase());
+        }
+        ('I', Language::Turkic) => return format!("ı{}", rest.to_lowercase()),
+        _ => {}
+    }
+
+    // a capital sigma at the end of a word becomes a final sigma
+    let is_final_sigma = first == 'Σ'
+        && previous.is_some_and(|p| p.starts_with(char::is_alphabetic))
+        && !next.is_some_and(|n| n.starts_with(char::is_alphabetic));
+    if is_final_sigma {
+        return format!("ς{}", rest.to_lowercase());
+    }
+
+    unit.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// Converts the first character of the letter to title case, the combining marks are not changed
+fn to_titlecase(unit: &str, language: Language) -> String {
+    let mut chars = unit.chars();
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return String::new(),
+    };
+    let marks = chars.as_str();
+
+    let titlecase = match (first, language) {
+        ('i', Language::Turkic) => "İ".to_string(),
+        // digraphs that have a separate title case form
+        ('Ǆ' | 'ǅ' | 'ǆ', _) => "ǅ".to_string(),
+        ('Ǉ' | 'ǈ' | 'ǉ', _) => "ǈ".to_string(),
+        ('Ǌ' | 'ǋ' | 'ǌ', _) => "ǋ".to_string(),
+        ('Ǳ' | 'ǲ' | 'ǳ', _) => "ǲ".to_string(),
+        // only the first letter of an expansion is in upper case ("ß" → "Ss", "ﬁ" → "Fi")
+        _ => {
+            let mut uppercase = first.to_uppercase();
+            uppercase
+                .next()
+                .into_iter()
+                .chain(uppercase.flat_map(char::to_lowercase))
+                .collect()
+        }
+    };
+
+    titlecase + marks
+}
+
+/// Upper case for Greek text: accents and breathing marks are removed, the diaeresis is kept
+fn to_greek_uppercase(unit: &str, previous: Option<&str>, is_single_letter_word: bool) -> String {
+    // the disjunctive eta ("ή" = "or") keeps its accent
+    if is_single_letter_word && matches!(unit, "\u{3AE}" | "\u{1F75}") {
+        return "\u{389}".to_string();
+    }
+
+    let (base, marks) = match decompose_greek_letter(unit) {
+        Some(letter) => letter,
+        None => return to_uppercase(unit, Language::Greek),
+    };
+
+    // an accent on the first of two vowels means that they don't form a diphthong,
+    // which is marked with a diaeresis on the second vowel once the accent is removed
+    let needs_diaeresis = marks.is_empty()
+        && previous.and_then(decompose_greek_letter).is_some_and(
+            |(previous_base, previous_marks)| {
+                previous_marks.iter().any(|m| is_greek_accent(*m))
+                    && !previous_marks.contains(&'\u{308}')
+                    && matches!(
+                        (to_lower(previous_base), to_lower(base)),
+                        ('α' | 'ε' | 'η' | 'ο' | 'υ', 'ι') | ('α' | 'ε' | 'η' | 'ο', 'υ')
+                    )
+            },
+        );
+
+    let mut uppercase = base.to_uppercase().collect::<String>();
+    uppercase.extend(
+        marks
+            .into_iter()
+            .filter(|m| !is_greek_accent(*m))
+            .flat_map(char::to_uppercase),
+    );
+    if needs_diaeresis {
+        uppercase.push('\u{308}');
+    }
+
+    uppercase.nfc().collect()
+}
+
+/// Decomposes a Greek letter into its base letter and combining marks,
+/// `None` if the letter isn't Greek
+fn decompose_greek_letter(unit: &str) -> Option<(char, Vec<char>)> {
+    let mut decomposed = Vec::new();
+    for c in unit.chars() {
+        decompose_canonical(c, |d| decomposed.push(d));
+    }
+
+    let base = *decomposed.first()?;
+    let is_greek =
+        ('\u{370}'..='\u{3FF}').contains(&base) || ('\u{1F00}'..='\u{1FFF}').contains(&base);
+    if !is_greek {
+        return None;
+    }
+
+    decomposed.remove(0);
+    Some((base, decomposed))
+}
+
+/// Accents, breathing marks and length marks that are not written in Greek upper case
+fn is_greek_accent(mark: char) -> bool {
+    matches!(
+        mark,
+        '\u{300}' | '\u{301}' | '\u{304}' | '\u{306}' | '\u{313}' | '\u{314}' | '\u{342}'
+    )
+}
+
+fn to_lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn to_full_width(unit: &str) -> String {
+    let full_width = unit
+        .chars()
+        .map(|c| match c {
+            '!'..='~' => char::from_u32(c as u32 + 0xFEE0).unwrap_or(c),
+            '\u{FF61}'..='\u{FF9F}' => FULL_WIDTH_KATAKANA[(c as u32 - 0xFF61) as usize],
+            '¢' => '￠',
+            '£' => '￡',
+            '¬' => '￢',
+            '¯' => '￣',
+            '¦' => '￤',
+            '¥' => '￥',
+            '₩' => '￦',
+            _ => c,
+        })
+        .collect::<String>();
+
+    // the half-width sound marks are composed with the preceding katakana ("ｶﾞ" → "ガ")
+    if unit.contains(HALF_WIDTH_SOUND_MARKS) {
+        full_width.nfc().collect()
+    } else {
+        full_width
+    }
+}
+
+#[test]
+fn test_apply_text_transform() {
+    use crate::{
+        css::StyleWhiteSpace,
+        test_font::TestFont,
+        text_layout::{position_words, shape_words, split_text_into_words},
+        text_shaping::ParsedFont,
+        ui_solver::ResolvedTextLayoutOptions,
+        words::TextNormalization,
+    };
+
+    fn transform(text: &str, text_transform: StyleTextTransform, language: &str) -> String {
+        let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+        apply_text_transform(&words, text_transform, language).internal_str
+    }
+
+    use StyleTextTransform::*;
+    assert_eq!(transform("straße ist", Uppercase, "de"), "STRASSE IST");
+    assert_eq!(transform("istanbul ılık", Uppercase, "tr"), "İSTANBUL ILIK");
+    assert_eq!(
+        transform("İSTANBUL IRMAK", Lowercase, "tr-TR"),
+        "istanbul ırmak"
+    );
+    assert_eq!(transform("ΟΔΟΣ ΣΑΣ", Lowercase, "el"), "οδος σας");
+    assert_eq!(
+        transform("άδικος, κείμενο ή ἀάι", Uppercase, "el"),
+        "ΑΔΙΚΟΣ, ΚΕΙΜΕΝΟ Ή ΑΑΪ"
+    );
+    assert_eq!(transform("άδικος", Uppercase, "en"), "ΆΔΙΚΟΣ");
+    assert_eq!(
+        transform("(hello) ßo ǆungla", Capitalize, "en"),
+        "(Hello) Sso ǅungla"
+    );
+    assert_eq!(
+        transform("ijsselmeer iris", Capitalize, "nl"),
+        "IJsselmeer Iris"
+    );
+    // spaces become ideographic spaces, tabs are kept
+    assert_eq!(transform("a1! ｶﾞｷ", FullWidth, ""), "ａ１！\u{3000}ガキ");
+    assert_eq!(transform("a \tb", FullWidth, ""), "ａ\u{3000}\tｂ");
+
+    // ideographic spaces are one em wide (the space of the test font is half an em)
+    let font = ParsedFont::from_bytes(&TestFont::default().to_bytes(), 0).unwrap();
+    let words = split_text_into_words("a b", StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let words = apply_text_transform(&words, FullWidth, "");
+    let options = ResolvedTextLayoutOptions {
+        font_size_px: 10.0,
+        ..Default::default()
+    };
+    let word_positions = position_words(&words, &shape_words(&words, &font), &options);
+    assert_eq!(word_positions.word_positions[2].position.x, 15.0);
+
+    // the transformed words can be mapped back to the original (decomposed) text
+    let text = "stra\u{DF}e e\u{301}t\u{E9}";
+    let words = split_text_into_words(text, StyleWhiteSpace::Normal, TextNormalization::Nfc);
+    let words = apply_text_transform(&words, Uppercase, "");
+    assert_eq!(words.internal_str, "STRASSE \u{C9}T\u{C9}");
+    let original_words = words
+        .items
+        .iter()
+        .filter(|w| w.word_type == Token::Word)
+        .map(|w| &text[words.offset_map.range_to_original(w.index.clone())])
+        .collect::<Vec<_>>();
+    assert_eq!(original_words, vec!["stra\u{DF}e", "e\u{301}t\u{E9}"]);
+    assert_eq!(words.offset_map.to_original(5), 4); // second "S" of "SS"
+    assert_eq!(words.offset_map.to_original(6), 6); // "E" after "SS"
+    assert_eq!(words.offset_map.to_normalized(8), 8); // "é" → "É"
+}
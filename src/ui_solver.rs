@@ -1,12 +1,15 @@
-use std::ops::RangeInclusive;
+use std::ops::{Range, RangeInclusive};
 
 use crate::{
-    css::{StyleTextAlign, StyleVerticalAlign},
-    logical::{LogicalRect, LogicalSize},
+    css::{
+        StyleColumnFill, StyleFontVariantCaps, StyleHangingPunctuation, StyleInlineVerticalAlign,
+        StyleLineHeight, StyleOverflowWrap, StyleTextAlign, StyleTextSpacing, StyleVerticalAlign,
+        StyleWhiteSpace, StyleWordBreak, StyleWritingMode,
+    },
+    logical::{LogicalPosition, LogicalRect, LogicalSize},
 };
 
-pub const DEFAULT_LINE_HEIGHT: f32 = 1.0;
-pub const DEFAULT_WORD_SPACING: f32 = 1.0;
+pub const DEFAULT_TAB_WIDTH: f32 = 8.0;
 
 #[derive(Debug, Clone, PartialEq)]
 #[repr(C)]
@@ -15,13 +18,34 @@ pub struct InlineTextLayout {
     pub content_size: LogicalSize,
 }
 
+/// Intrinsic widths of a text, necessary to size the text box in flex / grid layouts
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+#[repr(C)]
+pub struct IntrinsicSizes {
+    /// Width of the text if it is broken at every possible line break
+    /// (usually the width of the widest word)
+    pub min_content_width: f32,
+    /// Width of the text if it is only broken at forced line breaks
+    pub max_content_width: f32,
+}
+
 /// NOTE: The bounds of the text line is the TOP left corner (relative to the text origin),
-/// but the word_position is the BOTTOM left corner (relative to the text line)
+/// the word positions are relative to the start of the line (x) and the text origin (y)
 #[derive(Debug, Clone, PartialEq)]
 #[repr(C)]
 pub struct InlineTextLine {
     pub bounds: LogicalRect,
     pub words: RangeInclusive<usize>,
+    /// Distance from the top of the line box to the baseline (in pixels)
+    pub baseline: f32,
+    /// Distance from the baseline to the top of the tallest inline box on the line
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the lowest inline box on the line (POSITIVE)
+    pub descent: f32,
+    /// Set if the end of the line has been cut off (`text-overflow` / line clamping)
+    pub truncation: Option<LineTruncation>,
+    /// Parts of the words on this line that are broken across multiple lines
+    pub fragments: Vec<WordFragment>,
 }
 
 impl InlineTextLine {
@@ -29,10 +53,60 @@ impl InlineTextLine {
         Self {
             bounds,
             words: word_start..=word_end,
+            baseline: bounds.size.height,
+            ascent: bounds.size.height,
+            descent: 0.0,
+            truncation: None,
+            fragments: Vec::new(),
         }
     }
 }
 
+/// Describes where a line has been cut off to make room for the text overflow marker
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct LineTruncation {
+    /// Index of the last (partially) visible word on the line
+    pub word_index: usize,
+    /// Glyphs of the last word before this index are still visible
+    /// (if the last word is an inline object: 1 if it is visible, 0 if it is hidden)
+    pub glyph_end: usize,
+    /// Byte offset (into `Words::internal_str`) of the first hidden character
+    pub byte_offset: usize,
+    /// Position of the text overflow marker (TOP left corner, same as the word positions)
+    pub marker_position: LogicalPosition,
+}
+
+/// Lines of the text that are laid out into one of the `ResolvedTextLayoutOptions::containers`
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct TextContainerFragment {
+    /// Index into `ResolvedTextLayoutOptions::containers` (or the index of the column)
+    pub container_index: usize,
+    /// Position and size of the container, relative to the origin that the lines
+    /// are positioned relative to (the top left corner of the container, except
+    /// for columns, which are positioned next to each other)
+    pub bounds: LogicalRect,
+    /// Indices of the lines in this container
+    pub lines: Range<usize>,
+    /// Indices of the words on these lines, a word that is broken across
+    /// containers is part of both containers
+    pub words: Range<usize>,
+}
+
+/// Part of a word that is broken across lines (`overflow-wrap` / `word-break`)
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct WordFragment {
+    /// Index of the broken word
+    pub word_index: usize,
+    /// Glyphs of the shaped word that are on this line
+    pub glyphs: Range<usize>,
+    /// Position of the fragment (TOP left corner, same as the word positions)
+    pub position: LogicalPosition,
+    pub size: LogicalSize,
+}
+
 impl InlineTextLayout {
     #[inline]
     pub fn get_leading(&self) -> f32 {
@@ -51,6 +125,9 @@ impl InlineTextLayout {
     }
 
     /// Align the lines horizontal to *their bounding box*
+    ///
+    /// Hanging punctuation and the optical margin protrusion are outside of the bounding
+    /// box of the line (the words start before / end after it), so they hang over the edge.
     pub fn align_children_horizontal(
         &mut self,
         parent_size: &LogicalSize,
@@ -77,7 +154,11 @@ impl InlineTextLayout {
             Some(s) => s,
         };
 
-        let glyphs_vertical_bottom = self.lines.last().map(|l| l.bounds.origin.y).unwrap_or(0.0);
+        let glyphs_vertical_bottom = self
+            .lines
+            .last()
+            .map(|l| l.bounds.origin.y + l.bounds.size.height)
+            .unwrap_or(0.0);
         let vertical_shift = (parent_size.height - glyphs_vertical_bottom) * shift_multiplier;
 
         for line in self.lines.iter_mut() {
@@ -106,22 +187,206 @@ fn calculate_vertical_shift_multiplier(vertical_alignment: StyleVerticalAlign) -
     }
 }
 
-/// Same as `TextLayoutOptions`, but with the widths / heights of the `PixelValue`s
-/// resolved to regular f32s (because `letter_spacing`, `word_spacing`, etc. may be %-based value)
-#[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
+/// Same as `TextLayoutOptions`, but with the widths / heights of the `PixelValue`s resolved
+/// to regular f32s (except for `letter_spacing` and `word_spacing`, which depend on the
+/// font size of each span)
+#[derive(Debug, Clone, PartialEq)]
 #[repr(C)]
 pub struct ResolvedTextLayoutOptions {
     /// Font size (in pixels) that this text has been laid out with
     pub font_size_px: f32,
-    /// Multiplier for the line height, default to 1.0
-    pub line_height: Option<f32>,
-    /// Additional spacing between glyphs (in pixels)
-    pub letter_spacing: Option<f32>,
-    /// Additional spacing between words (in pixels)
-    pub word_spacing: Option<f32>,
+    /// Height of a line box, the extra space (leading) is split evenly above and below the glyphs
+    pub line_height: StyleLineHeight,
+    /// Additional spacing after every letter (`letter-spacing`), except for the last letter
+    /// on a line (marks are not spaced apart from their base letter)
+    pub letter_spacing: Option<StyleTextSpacing>,
+    /// Additional spacing after every space (`word-spacing`)
+    pub word_spacing: Option<StyleTextSpacing>,
+    /// Capital letter glyphs (`font-variant-caps`) that the text is shaped with
+    /// by `shape_words_with_options`
+    pub font_variant_caps: StyleFontVariantCaps,
+    /// Width of a tab stop (in multiples of the space advance), defaults to 8.0
+    pub tab_width: Option<f32>,
     /// Maximum width of the text (in pixels) - if the text is set to `overflow:visible`, set this to None.
+    ///
+    /// NOTE: In vertical writing modes, this is the maximum length of a line (i.e. the HEIGHT
+    /// of the text), all sizes and positions of the layout are along the line (x) and
+    /// across the lines (y) until the glyphs are rotated in `InlineText::get_layouted_glyphs`
     pub max_horizontal_width: Option<f32>,
+    /// Whether words that don't fit onto a line on their own may be broken
+    pub overflow_wrap: StyleOverflowWrap,
+    /// Where lines may be broken inside of words
+    pub word_break: StyleWordBreak,
+    /// Punctuation at the start and end of the lines that is placed outside of the line box
+    pub hanging_punctuation: StyleHangingPunctuation,
+    /// Whether quotes, hyphens, periods, etc. at the start and end of the lines protrude
+    /// slightly past the edges of the line, so that the margins look straight
+    pub optical_margin_alignment: bool,
+    /// Maximum number of lines, the remaining text is cut off (`line-clamp`)
+    pub max_lines: Option<usize>,
+    /// Maximum height of the text (in pixels), lines that would exceed it are cut off
+    /// (the maximum width in vertical writing modes). Ignored if the text flows through
+    /// `containers`, the height of the columns if the text is laid out in columns.
+    pub max_vertical_height: Option<f32>,
     /// How many pixels of leading does the first line have? Note that this added onto to the holes,
     /// so for effects like `:first-letter`, use a hole instead of a leading.
     pub leading: Option<f32>,
+    /// How spaces and newlines are collapsed and whether lines are wrapped,
+    /// must be the same mode that the text was split with
+    pub white_space: StyleWhiteSpace,
+    /// Parts of the text with a different font size or vertical alignment,
+    /// if spans overlap, the last one wins. A span may start or end inside of a word
+    /// (i.e. "H<sub>2</sub>O"), the parts of the word are shaped and measured separately.
+    pub spans: Vec<InlineSpan>,
+    /// Sizes of the inline objects (images, widgets, ...) in the text, in the order of
+    /// their `OBJECT_REPLACEMENT_CHARACTER`s, see `Token::InlineObject`
+    pub inline_objects: Vec<InlineObject>,
+    /// Whether the lines are horizontal or vertical, vertical text
+    /// has to be shaped with `shape_words_vertical`
+    ///
+    /// NOTE: vertical text is laid out exactly like horizontal text, all sizes and
+    /// positions of the layout (`max_horizontal_width`, `WordPositions`, `InlineText`)
+    /// are in line coordinates: x runs along the line, y across the lines. The lines
+    /// are only rotated into place by `InlineText::get_layouted_glyphs` and
+    /// `InlineText::get_physical_rect`.
+    pub writing_mode: StyleWritingMode,
+    /// Boxes (pages, columns, linked text frames) that the text flows through: each box
+    /// is filled with lines until its height is exhausted, then the text continues at the
+    /// top of the next box. If empty, the text is laid out into a single box that is
+    /// `max_horizontal_width` wide and has no height limit.
+    pub containers: Vec<LogicalSize>,
+    /// Minimum number of lines of a paragraph that are moved to the top of the
+    /// next container, if the paragraph is split across containers (CSS default: 2)
+    pub widows: usize,
+    /// Minimum number of lines of a paragraph that are left at the bottom of a
+    /// container, if the paragraph is split across containers (CSS default: 2)
+    pub orphans: usize,
+    /// Number of columns (`column-count`) that the `max_horizontal_width` is split into,
+    /// `None` = as many columns of the `column_width` as fit
+    pub column_count: Option<usize>,
+    /// Minimum width of the columns (`column-width`, in pixels), `None` = the width
+    /// is determined by the `column_count`. Columns are ignored if both are `None`
+    /// or if the text flows through `containers`.
+    pub column_width: Option<f32>,
+    /// Space between the columns (`column-gap`, in pixels)
+    pub column_gap: f32,
+    pub column_fill: StyleColumnFill,
+}
+
+impl Default for ResolvedTextLayoutOptions {
+    fn default() -> Self {
+        Self {
+            font_size_px: 0.0,
+            line_height: StyleLineHeight::default(),
+            letter_spacing: None,
+            word_spacing: None,
+            font_variant_caps: StyleFontVariantCaps::default(),
+            tab_width: None,
+            max_horizontal_width: None,
+            overflow_wrap: StyleOverflowWrap::default(),
+            word_break: StyleWordBreak::default(),
+            hanging_punctuation: StyleHangingPunctuation::default(),
+            optical_margin_alignment: false,
+            max_lines: None,
+            max_vertical_height: None,
+            leading: None,
+            white_space: StyleWhiteSpace::default(),
+            spans: Vec::new(),
+            inline_objects: Vec::new(),
+            writing_mode: StyleWritingMode::default(),
+            containers: Vec::new(),
+            widows: 2,
+            orphans: 2,
+            column_count: None,
+            column_width: None,
+            column_gap: 0.0,
+            column_fill: StyleColumnFill::default(),
+        }
+    }
+}
+
+impl ResolvedTextLayoutOptions {
+    /// Returns the span that the character at the given byte offset belongs to
+    pub fn get_span(&self, byte_offset: usize) -> Option<&InlineSpan> {
+        self.spans
+            .iter()
+            .rev()
+            .find(|span| span.range.contains(&byte_offset))
+    }
+
+    /// Returns whether the characters at the two byte offsets belong to the same span
+    /// (a word is split into separately shaped runs where this is not the case)
+    pub fn is_same_span(&self, a: usize, b: usize) -> bool {
+        match (self.get_span(a), self.get_span(b)) {
+            (Some(a), Some(b)) => std::ptr::eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the font size (in pixels) of the character at the given byte offset
+    pub fn get_font_size_px(&self, byte_offset: usize) -> f32 {
+        self.get_span(byte_offset)
+            .and_then(|span| span.font_size_px)
+            .unwrap_or(self.font_size_px)
+    }
+
+    /// Returns the `font-variant-caps` of the character at the given byte offset
+    pub fn get_font_variant_caps(&self, byte_offset: usize) -> StyleFontVariantCaps {
+        self.get_span(byte_offset)
+            .and_then(|span| span.font_variant_caps)
+            .unwrap_or(self.font_variant_caps)
+    }
+
+    /// Returns the letter spacing (in pixels) after the character at the given byte offset
+    pub fn get_letter_spacing_px(&self, byte_offset: usize) -> f32 {
+        let font_size_px = self.get_font_size_px(byte_offset);
+        self.letter_spacing
+            .map_or(0.0, |spacing| spacing.resolve(font_size_px, font_size_px))
+    }
+
+    /// Returns the word spacing (in pixels) after the space at the given byte offset,
+    /// `space_advance_px` is the advance of the space glyph (at the font size of the span)
+    pub fn get_word_spacing_px(&self, byte_offset: usize, space_advance_px: f32) -> f32 {
+        let font_size_px = self.get_font_size_px(byte_offset);
+        self.word_spacing.map_or(0.0, |spacing| {
+            spacing.resolve(font_size_px, space_advance_px)
+        })
+    }
+}
+
+/// Placeholder box for an image or widget inside of the text, which is
+/// positioned like a word (its `vertical-align` is set via an `InlineSpan`)
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+#[repr(C)]
+pub struct InlineObject {
+    pub size: LogicalSize,
+    /// Distance from the bottom edge of the object to its baseline (in pixels),
+    /// usually 0.0, so that the object sits on the baseline of the line
+    pub baseline_offset: f32,
+}
+
+impl InlineObject {
+    /// Returns the ascent and descent of the object (relative to its baseline)
+    pub fn get_extent(&self) -> (f32, f32) {
+        (
+            self.size.height - self.baseline_offset,
+            self.baseline_offset,
+        )
+    }
+}
+
+/// Styled part of an inline text (i.e. a `<span>`, `<sub>` or `<sup>`), applies to
+/// the characters inside of the `range` (see `ResolvedTextLayoutOptions::spans`)
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C)]
+pub struct InlineSpan {
+    /// Byte range of the span (into `Words::internal_str`)
+    pub range: Range<usize>,
+    /// Font size of the span (in pixels), defaults to the font size of the text
+    pub font_size_px: Option<f32>,
+    /// Position of the span relative to the baseline of the line
+    pub vertical_align: StyleInlineVerticalAlign,
+    /// `font-variant-caps` of the span, defaults to the one of the text
+    pub font_variant_caps: Option<StyleFontVariantCaps>,
 }
@@ -4,26 +4,40 @@ use std::ops::Range;
 use allsorts::gpos::{Info, Placement};
 
 use crate::{
+    css::{StyleLineHeight, StyleWordBreak, StyleWritingMode},
     logical::{LogicalPosition, LogicalRect, LogicalSize},
-    ui_solver::{InlineTextLine, ResolvedTextLayoutOptions},
+    ui_solver::{InlineTextLine, ResolvedTextLayoutOptions, TextContainerFragment},
 };
 
 /// Word that is scaled (to a font / font instance), but not yet positioned
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ShapedWord {
     /// Glyph codepoint, glyph ID + kerning data
     pub glyph_infos: Vec<GlyphInfo>,
     /// The sum of the width of all the characters in this word
     pub word_width: usize,
+    /// Mapping between the text and the glyphs, ordered by text (and glyph) position
+    pub clusters: Vec<GlyphCluster>,
+}
+
+/// Smallest unit of text that maps to a sequence of glyphs: a ligature covers
+/// all of its characters, a decomposed character covers all of its glyphs
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GlyphCluster {
+    /// Bytes of the source text (in `Words::internal_str`) that the glyphs were shaped from
+    pub text_range: Range<usize>,
+    /// Indices of the glyphs in `ShapedWord::glyph_infos`
+    pub glyph_range: Range<usize>,
 }
 
 impl fmt::Debug for ShapedWord {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "ShapedWord {{ glyph_infos: {} glyphs, word_width: {} }}",
+            "ShapedWord {{ glyph_infos: {} glyphs, word_width: {}, clusters: {} }}",
             self.glyph_infos.len(),
-            self.word_width
+            self.word_width,
+            self.clusters.len()
         )
     }
 }
@@ -32,12 +46,390 @@ impl ShapedWord {
     pub fn get_word_width(&self, units_per_em: u16, target_font_size: f32) -> f32 {
         self.word_width as f32 / units_per_em as f32 * target_font_size
     }
+    /// Returns the cluster that the glyph at `glyph_index` belongs to
+    pub fn get_cluster_of_glyph(&self, glyph_index: usize) -> Option<&GlyphCluster> {
+        let i = self
+            .clusters
+            .partition_point(|c| c.glyph_range.end <= glyph_index);
+        self.clusters
+            .get(i)
+            .filter(|c| c.glyph_range.contains(&glyph_index))
+    }
+
+    /// Returns the cluster that contains the byte at `byte_offset` (in `Words::internal_str`)
+    pub fn get_cluster_at_byte(&self, byte_offset: usize) -> Option<&GlyphCluster> {
+        let i = self
+            .clusters
+            .partition_point(|c| c.text_range.end <= byte_offset);
+        self.clusters
+            .get(i)
+            .filter(|c| c.text_range.contains(&byte_offset))
+    }
+
+    /// Moves the clusters by `offset` bytes (the word was shaped on its own,
+    /// but the clusters are relative to the start of `Words::internal_str`)
+    pub(crate) fn offset_clusters(&mut self, offset: usize) {
+        for glyph_info in self.glyph_infos.iter_mut() {
+            glyph_info.cluster += offset;
+        }
+        for cluster in self.clusters.iter_mut() {
+            cluster.text_range = cluster.text_range.start + offset..cluster.text_range.end + offset;
+        }
+    }
+
     /// Returns the number of glyphs THAT ARE NOT DIACRITIC MARKS
     pub fn number_of_glyphs(&self) -> usize {
+        self.glyph_infos.iter().filter(|i| !i.is_mark()).count()
+    }
+
+    /// Returns whether the word can be split before the glyph at `glyph_index` without
+    /// tearing apart a glyph cluster (ligatures, decompositions) or an extended grapheme
+    /// cluster of [UAX #29](https://www.unicode.org/reports/tr29/) (base character + marks,
+    /// hangul syllables, emoji sequences, flags)
+    pub fn is_grapheme_boundary(&self, glyph_index: usize) -> bool {
+        let next = match self.glyph_infos.get(glyph_index) {
+            Some(s) if glyph_index > 0 => s,
+            _ => return true,
+        };
+
+        // glyphs of the same cluster are never separated
+        if self
+            .get_cluster_of_glyph(glyph_index)
+            .is_some_and(|c| c.glyph_range.start != glyph_index)
+            || next.info.glyph.multi_subst_dup
+        {
+            return false;
+        }
+
+        let next_char = match next.info.glyph.unicodes.first() {
+            Some(s) => *s,
+            None => return true,
+        };
+        let previous_chars = self.glyph_infos[..glyph_index]
+            .iter()
+            .filter(|g| !g.info.glyph.multi_subst_dup)
+            .flat_map(|g| g.info.glyph.unicodes.iter().copied())
+            .collect::<Vec<_>>();
+        is_grapheme_break(&previous_chars, next_char)
+    }
+
+    /// Appends the glyphs of the next part of the word, which was shaped separately
+    /// because it is in a different span (the clusters have to be offset already)
+    pub(crate) fn append(&mut self, next: ShapedWord) {
+        let glyph_offset = self.glyph_infos.len();
+        self.glyph_infos
+            .extend(next.glyph_infos.into_iter().map(|mut glyph_info| {
+                // marks are attached to glyphs of the same part
+                glyph_info.info.placement = match glyph_info.info.placement {
+                    Placement::MarkAnchor(base, base_anchor, mark_anchor) => {
+                        Placement::MarkAnchor(base + glyph_offset, base_anchor, mark_anchor)
+                    }
+                    Placement::MarkOverprint(base) => Placement::MarkOverprint(base + glyph_offset),
+                    Placement::CursiveAnchor(exit, rtl, exit_anchor, entry_anchor) => {
+                        Placement::CursiveAnchor(
+                            exit + glyph_offset,
+                            rtl,
+                            exit_anchor,
+                            entry_anchor,
+                        )
+                    }
+                    placement => placement,
+                };
+                glyph_info
+            }));
+        self.clusters
+            .extend(next.clusters.into_iter().map(|cluster| GlyphCluster {
+                text_range: cluster.text_range,
+                glyph_range: cluster.glyph_range.start + glyph_offset
+                    ..cluster.glyph_range.end + glyph_offset,
+            }));
+        self.word_width += next.word_width;
+    }
+
+    /// Splits the glyphs in the given range into runs of glyphs of the same span,
+    /// which share the font size, letter spacing and vertical alignment
+    pub fn get_span_runs(
+        &self,
+        glyphs: Range<usize>,
+        text_layout_options: &ResolvedTextLayoutOptions,
+    ) -> Vec<Range<usize>> {
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        for glyph_idx in glyphs.clone() {
+            match runs.last_mut() {
+                Some(run)
+                    if text_layout_options.is_same_span(
+                        self.glyph_infos[run.start].cluster,
+                        self.glyph_infos[glyph_idx].cluster,
+                    ) =>
+                {
+                    run.end = glyph_idx + 1
+                }
+                _ => runs.push(glyph_idx..glyph_idx + 1),
+            }
+        }
+        runs
+    }
+
+    /// Returns the width (in pixels) of the glyphs in the given range, including
+    /// the letter spacing between (but not after) the glyphs. Every glyph is scaled
+    /// to the font size of its span.
+    pub fn get_glyphs_width(
+        &self,
+        glyphs: Range<usize>,
+        units_per_em: u16,
+        text_layout_options: &ResolvedTextLayoutOptions,
+    ) -> f32 {
+        let last_letter_spacing_px = self
+            .glyph_infos
+            .get(glyphs.clone())
+            .unwrap_or_default()
+            .iter()
+            .rev()
+            .find(|g| !g.is_mark())
+            .map_or(0.0, |g| {
+                text_layout_options.get_letter_spacing_px(g.cluster)
+            });
+        self.get_glyphs_advance(glyphs, units_per_em, text_layout_options) - last_letter_spacing_px
+    }
+
+    /// Returns how far the caret advances (in pixels) over the glyphs in the given range,
+    /// like `get_glyphs_width`, but including the letter spacing after the last glyph
+    pub fn get_glyphs_advance(
+        &self,
+        glyphs: Range<usize>,
+        units_per_em: u16,
+        text_layout_options: &ResolvedTextLayoutOptions,
+    ) -> f32 {
         self.glyph_infos
+            .get(glyphs)
+            .unwrap_or_default()
+            .iter()
+            .map(|g| {
+                let letter_spacing_px = if g.is_mark() {
+                    0.0
+                } else {
+                    text_layout_options.get_letter_spacing_px(g.cluster)
+                };
+                g.get_x_advance_total_scaled(
+                    units_per_em,
+                    text_layout_options.get_font_size_px(g.cluster),
+                ) + letter_spacing_px
+            })
+            .sum()
+    }
+
+    /// Returns whether a line may be broken before the glyph at `glyph_index`
+    /// (without having to resort to `overflow-wrap`)
+    pub fn can_break_before(&self, glyph_index: usize, word_break: StyleWordBreak) -> bool {
+        if !self.is_grapheme_boundary(glyph_index) {
+            return false;
+        }
+
+        match word_break {
+            StyleWordBreak::BreakAll => glyph_index > 0 && glyph_index < self.glyph_infos.len(),
+            StyleWordBreak::KeepAll => false,
+            StyleWordBreak::Normal => {
+                let is_ideographic_glyph = |glyph_index: usize| {
+                    self.glyph_infos
+                        .get(glyph_index)
+                        .and_then(|g| g.info.glyph.unicodes.first())
+                        .is_some_and(|c| is_ideographic(*c))
+                };
+                glyph_index > 0
+                    && is_ideographic_glyph(glyph_index - 1)
+                    && is_ideographic_glyph(glyph_index)
+            }
+        }
+    }
+
+    /// Returns the width (in pixels) of the widest part of the word that can't be broken
+    pub fn get_min_content_width(
+        &self,
+        units_per_em: u16,
+        text_layout_options: &ResolvedTextLayoutOptions,
+        can_break_before: impl Fn(usize) -> bool,
+    ) -> f32 {
+        let mut min_content_width = 0.0_f32;
+        let mut segment_start = 0;
+        for glyph_idx in 1..=self.glyph_infos.len() {
+            if glyph_idx == self.glyph_infos.len() || can_break_before(glyph_idx) {
+                min_content_width = min_content_width.max(self.get_glyphs_width(
+                    segment_start..glyph_idx,
+                    units_per_em,
+                    text_layout_options,
+                ));
+                segment_start = glyph_idx;
+            }
+        }
+        min_content_width
+    }
+
+    /// Returns the length (in bytes of the source text) of the characters
+    /// that produced the glyphs in the given range
+    pub fn get_byte_len(&self, glyphs: Range<usize>) -> usize {
+        self.glyph_infos
+            .get(glyphs)
+            .unwrap_or_default()
+            .iter()
+            .filter(|g| !g.info.glyph.multi_subst_dup)
+            .flat_map(|g| g.info.glyph.unicodes.iter())
+            .map(|c| c.len_utf8())
+            .sum()
+    }
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// Returns whether the character is a CJK ideograph, kana or hangul syllable,
+/// which allow line breaks between them (CJK punctuation is excluded)
+fn is_ideographic(c: char) -> bool {
+    matches!(c,
+        '\u{2E80}'..='\u{2FFF}' // CJK radicals, Kangxi radicals
+        | '\u{3040}'..='\u{31FF}' // hiragana, katakana, bopomofo, hangul compat. jamo
+        | '\u{3200}'..='\u{4DBF}' // enclosed CJK, CJK compatibility, CJK ext. A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{A960}'..='\u{A97F}' // hangul jamo ext. A
+        | '\u{AC00}'..='\u{D7FF}' // hangul syllables, hangul jamo ext. B
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+        | '\u{20000}'..='\u{3FFFF}' // CJK ext. B - H
+    )
+}
+
+/// Returns whether the character is set upright in vertical text (instead of being
+/// rotated sideways), based on the `Vertical_Orientation` property of
+/// [UAX #50](https://www.unicode.org/reports/tr50/) (simplified to ranges, `Tu` and
+/// `Tr` characters are treated as upright, their alternate glyphs come from `vert`)
+pub(crate) fn is_upright_in_vertical_text(c: char) -> bool {
+    matches!(c,
+        '\u{00A7}' | '\u{00A9}' | '\u{00AE}' | '\u{00B1}' | '\u{00BC}'..='\u{00BE}'
+        | '\u{00D7}' | '\u{00F7}'
+        | '\u{1100}'..='\u{11FF}' // hangul jamo
+        | '\u{1401}'..='\u{167F}' // unified canadian aboriginal syllabics
+        | '\u{18B0}'..='\u{18FF}' // unified canadian aboriginal syllabics ext.
+        | '\u{2016}' | '\u{2020}' | '\u{2021}' | '\u{2030}' | '\u{2031}' | '\u{203B}' | '\u{203C}'
+        | '\u{2042}' | '\u{2047}'..='\u{2049}' | '\u{2051}'
+        | '\u{20DD}'..='\u{20E0}' | '\u{20E2}'..='\u{20E4}'
+        | '\u{2100}'..='\u{218F}' // letterlike symbols, number forms
+        | '\u{221E}' | '\u{2234}' | '\u{2235}'
+        | '\u{2300}'..='\u{2307}' | '\u{230C}'..='\u{231F}' | '\u{2324}'..='\u{2328}'
+        | '\u{232B}' | '\u{237D}'..='\u{239A}' | '\u{23BE}'..='\u{23CD}' | '\u{23CF}'
+        | '\u{23D1}'..='\u{23DB}' | '\u{23E2}'..='\u{2422}' | '\u{2424}'..='\u{24FF}'
+        | '\u{25A0}'..='\u{2619}' | '\u{2620}'..='\u{2767}' | '\u{2776}'..='\u{2793}'
+        | '\u{2B12}'..='\u{2B2F}' | '\u{2B50}'..='\u{2B59}' | '\u{2BB8}'..='\u{2BFF}'
+        | '\u{2E80}'..='\u{A4CF}' // CJK, kana, bopomofo, hangul compat. jamo, yi
+        | '\u{A960}'..='\u{A97F}' // hangul jamo ext. A
+        | '\u{AC00}'..='\u{D7FF}' // hangul syllables, hangul jamo ext. B
+        | '\u{E000}'..='\u{FAFF}' // private use, CJK compatibility ideographs
+        | '\u{FE10}'..='\u{FE1F}' // vertical forms
+        | '\u{FE30}'..='\u{FE4F}' // CJK compatibility forms
+        | '\u{FE50}'..='\u{FE6F}' // small form variants
+        | '\u{FF01}'..='\u{FF60}' // fullwidth forms
+        | '\u{FFE0}'..='\u{FFE7}'
+        | '\u{FFFC}'..='\u{FFFD}'
+        | '\u{1B000}'..='\u{1B2FF}' // kana supplement, small kana ext., nushu
+        | '\u{1F000}'..='\u{1F8FF}' // mahjong, domino, playing cards, enclosed, emoji
+        | '\u{1F900}'..='\u{1FAFF}' // supplemental symbols and pictographs
+        | '\u{20000}'..='\u{3FFFD}' // CJK ext. B - H
+        | '\u{F0000}'..='\u{10FFFD}' // supplementary private use
+    )
+}
+
+/// `Grapheme_Cluster_Break` property of a character (UAX #29), `Extend` includes `SpacingMark`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum GraphemeClusterBreak {
+    Control,
+    Extend,
+    ZeroWidthJoiner,
+    RegionalIndicator,
+    Prepend,
+    HangulL,
+    HangulV,
+    HangulT,
+    HangulLV,
+    HangulLVT,
+    Other,
+}
+
+impl GraphemeClusterBreak {
+    // `u32::is_multiple_of` needs a newer toolchain than the crate supports
+    #[allow(clippy::manual_is_multiple_of)]
+    fn of(c: char) -> Self {
+        use self::GraphemeClusterBreak::*;
+        match c {
+            ZERO_WIDTH_JOINER => ZeroWidthJoiner,
+            '\u{200B}' | '\u{2028}' | '\u{2029}' | '\u{FEFF}' => Control,
+            c if c.is_control() => Control,
+            '\u{200C}' // zero width non-joiner
+            | '\u{0E33}' | '\u{0EB3}' // thai / lao sara am
+            | '\u{FE00}'..='\u{FE0F}' // variation selectors
+            | '\u{FF9E}'..='\u{FF9F}' // half-width sound marks
+            | '\u{1F3FB}'..='\u{1F3FF}' // emoji skin tone modifiers
+            | '\u{E0020}'..='\u{E007F}' // emoji tag sequences
+            | '\u{E0100}'..='\u{E01EF}' => Extend, // variation selectors supplement
+            c if unicode_normalization::char::is_combining_mark(c) => Extend,
+            '\u{1F1E6}'..='\u{1F1FF}' => RegionalIndicator,
+            '\u{0600}'..='\u{0605}' | '\u{06DD}' | '\u{070F}' | '\u{0890}'..='\u{0891}'
+            | '\u{08E2}' | '\u{110BD}' | '\u{110CD}' => Prepend,
+            '\u{1100}'..='\u{115F}' | '\u{A960}'..='\u{A97C}' => HangulL,
+            '\u{1160}'..='\u{11A7}' | '\u{D7B0}'..='\u{D7C6}' => HangulV,
+            '\u{11A8}'..='\u{11FF}' | '\u{D7CB}'..='\u{D7FB}' => HangulT,
+            '\u{AC00}'..='\u{D7A3}' if (c as u32 - 0xAC00) % 28 == 0 => HangulLV,
+            '\u{AC00}'..='\u{D7A3}' => HangulLVT,
+            _ => Other,
+        }
+    }
+}
+
+/// Returns whether the character is an `Extended_Pictographic` (emoji), simplified to ranges
+fn is_extended_pictographic(c: char) -> bool {
+    matches!(c,
+        '\u{00A9}' | '\u{00AE}' | '\u{203C}' | '\u{2049}' | '\u{2122}' | '\u{2139}'
+        | '\u{2194}'..='\u{21AA}' | '\u{231A}'..='\u{23FF}' | '\u{24C2}'
+        | '\u{25AA}'..='\u{27BF}' | '\u{2934}' | '\u{2935}' | '\u{2B05}'..='\u{2B55}'
+        | '\u{3030}' | '\u{303D}' | '\u{3297}' | '\u{3299}'
+        | '\u{1F000}'..='\u{1F1E5}' | '\u{1F200}'..='\u{1F3FA}' | '\u{1F400}'..='\u{1FAFF}'
+        | '\u{1FC00}'..='\u{1FFFD}'
+    )
+}
+
+/// Returns whether there is an extended grapheme cluster boundary between the
+/// `previous` characters and the `next` character (rules GB3 - GB13 of UAX #29)
+#[allow(clippy::manual_is_multiple_of)]
+fn is_grapheme_break(previous: &[char], next: char) -> bool {
+    use self::GraphemeClusterBreak::*;
+
+    let before = match previous.last() {
+        Some(s) => GraphemeClusterBreak::of(*s),
+        None => return true,
+    };
+    let after = GraphemeClusterBreak::of(next);
+
+    match (before, after) {
+        // GB3 - GB5: break around controls (CR LF are separate tokens)
+        (Control, _) | (_, Control) => true,
+        // GB6 - GB8: hangul syllable sequences
+        (HangulL, HangulL | HangulV | HangulLV | HangulLVT)
+        | (HangulLV | HangulV, HangulV | HangulT)
+        | (HangulLVT | HangulT, HangulT) => false,
+        // GB9, GB9a, GB9b: marks, joiners and prepended characters
+        (_, Extend | ZeroWidthJoiner) | (Prepend, _) => false,
+        // GB11: emoji ZWJ sequences
+        (ZeroWidthJoiner, _) if is_extended_pictographic(next) => !previous[..previous.len() - 1]
             .iter()
-            .filter(|i| i.info.placement == Placement::None)
-            .count()
+            .rev()
+            .find(|c| GraphemeClusterBreak::of(**c) != Extend)
+            .is_some_and(|c| is_extended_pictographic(*c)),
+        // GB12, GB13: flags are pairs of regional indicators
+        (RegionalIndicator, RegionalIndicator) => {
+            let preceding_indicators = previous
+                .iter()
+                .rev()
+                .take_while(|c| GraphemeClusterBreak::of(**c) == RegionalIndicator)
+                .count();
+            preceding_indicators % 2 == 0
+        }
+        // GB999
+        _ => true,
     }
 }
 
@@ -67,6 +459,13 @@ pub struct WordPositions {
     /// Note that the vertical extent can be larger than the last words' position,
     /// because of trailing negative glyph advances.
     pub content_size: LogicalSize,
+    /// If the text has been cut off (`max_lines`, `max_vertical_height`,
+    /// `text-overflow` or because it doesn't fit into the containers),
+    /// the byte offset of the first character that is not visible
+    pub truncated_at: Option<usize>,
+    /// Lines and words per container, empty if the text doesn't flow
+    /// through containers (see `ResolvedTextLayoutOptions::containers`)
+    pub containers: Vec<TextContainerFragment>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -82,11 +481,27 @@ pub struct LayoutedGlyphs {
     pub glyphs: Vec<GlyphInstance>,
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub struct GlyphInstance {
     pub index: u32,
     pub point: LogicalPosition,
     pub size: LogicalSize,
+    /// The glyph is rotated 90° clockwise around the `point` (sideways text in vertical lines)
+    pub rotated: bool,
+    /// The glyph is drawn at `scale` times the font size (synthetic small caps)
+    pub scale: f32,
+}
+
+impl Default for GlyphInstance {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            point: LogicalPosition::zero(),
+            size: LogicalSize::zero(),
+            rotated: false,
+            scale: 1.0,
+        }
+    }
 }
 
 /// Text broken up into `Tab`, `Word()`, `Return` characters
@@ -97,6 +512,120 @@ pub struct Words {
     pub items: Vec<Word>,
     /// String that makes up this paragraph of words
     pub internal_str: String,
+    /// Maps the byte offsets of the original text to `internal_str` and back
+    pub offset_map: TextOffsetMap,
+}
+
+/// Whether `split_text_into_words` normalizes the text before splitting it
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub enum TextNormalization {
+    /// Canonical composition (NFC): decomposed characters ("e" + U+0301) are
+    /// composed ("é"), so that the font can use the precomposed glyph
+    #[default]
+    Nfc,
+    /// The text is used as-is, all offsets are the offsets of the original text
+    None,
+}
+
+/// Sorted list of the parts of a text that were changed during normalization,
+/// the text between the changes is the same in both strings
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct TextOffsetMap {
+    pub changes: Vec<TextOffsetChange>,
+}
+
+/// Bytes of the original text that were replaced with different bytes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct TextOffsetChange {
+    pub original: Range<usize>,
+    pub normalized: Range<usize>,
+}
+
+impl TextOffsetMap {
+    /// Maps a byte offset of the original text to `Words::internal_str`,
+    /// offsets inside of a changed part snap to the start of the change
+    pub fn to_normalized(&self, original_offset: usize) -> usize {
+        self.map_offset(original_offset, false, false)
+    }
+
+    /// Maps a byte offset of `Words::internal_str` back to the original text,
+    /// offsets inside of a changed part snap to the start of the change
+    pub fn to_original(&self, normalized_offset: usize) -> usize {
+        self.map_offset(normalized_offset, false, true)
+    }
+
+    /// Maps a range (e.g. a selection) to `Words::internal_str`, the range
+    /// grows to cover all changed parts that it only partially covers
+    pub fn range_to_normalized(&self, original: Range<usize>) -> Range<usize> {
+        self.map_offset(original.start, false, false)..self.map_offset(original.end, true, false)
+    }
+
+    /// Maps a range of `Words::internal_str` (e.g. a word or a glyph cluster) back to
+    /// the original text, the range grows to cover all changed parts that it only partially covers
+    pub fn range_to_original(&self, normalized: Range<usize>) -> Range<usize> {
+        self.map_offset(normalized.start, false, true)..self.map_offset(normalized.end, true, true)
+    }
+
+    /// Combines two consecutive changes of a text: `self` maps the original text to an
+    /// intermediate text, `next` maps the intermediate text to the final text. Changes
+    /// that overlap in the intermediate text are merged into a single change.
+    pub fn chain(&self, next: &TextOffsetMap) -> TextOffsetMap {
+        let mut ranges = self
+            .changes
+            .iter()
+            .map(|c| c.normalized.clone())
+            .chain(next.changes.iter().map(|c| c.original.clone()))
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|r| (r.start, r.end));
+
+        let mut merged = Vec::<Range<usize>>::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        TextOffsetMap {
+            changes: merged
+                .into_iter()
+                .map(|range| TextOffsetChange {
+                    original: self.range_to_original(range.clone()),
+                    normalized: next.range_to_normalized(range),
+                })
+                .collect(),
+        }
+    }
+
+    fn map_offset(&self, offset: usize, round_up: bool, to_original: bool) -> usize {
+        let from_to = |c: &TextOffsetChange| {
+            if to_original {
+                (c.normalized.clone(), c.original.clone())
+            } else {
+                (c.original.clone(), c.normalized.clone())
+            }
+        };
+
+        // last change that starts before the offset
+        let i = self
+            .changes
+            .partition_point(|c| from_to(c).0.start < offset);
+        let (from, to) = match i.checked_sub(1) {
+            Some(i) => from_to(&self.changes[i]),
+            None => return offset,
+        };
+
+        if offset >= from.end {
+            offset - from.end + to.end
+        } else if round_up {
+            to.end
+        } else {
+            to.start
+        }
+    }
 }
 
 impl Words {
@@ -125,8 +654,14 @@ pub enum Token {
     Word,
     /// `\r`, `\n` or `\r\n`, escaped: `\x0D`, `\x0A` or `\x0D\x0A`
     Return,
-    /// Space character
+    /// Space character (or a collapsed sequence of white space)
     Space,
+    /// `\t` character, only emitted if the white-space mode preserves spaces
+    Tab,
+    /// Object replacement character (U+FFFC), a placeholder for an image or widget
+    /// inside of the text - the value is the index into
+    /// `ResolvedTextLayoutOptions::inline_objects` (the n-th object in the text)
+    InlineObject(usize),
 }
 
 /// A paragraph of words that are shaped and scaled (* but not yet layouted / positioned*!)
@@ -147,6 +682,14 @@ pub struct ShapedWords {
     pub font_metrics_ascender: i16,
     pub font_metrics_descender: i16,
     pub font_metrics_line_gap: i16,
+    /// Height of the lowercase letters (for `vertical-align: middle`)
+    pub font_metrics_x_height: i16,
+    /// Offset of subscripts below the baseline (POSITIVE = down)
+    pub font_metrics_subscript_y_offset: i16,
+    /// Offset of superscripts above the baseline
+    pub font_metrics_superscript_y_offset: i16,
+    /// Marker that is displayed at the end of a cut off line, see `shape_text_overflow`
+    pub text_overflow: Option<ShapedWord>,
 }
 
 impl ShapedWords {
@@ -160,7 +703,7 @@ impl ShapedWords {
 
     /// Get the distance from the top of the text to the baseline of the text (= ascender)
     pub fn get_baseline_px(&self, target_font_size: f32) -> f32 {
-        target_font_size + self.get_descender(target_font_size)
+        self.get_ascender(target_font_size)
     }
 
     /// NOTE: descender is NEGATIVE
@@ -169,17 +712,103 @@ impl ShapedWords {
             * target_font_size
     }
 
-    /// `height = sTypoAscender - sTypoDescender + sTypoLineGap`
+    /// `height = sTypoAscender - sTypoDescender + sTypoLineGap` (= `line-height: normal`)
     pub fn get_line_height(&self, target_font_size: f32) -> f32 {
-        self.font_metrics_ascender as f32 / self.font_metrics_units_per_em as f32
-            - self.font_metrics_descender as f32 / self.font_metrics_units_per_em as f32
-            + self.font_metrics_line_gap as f32 / self.font_metrics_units_per_em as f32
-                * target_font_size
+        self.get_content_height(target_font_size) + self.get_line_gap(target_font_size)
+    }
+
+    /// `height = sTypoAscender - sTypoDescender`, the height of the glyphs without the line gap
+    pub fn get_content_height(&self, target_font_size: f32) -> f32 {
+        self.get_ascender(target_font_size) - self.get_descender(target_font_size)
+    }
+
+    pub fn get_line_gap(&self, target_font_size: f32) -> f32 {
+        self.font_metrics_line_gap as f32 / self.font_metrics_units_per_em as f32 * target_font_size
     }
 
     pub fn get_ascender(&self, target_font_size: f32) -> f32 {
         self.font_metrics_ascender as f32 / self.font_metrics_units_per_em as f32 * target_font_size
     }
+
+    pub fn get_x_height(&self, target_font_size: f32) -> f32 {
+        self.font_metrics_x_height as f32 / self.font_metrics_units_per_em as f32 * target_font_size
+    }
+
+    /// NOTE: subscript offset is POSITIVE (distance below the baseline)
+    pub fn get_subscript_y_offset(&self, target_font_size: f32) -> f32 {
+        self.font_metrics_subscript_y_offset as f32 / self.font_metrics_units_per_em as f32
+            * target_font_size
+    }
+
+    pub fn get_superscript_y_offset(&self, target_font_size: f32) -> f32 {
+        self.font_metrics_superscript_y_offset as f32 / self.font_metrics_units_per_em as f32
+            * target_font_size
+    }
+
+    /// Returns the ascent and descent (both POSITIVE, including the half-leading)
+    /// of an inline box with the given font size and line height
+    pub fn get_inline_box_extent(
+        &self,
+        target_font_size: f32,
+        line_height: StyleLineHeight,
+    ) -> (f32, f32) {
+        let line_height_px =
+            line_height.resolve(target_font_size, self.get_line_height(target_font_size));
+        // half of the leading is added above the ascender, half below the descender
+        let half_leading_px = (line_height_px - self.get_content_height(target_font_size)) / 2.0;
+        (
+            half_leading_px + self.get_ascender(target_font_size),
+            half_leading_px - self.get_descender(target_font_size),
+        )
+    }
+
+    /// Returns the ascent and descent (both POSITIVE, including the half-leading)
+    /// of the inline box of the word starting at the given byte offset
+    pub fn get_word_box_extent(
+        &self,
+        text_layout_options: &ResolvedTextLayoutOptions,
+        byte_offset: usize,
+    ) -> (f32, f32) {
+        self.get_inline_box_extent(
+            text_layout_options.get_font_size_px(byte_offset),
+            text_layout_options.line_height,
+        )
+    }
+
+    /// Returns how far the baseline of the inline box (word or inline object) at the given
+    /// byte offset is raised above the baseline of the line (negative values lower it).
+    ///
+    /// `box_extent` is the ascent and descent of the inline box, `line_extent` is the final
+    /// ascent and descent of the line, which is necessary for `vertical-align: top / bottom`
+    pub fn get_baseline_shift(
+        &self,
+        text_layout_options: &ResolvedTextLayoutOptions,
+        byte_offset: usize,
+        box_extent: (f32, f32),
+        line_extent: (f32, f32),
+    ) -> f32 {
+        use crate::css::StyleInlineVerticalAlign::*;
+
+        let span = match text_layout_options.get_span(byte_offset) {
+            Some(s) => s,
+            None => return 0.0,
+        };
+        let parent_font_size_px = text_layout_options.font_size_px;
+        let (ascent, descent) = box_extent;
+        let (line_ascent, line_descent) = line_extent;
+
+        match span.vertical_align {
+            Baseline => 0.0,
+            Sub => -self.get_subscript_y_offset(parent_font_size_px),
+            Super => self.get_superscript_y_offset(parent_font_size_px),
+            Top => line_ascent - ascent,
+            Bottom => descent - line_descent,
+            Middle => self.get_x_height(parent_font_size_px) / 2.0 - (ascent - descent) / 2.0,
+            TextTop => self.get_ascender(parent_font_size_px) - ascent,
+            TextBottom => descent + self.get_descender(parent_font_size_px),
+            Length(px) => px,
+        }
+    }
 }
 
 pub fn get_inline_text(
@@ -188,14 +817,8 @@ pub fn get_inline_text(
     word_positions: &WordPositions,
     inline_text_layout: &crate::ui_solver::InlineTextLayout,
 ) -> InlineText {
-    let font_size_px = word_positions.text_layout_options.font_size_px;
-    let descender_px = &shaped_words.get_descender(font_size_px); // descender is NEGATIVE
-    let letter_spacing_px = word_positions
-        .text_layout_options
-        .letter_spacing
-        .as_ref()
-        .copied()
-        .unwrap_or(0.0);
+    let text_layout_options = &word_positions.text_layout_options;
+    let font_size_px = text_layout_options.font_size_px;
     let units_per_em = shaped_words.font_metrics_units_per_em;
 
     let inline_lines = inline_text_layout
@@ -218,103 +841,151 @@ pub fn get_inline_text(
                             let shaped_word_index = word_position.shaped_word_index?;
                             let shaped_word = shaped_words.items.get(shaped_word_index)?;
 
-                            // most words are less than 16 chars, avg length of an english word is 4.7 chars
-                            let mut all_glyphs_in_this_word = Vec::<InlineGlyph>::with_capacity(16);
-                            let mut x_pos_in_word_px = 0.0;
-
-                            // all words only store the unscaled horizontal advance + horizontal kerning
-                            for glyph_info in shaped_word.glyph_infos.iter() {
-                                // local x and y displacement of the glyph - does NOT advance the horizontal cursor!
-                                let mut displacement = LogicalPosition::zero();
-
-                                // if the character is a mark, the mark displacement has to be added ON TOP OF the existing displacement
-                                // the origin should be relative to the word, not the final text
-                                let (letter_spacing_for_glyph, origin) =
-                                    match glyph_info.info.placement {
-                                        Placement::None => (
-                                            letter_spacing_px,
+                            // words that are broken across lines are only partially on this line
+                            let (mut glyphs, bounds) = match line
+                                .fragments
+                                .iter()
+                                .find(|f| f.word_index == word_idx)
+                            {
+                                Some(f) => (f.glyphs.clone(), LogicalRect::new(f.position, f.size)),
+                                None => (
+                                    0..shaped_word.glyph_infos.len(),
+                                    LogicalRect::new(word_position.position, word_position.size),
+                                ),
+                            };
+
+                            // only the glyphs before the truncation point are visible
+                            if let Some(t) = line.truncation.as_ref() {
+                                if t.word_index == word_idx {
+                                    glyphs.end = glyphs.end.min(t.glyph_end);
+                                }
+                            }
+
+                            // every part of the word in a different span is a separate
+                            // inline word with its own font size and baseline
+                            let inline_words = shaped_word
+                                .get_span_runs(glyphs.clone(), text_layout_options)
+                                .into_iter()
+                                .map(|run| {
+                                    let byte_offset = shaped_word.glyph_infos[run.start].cluster;
+                                    let run_font_size_px =
+                                        text_layout_options.get_font_size_px(byte_offset);
+                                    let run_x = shaped_word.get_glyphs_advance(
+                                        glyphs.start..run.start,
+                                        units_per_em,
+                                        text_layout_options,
+                                    );
+                                    let run_width = shaped_word.get_glyphs_width(
+                                        run.clone(),
+                                        units_per_em,
+                                        text_layout_options,
+                                    );
+                                    let all_glyphs_in_this_run = get_inline_glyphs(
+                                        &shaped_word.glyph_infos,
+                                        run.clone(),
+                                        units_per_em,
+                                        run_font_size_px,
+                                        text_layout_options.get_letter_spacing_px(byte_offset),
+                                    );
+
+                                    let baseline_shift = shaped_words.get_baseline_shift(
+                                        text_layout_options,
+                                        byte_offset,
+                                        shaped_words
+                                            .get_word_box_extent(text_layout_options, byte_offset),
+                                        (line.ascent, line.descent),
+                                    );
+
+                                    InlineWord::Word(InlineTextContents {
+                                        glyphs: all_glyphs_in_this_run,
+                                        bounds: LogicalRect::new(
                                             LogicalPosition::new(
-                                                x_pos_in_word_px + displacement.x,
-                                                displacement.y,
+                                                bounds.origin.x + run_x,
+                                                bounds.origin.y,
                                             ),
+                                            LogicalSize::new(run_width, bounds.size.height),
                                         ),
-                                        Placement::Distance(x, y) => {
-                                            let font_metrics_divisor =
-                                                units_per_em as f32 / font_size_px;
-                                            displacement = LogicalPosition {
-                                                x: x as f32 / font_metrics_divisor,
-                                                y: y as f32 / font_metrics_divisor,
-                                            };
-                                            (
-                                                letter_spacing_px,
-                                                LogicalPosition::new(
-                                                    x_pos_in_word_px + displacement.x,
-                                                    displacement.y,
-                                                ),
-                                            )
-                                        }
-                                        Placement::MarkAnchor(base_glyph_index, _, _) => {
-                                            let anchor = &all_glyphs_in_this_word[base_glyph_index];
-                                            (0.0, anchor.bounds.origin + displacement)
-                                            // TODO: wrong
-                                        }
-                                        Placement::MarkOverprint(index) => {
-                                            let anchor = &all_glyphs_in_this_word[index];
-                                            (0.0, anchor.bounds.origin + displacement)
-                                        }
-                                        Placement::CursiveAnchor(exit_glyph_index, _, _, _) => {
-                                            let anchor = &all_glyphs_in_this_word[exit_glyph_index];
-                                            (0.0, anchor.bounds.origin + displacement)
-                                            // TODO: wrong
-                                        }
-                                    };
-
-                                let glyph_scale_x = glyph_info
-                                    .advance
-                                    .get_x_size_scaled(units_per_em, font_size_px);
-                                let glyph_scale_y = glyph_info
-                                    .advance
-                                    .get_y_size_scaled(units_per_em, font_size_px);
-
-                                let glyph_advance_x = glyph_info
-                                    .advance
-                                    .get_x_advance_scaled(units_per_em, font_size_px);
-                                let kerning_x =
-                                    glyph_info.get_kerning_scaled(units_per_em, font_size_px);
-
-                                let inline_char = InlineGlyph {
-                                    bounds: LogicalRect::new(
-                                        origin,
-                                        LogicalSize::new(glyph_scale_x, glyph_scale_y),
-                                    ),
-                                    glyph_index: glyph_info.info.glyph.glyph_index as u32,
-                                };
-
-                                x_pos_in_word_px +=
-                                    glyph_advance_x + kerning_x + letter_spacing_for_glyph;
+                                        baseline: line.baseline - baseline_shift,
+                                        font_size_px: run_font_size_px,
+                                    })
+                                })
+                                .collect();
 
-                                all_glyphs_in_this_word.push(inline_char);
+                            Some(inline_words)
+                        }
+                        Token::InlineObject(object_index) => {
+                            // the object is hidden if the line is cut off right before it
+                            if line
+                                .truncation
+                                .as_ref()
+                                .is_some_and(|t| t.word_index == word_idx && t.glyph_end == 0)
+                            {
+                                return None;
                             }
 
-                            let inline_word = InlineWord::Word(InlineTextContents {
-                                glyphs: all_glyphs_in_this_word,
+                            let word_position = word_positions.word_positions.get(word_idx)?;
+                            let object = text_layout_options.inline_objects.get(object_index)?;
+                            let baseline_shift = shaped_words.get_baseline_shift(
+                                text_layout_options,
+                                word.index.start,
+                                object.get_extent(),
+                                (line.ascent, line.descent),
+                            );
+                            let (ascent, _) = object.get_extent();
+
+                            Some(vec![InlineWord::InlineObject(InlineObjectContents {
+                                object_index,
                                 bounds: LogicalRect::new(
-                                    word_position.position,
-                                    word_position.size,
+                                    LogicalPosition::new(
+                                        word_position.position.x,
+                                        line.baseline - baseline_shift - ascent,
+                                    ),
+                                    object.size,
                                 ),
-                            });
-
-                            Some(inline_word)
+                            })])
                         }
-                        Token::Return => Some(InlineWord::Return),
-                        Token::Space => Some(InlineWord::Space),
+                        Token::Return => Some(vec![InlineWord::Return]),
+                        Token::Space => Some(vec![InlineWord::Space]),
+                        Token::Tab => Some(vec![InlineWord::Tab]),
                     }
                 })
+                .flatten()
                 .collect::<Vec<InlineWord>>();
 
+            let mut words = words;
+            if let (Some(truncation), Some(marker)) = (
+                line.truncation.as_ref(),
+                shaped_words.text_overflow.as_ref(),
+            ) {
+                let height = word_positions
+                    .word_positions
+                    .get(truncation.word_index)
+                    .map(|p| p.size.height)
+                    .unwrap_or(line.bounds.size.height);
+                words.push(InlineWord::Word(InlineTextContents {
+                    // the marker is not letter spaced, like its width
+                    glyphs: get_inline_glyphs(
+                        &marker.glyph_infos,
+                        0..marker.glyph_infos.len(),
+                        units_per_em,
+                        font_size_px,
+                        0.0,
+                    ),
+                    bounds: LogicalRect::new(
+                        truncation.marker_position,
+                        LogicalSize::new(marker.get_word_width(units_per_em, font_size_px), height),
+                    ),
+                    baseline: line.baseline,
+                    font_size_px,
+                }));
+            }
+
             Some(InlineLine {
                 words,
                 bounds: line.bounds,
+                baseline: line.baseline,
+                ascent: line.ascent,
+                descent: line.descent,
             })
         })
         .collect::<Vec<InlineLine>>();
@@ -324,10 +995,109 @@ pub fn get_inline_text(
         content_size: word_positions.content_size,
         font_size_px,
         last_word_index: word_positions.number_of_shaped_words,
-        baseline_descender_px: *descender_px,
+        writing_mode: text_layout_options.writing_mode,
     }
 }
 
+/// Positions the glyphs in the given range of a shaped word relative to the first glyph
+fn get_inline_glyphs(
+    glyph_infos: &[GlyphInfo],
+    glyphs: Range<usize>,
+    units_per_em: u16,
+    font_size_px: f32,
+    letter_spacing_px: f32,
+) -> Vec<InlineGlyph> {
+    // most words are less than 16 chars, avg length of an english word is 4.7 chars
+    let mut all_glyphs_in_this_word = Vec::<InlineGlyph>::with_capacity(16);
+    let mut x_pos_in_word_px = 0.0;
+    let first_glyph = glyphs.start;
+
+    // all words only store the unscaled horizontal advance + horizontal kerning
+    for glyph_info in glyph_infos.get(glyphs).unwrap_or_default() {
+        // local x and y displacement of the glyph - does NOT advance the horizontal cursor!
+        let mut displacement = LogicalPosition::zero();
+        // the glyph indices of the placements are relative to the word, the base
+        // glyph is missing if the word is split before the mark
+        let base_origin = |glyph_index: usize| {
+            all_glyphs_in_this_word
+                .get(glyph_index.wrapping_sub(first_glyph))
+                .map_or(LogicalPosition::new(x_pos_in_word_px, 0.0), |g| {
+                    g.bounds.origin
+                })
+        };
+
+        // if the character is a mark, the mark displacement has to be added ON TOP OF the existing displacement
+        // the origin should be relative to the word, not the final text
+        let origin = match glyph_info.info.placement {
+            Placement::None => {
+                LogicalPosition::new(x_pos_in_word_px + displacement.x, displacement.y)
+            }
+            Placement::Distance(x, y) => {
+                let font_metrics_divisor = units_per_em as f32 / font_size_px;
+                displacement = LogicalPosition {
+                    x: x as f32 / font_metrics_divisor,
+                    y: y as f32 / font_metrics_divisor,
+                };
+                LogicalPosition::new(x_pos_in_word_px + displacement.x, displacement.y)
+            }
+            Placement::MarkAnchor(base_glyph_index, _, _) => {
+                base_origin(base_glyph_index) + displacement
+                // TODO: wrong
+            }
+            Placement::MarkOverprint(index) => base_origin(index) + displacement,
+            Placement::CursiveAnchor(exit_glyph_index, _, _, _) => {
+                base_origin(exit_glyph_index) + displacement
+                // TODO: wrong
+            }
+        };
+        // the same rule as in `ShapedWord::get_glyphs_width`
+        let letter_spacing_for_glyph = if glyph_info.is_mark() {
+            0.0
+        } else {
+            letter_spacing_px
+        };
+
+        let glyph_scale_x = glyph_info
+            .advance
+            .get_x_size_scaled(units_per_em, font_size_px);
+        let glyph_scale_y = glyph_info
+            .advance
+            .get_y_size_scaled(units_per_em, font_size_px);
+
+        let glyph_advance_x = glyph_info
+            .advance
+            .get_x_advance_scaled(units_per_em, font_size_px);
+        let kerning_x = glyph_info.get_kerning_scaled(units_per_em, font_size_px);
+
+        let upright_offset = match glyph_info.orientation {
+            GlyphOrientation::Upright {
+                horizontal_advance,
+                vertical_origin_y,
+            } => {
+                let scale = font_size_px / units_per_em as f32;
+                Some(LogicalPosition::new(
+                    -(horizontal_advance as f32) * scale / 2.0,
+                    vertical_origin_y as f32 * scale,
+                ))
+            }
+            GlyphOrientation::Horizontal | GlyphOrientation::Sideways => None,
+        };
+
+        let inline_char = InlineGlyph {
+            bounds: LogicalRect::new(origin, LogicalSize::new(glyph_scale_x, glyph_scale_y)),
+            glyph_index: glyph_info.info.glyph.glyph_index as u32,
+            upright_offset,
+            scale: glyph_info.scale,
+        };
+
+        x_pos_in_word_px += glyph_advance_x + kerning_x + letter_spacing_for_glyph;
+
+        all_glyphs_in_this_word.push(inline_char);
+    }
+
+    all_glyphs_in_this_word
+}
+
 /// inline text so that hit-testing is easier
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 #[repr(C)]
@@ -341,8 +1111,9 @@ pub struct InlineText {
     pub font_size_px: f32,
     /// Index of the last word
     pub last_word_index: usize,
-    /// NOTE: descender is NEGATIVE (pixels from baseline to font size)
-    pub baseline_descender_px: f32,
+    /// NOTE: the lines are always laid out horizontally, vertical lines
+    /// are only rotated in `get_layouted_glyphs` / `get_physical_rect`
+    pub writing_mode: StyleWritingMode,
 }
 
 impl InlineText {
@@ -354,47 +1125,118 @@ impl InlineText {
     /// The text_origin gets added to each glyph
     ///
     /// NOTE: The lines in the text are relative to the TOP left corner (of the text, i.e.
-    /// relative to the text_origin), but the word position is relative to the BOTTOM left
-    /// corner (of the line bounds)
+    /// relative to the text_origin), the glyphs are positioned on the baseline of each line
+    ///
+    /// In vertical writing modes, this is where the (horizontally laid out) lines are
+    /// rotated: each line becomes a column from top to bottom, the columns are stacked
+    /// from right to left (`VerticalRl`) or left to right (`VerticalLr`). Sideways glyphs
+    /// are `rotated` around their `point` on the baseline, upright glyphs are centered
+    /// on the column and `point` is their baseline origin.
     pub fn get_layouted_glyphs(&self) -> LayoutedGlyphs {
-        // descender_px is NEGATIVE
-        let baseline_descender_px = LogicalPosition::new(0.0, self.baseline_descender_px);
-
         LayoutedGlyphs {
             glyphs: self
                 .lines
                 .iter()
                 .flat_map(move |line| {
-                    // bottom left corner of line rect
-                    let line_origin = line.bounds.origin;
-
-                    line.words.iter().flat_map(move |word| {
-                        let (glyphs, mut word_origin) = match word {
-                            InlineWord::Return | InlineWord::Space => {
-                                ([].as_slice(), LogicalPosition::zero())
-                            }
-                            InlineWord::Word(text_contents) => {
-                                (text_contents.glyphs.as_slice(), text_contents.bounds.origin)
-                            }
-                        };
-
-                        word_origin.y = 0.0;
-
-                        glyphs.iter().map(move |glyph| GlyphInstance {
-                            index: glyph.glyph_index,
-                            point: {
-                                line_origin
-                                    + baseline_descender_px
-                                    + word_origin
-                                    + glyph.bounds.origin
-                            },
-                            size: glyph.bounds.size,
+                    line.words
+                        .iter()
+                        .filter_map(InlineWord::get_text_content)
+                        .flat_map(move |word| {
+                            word.glyphs
+                                .iter()
+                                .map(move |glyph| self.get_glyph_instance(line, word, glyph))
                         })
-                    })
                 })
                 .collect::<Vec<GlyphInstance>>(),
         }
     }
+
+    /// Positions a glyph of a word on the line and - in vertical writing modes -
+    /// rotates the lines, so that they run from top to bottom
+    pub(crate) fn get_glyph_instance(
+        &self,
+        line: &InlineLine,
+        word: &InlineTextContents,
+        glyph: &InlineGlyph,
+    ) -> GlyphInstance {
+        use crate::css::StyleWritingMode::*;
+
+        // the glyphs of each word sit on the (possibly shifted) baseline of the word
+        let line_x = line.bounds.origin.x + word.bounds.origin.x + glyph.bounds.origin.x;
+        let baseline_y = word.baseline + glyph.bounds.origin.y;
+
+        let (point, rotated) = match self.writing_mode {
+            HorizontalTb => (
+                LogicalPosition::new(line_x, line.bounds.origin.y + baseline_y),
+                false,
+            ),
+            VerticalRl | VerticalLr => {
+                // the top of a line (where the ascenders point to) is always on the right
+                let line_right = match self.writing_mode {
+                    VerticalLr => line.bounds.origin.y + line.bounds.size.height,
+                    _ => self.content_size.height - line.bounds.origin.y,
+                };
+                match glyph.upright_offset {
+                    // upright glyphs are centered on the line
+                    Some(offset) => (
+                        LogicalPosition::new(
+                            line_right - line.bounds.size.height / 2.0 + offset.x,
+                            line_x + offset.y,
+                        ),
+                        false,
+                    ),
+                    None => (LogicalPosition::new(line_right - baseline_y, line_x), true),
+                }
+            }
+        };
+
+        GlyphInstance {
+            index: glyph.glyph_index,
+            point,
+            size: glyph.bounds.size,
+            rotated,
+            scale: glyph.scale,
+        }
+    }
+
+    /// Converts a rectangle from the coordinates of the lines (x = along the line,
+    /// y = across the lines) to the final coordinates (relative to the text origin)
+    pub fn get_physical_rect(&self, rect: LogicalRect) -> LogicalRect {
+        use crate::css::StyleWritingMode::*;
+
+        let (x, y) = (rect.origin.x, rect.origin.y);
+        let (width, height) = (rect.size.width, rect.size.height);
+        match self.writing_mode {
+            HorizontalTb => rect,
+            VerticalRl => LogicalRect::new(
+                LogicalPosition::new(self.content_size.height - y - height, x),
+                LogicalSize::new(height, width),
+            ),
+            VerticalLr => {
+                LogicalRect::new(LogicalPosition::new(y, x), LogicalSize::new(height, width))
+            }
+        }
+    }
+
+    /// Returns the final bounds of the inline objects (relative to the text origin),
+    /// so that the images / widgets can be positioned on top of the text
+    pub fn get_inline_object_bounds(&self) -> Vec<(usize, LogicalRect)> {
+        self.lines
+            .iter()
+            .flat_map(|line| {
+                line.words.iter().filter_map(move |word| match word {
+                    InlineWord::InlineObject(object) => Some((
+                        object.object_index,
+                        self.get_physical_rect(LogicalRect::new(
+                            line.bounds.origin + object.bounds.origin,
+                            object.bounds.size,
+                        )),
+                    )),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -402,6 +1244,12 @@ impl InlineText {
 pub struct InlineLine {
     pub words: Vec<InlineWord>,
     pub bounds: LogicalRect,
+    /// Distance from the top of the line to the baseline (in pixels)
+    pub baseline: f32,
+    /// Distance from the baseline to the top of the tallest inline box on the line
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the lowest inline box on the line (POSITIVE)
+    pub descent: f32,
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -409,7 +1257,9 @@ pub struct InlineLine {
 pub enum InlineWord {
     Return,
     Space,
+    Tab,
     Word(InlineTextContents),
+    InlineObject(InlineObjectContents),
 }
 
 impl InlineWord {
@@ -418,7 +1268,10 @@ impl InlineWord {
     }
     pub fn get_text_content(&self) -> Option<&InlineTextContents> {
         match self {
-            InlineWord::Return | InlineWord::Space => None,
+            InlineWord::Return
+            | InlineWord::Space
+            | InlineWord::Tab
+            | InlineWord::InlineObject(_) => None,
             InlineWord::Word(tc) => Some(tc),
         }
     }
@@ -428,12 +1281,32 @@ impl InlineWord {
 pub struct InlineGlyph {
     pub bounds: LogicalRect,
     pub glyph_index: u32,
+    /// Only set for upright glyphs in vertical text (see `GlyphOrientation::Upright`):
+    /// offset from the center of the line to the origin of the glyph
+    pub upright_offset: Option<LogicalPosition>,
+    /// Size of the glyph relative to the font size of the word (see `GlyphInfo::scale`)
+    pub scale: f32,
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct InlineTextContents {
     pub glyphs: Vec<InlineGlyph>,
     pub bounds: LogicalRect,
+    /// Distance from the top of the line to the baseline of this word (in pixels),
+    /// differs from the baseline of the line if the word is in a `vertical-align` span
+    pub baseline: f32,
+    /// Font size that the glyphs of this word are scaled to (a word with spans inside
+    /// of it is split into one `InlineTextContents` per span)
+    pub font_size_px: f32,
+}
+
+/// Position of an inline object (see `Token::InlineObject`) on a line
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct InlineObjectContents {
+    /// Index into `ResolvedTextLayoutOptions::inline_objects`
+    pub object_index: usize,
+    /// Bounds of the object, relative to the TOP left corner of the line
+    pub bounds: LogicalRect,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
@@ -468,7 +1341,33 @@ pub struct InlineTextHit {
 #[derive(Debug, Clone)]
 pub struct GlyphInfo {
     pub info: Info,
+    /// NOTE: in vertical text, the x advance is the advance along the (vertical) line
     pub advance: Advance,
+    pub orientation: GlyphOrientation,
+    /// Byte offset of the start of the cluster that the glyph belongs to (see `GlyphCluster`)
+    pub cluster: usize,
+    /// Size of the glyph relative to the font size, less than 1.0 for synthetic
+    /// small caps (the `advance` and `kerning` are already scaled)
+    pub scale: f32,
+}
+
+/// How a glyph is oriented on the line
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C, u8)]
+pub enum GlyphOrientation {
+    /// Horizontal text
+    #[default]
+    Horizontal,
+    /// Vertical text, the glyph is rotated 90° clockwise (e.g. latin text)
+    Sideways,
+    /// Vertical text, the glyph is upright and centered on the line (e.g. CJK text),
+    /// values are in unscaled font units
+    Upright {
+        /// Horizontal advance of the glyph (necessary for centering it on the line)
+        horizontal_advance: u16,
+        /// Distance from the top of the glyph box to the baseline of the glyph
+        vertical_origin_y: i16,
+    },
 }
 
 #[derive(Debug, Default, Copy, PartialEq, PartialOrd, Clone, Hash)]
@@ -507,6 +1406,15 @@ impl Advance {
 }
 
 impl GlyphInfo {
+    /// Whether the glyph is a mark that is attached to a base glyph, marks are
+    /// not letter spaced (every other glyph is, including positioned glyphs)
+    pub fn is_mark(&self) -> bool {
+        matches!(
+            self.info.placement,
+            Placement::MarkAnchor(..) | Placement::MarkOverprint(_)
+        )
+    }
+
     #[inline]
     pub const fn get_x_advance_total_unscaled(&self) -> i32 {
         self.advance.advance_x as i32 + self.info.kerning as i32